@@ -1,3 +1,4 @@
+use crate::defs::{BLACK, WHITE};
 use crate::helper::get_bitmask;
 
 pub struct Bitboard(u64);
@@ -19,3 +20,125 @@ impl Iterator for Bitboard {
         Some(square)
     }
 }
+
+// Set-wise helpers, mostly for evaluation: they work on all squares of a
+// bitboard at once. "Forward" is towards the promotion rank of `side`.
+pub const FILE_A: u64 = 0x0101_0101_0101_0101;
+pub const FILE_H: u64 = FILE_A << 7;
+pub const RANK_1: u64 = 0xff;
+
+pub fn rank_of(square: u8) -> u64 {
+    RANK_1 << (square / 8 * 8)
+}
+
+pub fn east(bb: u64) -> u64 {
+    (bb << 1) & !FILE_A
+}
+
+pub fn west(bb: u64) -> u64 {
+    (bb >> 1) & !FILE_H
+}
+
+pub fn forward(side: u8, bb: u64) -> u64 {
+    if side == WHITE {
+        bb << 8
+    } else {
+        bb >> 8
+    }
+}
+
+pub fn backward(side: u8, bb: u64) -> u64 {
+    forward(side ^ 1, bb)
+}
+
+// The squares of the bitboard and everything in front of them.
+pub fn forward_fill(side: u8, mut bb: u64) -> u64 {
+    if side == WHITE {
+        bb |= bb << 8;
+        bb |= bb << 16;
+        bb |= bb << 32;
+    } else {
+        bb |= bb >> 8;
+        bb |= bb >> 16;
+        bb |= bb >> 32;
+    }
+    bb
+}
+
+// Everything in front of the squares, the squares themselves excluded.
+pub fn front_span(side: u8, bb: u64) -> u64 {
+    forward_fill(side, forward(side, bb))
+}
+
+// Whole files with at least one square set.
+pub fn file_fill(bb: u64) -> u64 {
+    forward_fill(WHITE, bb) | forward_fill(BLACK, bb)
+}
+
+pub fn adjacent_files(bb: u64) -> u64 {
+    east(bb) | west(bb)
+}
+
+pub fn pawn_attacks(side: u8, pawns: u64) -> u64 {
+    adjacent_files(forward(side, pawns))
+}
+
+// The square of the bitboard which is the furthest back for the side.
+pub fn rearmost(side: u8, bb: u64) -> Option<u8> {
+    match (bb, side) {
+        (0, _) => None,
+        (_, WHITE) => Some(bb.trailing_zeros() as u8),
+        _ => Some(63 - bb.leading_zeros() as u8),
+    }
+}
+
+// Number of king moves between two squares.
+pub fn distance(a: u8, b: u8) -> u8 {
+    let files = (a % 8).abs_diff(b % 8);
+    let ranks = (a / 8).abs_diff(b / 8);
+    files.max(ranks)
+}
+
+// The rank as seen from the side, 0 being its own back rank.
+pub fn relative_rank(side: u8, square: u8) -> u8 {
+    if side == WHITE {
+        square / 8
+    } else {
+        7 - square / 8
+    }
+}
+
+// The square as seen from the side, with its own back rank at the bottom.
+pub fn relative_square(side: u8, square: u8) -> u8 {
+    if side == WHITE {
+        square
+    } else {
+        square ^ 56
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::defs::Squares;
+
+    #[test]
+    fn test_set_wise_helpers() {
+        let e4 = 1u64 << 28;
+        assert_eq!(east(FILE_H), 0);
+        assert_eq!(west(FILE_A), 0);
+        assert_eq!(front_span(WHITE, e4), (FILE_A << 4) & !((1u64 << 29) - 1));
+        assert_eq!(front_span(BLACK, e4), (FILE_A << 4) & ((1u64 << 28) - 1));
+        assert_eq!(file_fill(e4), (FILE_A << 4));
+        assert_eq!(pawn_attacks(WHITE, e4), (1 << 35) | (1 << 37));
+        assert_eq!(pawn_attacks(BLACK, 1 << Squares::A8), 1 << 49);
+        assert_eq!(rank_of(28), 0xff << 24);
+        assert_eq!(rearmost(WHITE, FILE_A), Some(Squares::A1));
+        assert_eq!(rearmost(BLACK, FILE_A), Some(Squares::A8));
+        assert_eq!(rearmost(BLACK, 0), None);
+        assert_eq!(distance(Squares::A1, Squares::H8), 7);
+        assert_eq!(distance(Squares::E1, 14), 2);
+        assert_eq!(relative_rank(BLACK, Squares::A7), 1);
+        assert_eq!(relative_square(BLACK, Squares::A7), Squares::A2);
+    }
+}
@@ -1,27 +1,28 @@
 mod fen;
 mod gamestate;
+mod generate;
 mod history;
+pub mod movelist;
 pub mod moves;
 pub mod pieces;
 mod playmove;
+pub mod see;
 pub mod sides;
 mod zobrist;
 
 use core::fmt;
 use std::sync::Arc;
 
-use moves::Castle::*;
-use moves::Move;
-use moves::MoveType::*;
 use zobrist::ZobristKey;
 use zobrist::ZobristRandoms;
 
 use self::{fen::*, gamestate::GameState, history::GameHistory, pieces::Pieces};
 use crate::bitboard::Bitboard;
 use crate::defs::*;
+use crate::evaluation;
+use crate::evaluation::nnue::{Accumulators, Network};
 use crate::helper;
 use crate::helper::*;
-use crate::move_generator::MoveGenerator;
 
 #[derive(Clone)]
 pub struct Board {
@@ -30,8 +31,9 @@ pub struct Board {
     pub game_state: GameState,
     pub history: GameHistory,
     pub piece_list: [Pieces; NrOf::SQUARES],
+    // only with a network for the evaluation
+    pub nnue: Option<Accumulators>,
     zobrist_randoms: Arc<ZobristRandoms>,
-    // TODO: make history start with first real gamestate instead of finst one being empty
 }
 
 impl PartialEq for Board {
@@ -49,11 +51,17 @@ impl fmt::Debug for Board {
             .field("pieces", &self.pieces)
             .field("side", &self.side)
             .field("game_state", &self.game_state)
-            .field("history", &&self.history.get_current())
+            .field("history", &self.history.get_current())
             .finish()
     }
 }
 
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Board {
     pub fn new() -> Self {
         Self {
@@ -62,6 +70,7 @@ impl Board {
             game_state: GameState::new(),
             history: GameHistory::new(),
             piece_list: [Pieces::Empty; NrOf::SQUARES],
+            nnue: None,
             zobrist_randoms: Arc::new(ZobristRandoms::new()),
         }
     }
@@ -69,7 +78,12 @@ impl Board {
         self.init_side_bb();
         self.init_piece_list();
         self.game_state.zobrist_key = self.init_zobrist_key();
-        // TODO: init psqt here as well
+        self.game_state.pawn_key = self.init_pawn_key();
+        self.init_material();
+        if let Some(nnue) = &mut self.nnue {
+            nnue.clear();
+        }
+        self.refresh_network();
     }
     pub fn reset(&mut self) {
         self.pieces = [[EMPTY; NrOf::PIECE_TYPES]; NrOf::SIDES];
@@ -106,136 +120,6 @@ impl Board {
 }
 
 impl Board {
-    pub fn get_moves(&self, mg: &MoveGenerator) -> Vec<Move> {
-        let mut moves: Vec<Move> = Vec::new();
-        let active_piece = self.side[self.we() as usize];
-        let opponent = self.side[self.opponent() as usize];
-        let we = self.side[self.we() as usize];
-        for (square, piece) in self.piece_list.iter().enumerate() {
-            let position = get_bitmask(square as u8);
-            if active_piece & position == 0 {
-                // just generate moves for the active side
-                continue;
-            }
-            let possible_moves: Bitboard = match *piece {
-                Pieces::Bishop => Bitboard::new(MoveGenerator::sliding_attacks(
-                    square as u8,
-                    &MoveGenerator::BISHOP_DIRS,
-                    opponent,
-                    we,
-                )),
-                Pieces::Rook => Bitboard::new(MoveGenerator::sliding_attacks(
-                    square as u8,
-                    &MoveGenerator::ROOK_DIRS,
-                    opponent,
-                    we,
-                )),
-                Pieces::Queen => Bitboard::new(
-                    MoveGenerator::sliding_attacks(
-                        square as u8,
-                        &MoveGenerator::BISHOP_DIRS,
-                        opponent,
-                        we,
-                    ) | MoveGenerator::sliding_attacks(
-                        square as u8,
-                        &MoveGenerator::ROOK_DIRS,
-                        opponent,
-                        we,
-                    ),
-                ),
-                _ => Bitboard::new(mg.get_moves(self.we(), piece, square as u8)),
-            };
-            for dest in possible_moves {
-                self.generate_moves(&mut moves, square as u8, dest, piece);
-            }
-        }
-        moves
-    }
-
-    fn generate_moves(&self, moves: &mut Vec<Move>, from: u8, to: u8, piece: &Pieces) {
-        let piece = *piece;
-        let opponent: u64 = self.side[self.opponent() as usize];
-        let diff: u8 = from.abs_diff(to);
-        let to_mask: u64 = get_bitmask(to);
-        let we: u8 = self.we();
-        // castle
-        if piece == Pieces::King {
-            // castle
-            if diff == 2 {
-                if we == WHITE {
-                    if from < to {
-                        moves.push(Move::new(piece, from, to, Castle(Kingside)));
-                    } else if to < from {
-                        moves.push(Move::new(piece, from, to, Castle(Queenside)));
-                    }
-                } else if we == BLACK {
-                    if to < from {
-                        moves.push(Move::new(piece, from, to, Castle(Queenside)));
-                    } else {
-                        moves.push(Move::new(piece, from, to, Castle(Kingside)));
-                    }
-                }
-            } else {
-                if opponent & to_mask > 0 {
-                    moves.push(Move::new(
-                        piece,
-                        from,
-                        to,
-                        Capture(self.piece_list[to as usize]),
-                    ));
-                } else {
-                    moves.push(Move::new(piece, from, to, Regular));
-                }
-            }
-        }
-        // pawns: promotion, capture and blocked moves
-        else if piece == Pieces::Pawn {
-            let promotion_squares = if we == WHITE {
-                PROMOTION_SQUARES_WHITE
-            } else {
-                PROMOTION_SQUARES_BLACK
-            };
-            if promotion_squares.contains(&to) && diff == 8 {
-                for promotion_piece in Pieces::iter() {
-                    if [Pieces::King, Pieces::Empty, Pieces::Pawn].contains(promotion_piece) {
-                        continue;
-                    }
-                    moves.push(Move::new(piece, from, to, Promotion(*promotion_piece)));
-                }
-            }
-            // diagonal move + capture
-            else if (diff == 9 || diff == 7) && (opponent & to_mask) > 0 {
-                moves.push(Move::new(
-                    piece,
-                    from,
-                    to,
-                    Capture(self.piece_list[to as usize]),
-                ));
-            // double move
-            } else if diff == 16 {
-                if (opponent & to_mask) == 0 && (opponent & get_bitmask(from + 8)) == 0 {
-                    moves.push(Move::new(piece, from, to, Regular));
-                }
-            // single move
-            } else if diff == 8 && ((opponent & to_mask) == 0) {
-                moves.push(Move::new(piece, from, to, Regular));
-            }
-        }
-        // capture
-        else if opponent & to_mask > 0 {
-            moves.push(Move::new(
-                piece,
-                from,
-                to,
-                Capture(self.piece_list[to as usize]),
-            ));
-        }
-        // regular
-        else {
-            moves.push(Move::new(piece, from, to, Regular));
-        }
-    }
-
     pub fn init_zobrist_key(&self) -> ZobristKey {
         let mut key: u64 = 0;
         key ^= self.zobrist_randoms.castling(self.game_state.castling);
@@ -260,6 +144,41 @@ impl Board {
         key
     }
 
+    pub fn init_pawn_key(&self) -> ZobristKey {
+        let mut key: u64 = 0;
+        for side in [WHITE, BLACK] {
+            for square in Bitboard::new(self.get_pieces(side, Pieces::Pawn)) {
+                key ^= self.zobrist_randoms.pieces(side, Pieces::Pawn, square);
+            }
+        }
+        key
+    }
+
+    // Material, piece squares and phase from scratch. During the game they
+    // are kept up to date by `put_piece` and `remove_piece`.
+    fn init_material(&mut self) {
+        self.game_state.game_phase = 0;
+        for side in [WHITE, BLACK] {
+            let (material, psqt, phase) = evaluation::count_material(self, side);
+            self.game_state.material[side as usize] = material;
+            self.game_state.psqt[side as usize] = psqt;
+            self.game_state.game_phase += phase as i16;
+        }
+    }
+
+    // Evaluates with the network from now on, or with the hand-crafted
+    // evaluation if None.
+    pub fn set_network(&mut self, network: Option<Arc<Network>>) {
+        self.nnue = network.map(|network| Accumulators::new(network, self));
+    }
+
+    pub fn refresh_network(&mut self) {
+        if let Some(mut nnue) = self.nnue.take() {
+            nnue.refresh(self);
+            self.nnue = Some(nnue);
+        }
+    }
+
     pub fn fen(fen_input: Option<String>) -> Self {
         let mut board = Board::new();
         let mut fen = String::from(FEN_START_POSITION);
@@ -328,7 +247,6 @@ impl Board {
         }
     }
     pub fn fen_setup(&mut self, fen_string: &str) -> Result<(), FenError> {
-        print!("Setting up from FEN: {}", fen_string);
         let parts = split_fen_string(fen_string)?;
         let mut tmp_board = self.clone();
         tmp_board.reset();
@@ -338,9 +256,19 @@ impl Board {
         }
         tmp_board.init();
         *self = tmp_board;
-        println!(" -> Success");
         Ok(())
     }
+    // Sets up the pieces with `stm` to move, without castling or en passant
+    // rights.
+    pub fn setup(&mut self, pieces: &[(u8, Pieces, Square)], stm: u8) {
+        self.reset();
+        self.game_state.active_color = stm;
+        self.game_state.zobrist_key = self.init_zobrist_key();
+        for &(side, piece, square) in pieces {
+            self.put_piece(side, piece, square);
+        }
+        self.refresh_network();
+    }
 
     pub fn debug_piece_list(&self) {
         println!("Piecelist");
@@ -372,24 +300,7 @@ impl Board {
         let even_file = (file & 1) == 0;
         (even_file && even_rank) || (!even_file && !even_rank)
     }
-    pub fn draw_by_insufficient_material(self) -> bool {
-        let w = self.get_bitboards(WHITE);
-        let b = self.get_bitboards(BLACK);
-
-        !(w[Pieces::Queen as usize] > 0
-            || w[Pieces::Rook as usize] > 0
-            || w[Pieces::Pawn as usize] > 0
-            || b[Pieces::Queen as usize] > 0
-            || b[Pieces::Rook as usize] > 0
-            || b[Pieces::Pawn as usize] > 0
-            || self.has_bishop_pair(BLACK)
-            || self.has_bishop_pair(WHITE)
-            || w[Pieces::Knight as usize].count_ones() >= 3
-            || b[Pieces::Knight as usize].count_ones() >= 3
-            || (w[Pieces::Knight as usize].count_ones() >= 1 && w[Pieces::Bishop as usize] >= 1)
-            || (b[Pieces::Knight as usize].count_ones() >= 1 && b[Pieces::Bishop as usize] >= 1))
-    }
-    fn has_bishop_pair(&self, side: u8) -> bool {
+    pub fn has_bishop_pair(&self, side: u8) -> bool {
         let mut bb = self.get_piece_bb(side, Pieces::Bishop);
         let mut square;
         let mut dark_squared_bishop = false;
@@ -414,6 +325,29 @@ impl Board {
     pub fn get_bitboards(&self, side: u8) -> [u64; NrOf::PIECE_TYPES] {
         self.pieces[side as usize]
     }
+    // Whether the current position occurred before since the last capture
+    // or pawn move. Only positions with the same side to move can match.
+    pub fn is_repetition(&self) -> bool {
+        let key = self.game_state.zobrist_key;
+        let count = self.history.len();
+        let reversible = (self.game_state.halfmove_clock as usize).min(count);
+        (2..=reversible)
+            .step_by(2)
+            .filter_map(|back| self.history.get_ref(count - back))
+            .any(|state| state.zobrist_key == key)
+    }
+    pub fn draw_by_fifty_moves(&self) -> bool {
+        self.game_state.halfmove_clock as usize >= MAX_MOVE_RULE
+    }
+    // Knights, bishops, rooks or queens; without them zugzwang is likely.
+    pub fn has_non_pawn_material(&self, side: u8) -> bool {
+        let pieces = self.get_bitboards(side);
+        pieces[Pieces::Knight as usize]
+            | pieces[Pieces::Bishop as usize]
+            | pieces[Pieces::Rook as usize]
+            | pieces[Pieces::Queen as usize]
+            != 0
+    }
 }
 #[cfg(test)]
 mod tests {
@@ -459,13 +393,16 @@ mod tests {
         assert!(board.has_bishop_pair(BLACK));
     }
     #[test]
-    fn test_has_sufficent_material() {
-        let board = Board::fen(None);
-        board.debug_bb();
-        assert!(!board.draw_by_insufficient_material());
-        let board = Board::fen(Some("8/8/8/5k2/8/7Q/1K6/8 w - - 0 1".to_string()));
-        assert!(!board.draw_by_insufficient_material());
-        let board = Board::fen(Some("8/8/3b4/5k2/8/8/1K6/8 w - - 0 1".to_string()));
-        assert!(board.draw_by_insufficient_material());
+    fn test_is_repetition() {
+        use crate::moves::{Move, MoveType};
+        let mut board = Board::fen(None);
+        let knight_moves = [(6, 21), (62, 45), (21, 6), (45, 62)];
+        for (i, (from, to)) in knight_moves.iter().enumerate() {
+            assert!(!board.is_repetition(), "repetition after {} moves", i);
+            board.make(Move::new(Pieces::Knight, *from, *to, MoveType::Regular));
+        }
+        assert!(board.is_repetition());
+        board.make(Move::new(Pieces::Pawn, 12, 28, MoveType::Regular));
+        assert!(!board.is_repetition());
     }
 }
@@ -59,9 +59,9 @@ pub fn color(board: &mut Board, part: &str) -> Result<(), FenError> {
         return Err(FenError::Part2);
     }
     if let 'w' = c {
-        board.game_state.active_color = WHITE as u8;
+        board.game_state.active_color = WHITE;
     } else {
-        board.game_state.active_color = BLACK as u8;
+        board.game_state.active_color = BLACK;
     }
     Ok(())
 }
@@ -254,6 +254,64 @@ pub fn create_fen_parsers() -> Vec<Parser> {
     ]
 }
 
+impl Board {
+    // The position as a FEN string, the inverse of `fen_setup`.
+    pub fn to_fen(&self) -> String {
+        let mut placement = String::new();
+        for rank in (0..8).rev() {
+            let mut empty = 0;
+            for file in 0..8 {
+                let square = rank * 8 + file;
+                let piece = self.piece_list[square];
+                if piece == Pieces::Empty {
+                    empty += 1;
+                    continue;
+                }
+                if empty > 0 {
+                    placement.push_str(&empty.to_string());
+                    empty = 0;
+                }
+                let symbol = piece.to_string();
+                if self.side[WHITE as usize] & (1 << square) != 0 {
+                    placement.push_str(&symbol);
+                } else {
+                    placement.push_str(&symbol.to_lowercase());
+                }
+            }
+            if empty > 0 {
+                placement.push_str(&empty.to_string());
+            }
+            if rank > 0 {
+                placement.push(SPLITTER);
+            }
+        }
+        let color = if self.we() == WHITE { "w" } else { "b" };
+        let castling: String = [
+            (Castling::WK, 'K'),
+            (Castling::WQ, 'Q'),
+            (Castling::BK, 'k'),
+            (Castling::BQ, 'q'),
+        ]
+        .iter()
+        .filter(|&&(right, _)| self.game_state.castling & right != 0)
+        .map(|&(_, c)| c)
+        .collect();
+        let castling = if castling.is_empty() {
+            String::from(DASH)
+        } else {
+            castling
+        };
+        let en_passant = match self.game_state.en_passant {
+            Some(square) => square_num_to_algebraic(square),
+            None => "-",
+        };
+        format!(
+            "{placement} {color} {castling} {en_passant} {} {}",
+            self.game_state.halfmove_clock, self.game_state.fullmove_number
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -296,11 +354,11 @@ mod tests {
     fn test_color_valid() {
         let mut board = Board::new();
         assert!(color(&mut board, "w").is_ok());
-        assert_eq!(board.game_state.active_color, WHITE as u8);
+        assert_eq!(board.game_state.active_color, WHITE);
 
         let mut board = Board::new();
         assert!(color(&mut board, "b").is_ok());
-        assert_eq!(board.game_state.active_color, BLACK as u8);
+        assert_eq!(board.game_state.active_color, BLACK);
     }
 
     #[test]
@@ -485,4 +543,16 @@ mod tests {
         assert!(en_passant(&mut board, "c8").is_err());
         assert!(en_passant(&mut board, "b5").is_err());
     }
+
+    #[test]
+    fn test_to_fen() {
+        for fen in [
+            FEN_START_POSITION,
+            "r3k2r/pPppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/8/PPPP1PPP/RNBQKBNR w KQkq c6 0 2",
+            "8/8/8/8/8/8/8/K1k5 b - - 37 80",
+        ] {
+            assert_eq!(Board::fen(Some(fen.to_string())).to_fen(), fen);
+        }
+    }
 }
@@ -1,7 +1,6 @@
-use std::sync::Arc;
-
-use super::{moves::*, zobrist::*};
+use super::moves::*;
 use crate::defs::*;
+use crate::evaluation::score::Score;
 
 #[derive(Copy, Clone, Debug)]
 pub struct GameState {
@@ -11,9 +10,10 @@ pub struct GameState {
     pub en_passant: Option<u8>,
     pub fullmove_number: u16,
     pub zobrist_key: u64,
-    pub game_phase: i16,                    // opening/midgame/endgame
-    pub material: [u16; NrOf::PIECE_TYPES], // piece values
-    pub psqt: [i16; NrOf::PIECE_TYPES],     // square values per piece
+    pub pawn_key: u64,                  // zobrist key of the pawns only
+    pub game_phase: i16,                // opening/midgame/endgame
+    pub material: [Score; NrOf::SIDES], // piece values per side
+    pub psqt: [Score; NrOf::SIDES],     // square values per side
     pub next_move: Move,
 }
 
@@ -28,6 +28,7 @@ impl PartialEq for GameState {
             && self.en_passant == other.en_passant
             && self.fullmove_number == other.fullmove_number
             && self.zobrist_key == other.zobrist_key
+            && self.pawn_key == other.pawn_key
             && self.game_phase == other.game_phase
             && self.material == other.material
             && self.psqt == other.psqt
@@ -43,10 +44,10 @@ impl GameState {
             halfmove_clock: 0,
             en_passant: None,
             zobrist_key: 0, // TODO: replace with actual u64 from generated from ZobristRandoms
+            pawn_key: 0,
             fullmove_number: 0,
-            // TODO: no clue what material stores
-            material: [0; NrOf::PIECE_TYPES],
-            psqt: [0; NrOf::PIECE_TYPES],
+            material: [Score::default(); NrOf::SIDES],
+            psqt: [Score::default(); NrOf::SIDES],
             game_phase: 0,
             next_move: Move::default(),
         }
@@ -57,14 +58,15 @@ impl GameState {
         self.halfmove_clock = 0;
         self.en_passant = None;
         self.zobrist_key = 0;
+        self.pawn_key = 0;
         self.fullmove_number = 0;
-        self.material = [0u16; NrOf::PIECE_TYPES];
-        self.psqt = [0; NrOf::PIECE_TYPES];
+        self.material = [Score::default(); NrOf::SIDES];
+        self.psqt = [Score::default(); NrOf::SIDES];
         self.game_phase = 0;
         self.next_move = Move::default();
     }
 
-    pub(crate) fn debug(&self) {
+    pub fn debug(&self) {
         println!("Game State Debug Information:");
         println!("--------------------------------");
         println!("Active Color: {}", self.active_color);
@@ -73,6 +75,7 @@ impl GameState {
         println!("En Passant: {:?}", self.en_passant);
         println!("Fullmove Number: {}", self.fullmove_number);
         println!("Zobrist Key: {}", self.zobrist_key);
+        println!("Pawn Key: {}", self.pawn_key);
         println!("Game Phase: {}", self.game_phase);
         println!("Material: {:?}", self.material);
         println!("PSQT: {:?}", self.psqt);
@@ -0,0 +1,625 @@
+use super::movelist::MoveList;
+use super::moves::{
+    Castle::{Kingside, Queenside},
+    Move,
+    MoveType::*,
+};
+use super::{get_bitmask, Board, Pieces};
+use crate::bitboard::Bitboard;
+use crate::defs::*;
+use crate::move_generator::MoveGenerator;
+
+const PROMOTION_PIECES: [Pieces; 4] = [Pieces::Queen, Pieces::Rook, Pieces::Bishop, Pieces::Knight];
+const NON_PAWN_PIECES: [Pieces; 5] = [
+    Pieces::Knight,
+    Pieces::Bishop,
+    Pieces::Rook,
+    Pieces::Queen,
+    Pieces::King,
+];
+const RANK_1: u64 = 0xFF;
+const RANK_8: u64 = 0xFF << 56;
+const RANK_3: u64 = 0xFF << 16;
+const RANK_6: u64 = 0xFF << 40;
+
+// Move generation is split into stages, so search can try the TT move,
+// good captures and killers before it ever generates the quiet moves. All
+// generators produce pseudo-legal moves and APPEND to the given list;
+// `make_legal` filters out moves which leave the own king in check.
+impl Board {
+    // All pseudo-legal moves of the side to move.
+    pub fn get_moves(&self, mg: &MoveGenerator, moves: &mut MoveList) {
+        moves.clear();
+        self.generate_captures(mg, moves);
+        self.generate_quiets(mg, moves);
+    }
+
+    // Captures, en-passant and all promotions (including non-captures).
+    pub fn generate_captures(&self, mg: &MoveGenerator, moves: &mut MoveList) {
+        self.generate_pieces(mg, moves, !EMPTY, true, false);
+        self.generate_pawns(mg, moves, !EMPTY, true, false);
+    }
+
+    // Non-capturing, non-promoting moves, including castling.
+    pub fn generate_quiets(&self, mg: &MoveGenerator, moves: &mut MoveList) {
+        self.generate_pieces(mg, moves, !EMPTY, false, true);
+        self.generate_pawns(mg, moves, !EMPTY, false, true);
+        self.generate_castling(mg, moves);
+    }
+
+    // Moves which may get the side to move out of check: king moves to
+    // squares not attacked, and on a single check captures of the checker
+    // or interpositions. Only call this when in check.
+    pub fn generate_evasions(&self, mg: &MoveGenerator, moves: &mut MoveList) {
+        let we = self.we();
+        let king = self.king(we);
+        let checkers =
+            self.attackers_to(mg, king, self.occupancy()) & self.side[self.opponent() as usize];
+        debug_assert!(checkers != 0, "generate_evasions called while not in check");
+
+        // the king itself must not shadow the squares behind it
+        let occupancy = self.occupancy() ^ get_bitmask(king);
+        let targets = mg.king_attacks(king) & !self.side[we as usize];
+        for to in Bitboard::new(targets) {
+            if !self.square_attacked_with(mg, to, self.opponent(), occupancy) {
+                self.push_move(moves, Pieces::King, king, to);
+            }
+        }
+
+        // on a double check only the king can move
+        if checkers.count_ones() > 1 {
+            return;
+        }
+        let checker = checkers.trailing_zeros() as Square;
+        let targets = get_bitmask(checker) | self.between(king, checker);
+        for piece in NON_PAWN_PIECES.iter().take(4) {
+            self.generate_piece(mg, moves, *piece, targets, true, true);
+        }
+        self.generate_pawns(mg, moves, targets, true, true);
+        // en-passant may remove the checking pawn or block a slider
+        if let Some(ep) = self.game_state.en_passant {
+            if self.ep_victim(ep) == checker || targets & get_bitmask(ep) != 0 {
+                self.generate_en_passant(mg, moves);
+            }
+        }
+    }
+
+    // Quiet moves which give check, for use in the first ply of quiescence.
+    pub fn generate_quiet_checks(&self, mg: &MoveGenerator, moves: &mut MoveList) {
+        let mut quiets = MoveList::new();
+        self.generate_quiets(mg, &mut quiets);
+        for m in quiets.iter() {
+            if self.gives_check(mg, *m) {
+                moves.push(*m);
+            }
+        }
+    }
+
+    // Only legal moves, for the protocol and perft. Not used in search.
+    pub fn get_legal_moves(&mut self, mg: &MoveGenerator, moves: &mut MoveList) {
+        let mut pseudo_legal = MoveList::new();
+        self.get_moves(mg, &mut pseudo_legal);
+        moves.clear();
+        for m in pseudo_legal.iter() {
+            if self.make_legal(*m, mg) {
+                self.unmake();
+                moves.push(*m);
+            }
+        }
+    }
+
+    // Cheap check whether a move (e.g. from the transposition table or a
+    // killer slot) could have been generated in this position.
+    pub fn is_pseudo_legal(&self, mg: &MoveGenerator, m: Move) -> bool {
+        if m.is_null() {
+            return false;
+        }
+        let we = self.we();
+        let from = get_bitmask(m.from());
+        let to = get_bitmask(m.to());
+        if self.side[we as usize] & from == 0 || self.piece_list[m.from() as usize] != m.piece() {
+            return false;
+        }
+        match m.kind() {
+            // rare enough to simply generate them
+            Castle(_) => {
+                let mut moves = MoveList::new();
+                self.generate_castling(mg, &mut moves);
+                return moves.contains(m);
+            }
+            EnPassant | Promotion(_) | PromotionCapture(_, _) => {
+                let mut moves = MoveList::new();
+                self.generate_captures(mg, &mut moves);
+                return moves.contains(m);
+            }
+            Regular => {
+                if self.occupancy() & to != 0 {
+                    return false;
+                }
+            }
+            Capture(captured) => {
+                if self.get_pieces(self.opponent(), captured) & to == 0 {
+                    return false;
+                }
+            }
+        }
+
+        if m.piece() != Pieces::Pawn {
+            return mg.attacks(m.piece(), m.from(), self.occupancy()) & to != 0;
+        }
+        let promotion_rank = if we == WHITE { RANK_8 } else { RANK_1 };
+        if to & promotion_rank != 0 {
+            return false;
+        }
+        if m.is_capture() {
+            return mg.pawn_attacks(we, m.from()) & to != 0;
+        }
+        // pawns never stand on the first or last rank, so these can't overflow
+        let (single, double, double_rank) = if we == WHITE {
+            (m.from() + 8, m.from() + 16, RANK_3)
+        } else {
+            (m.from() - 8, m.from().wrapping_sub(16), RANK_6)
+        };
+        let single_mask = get_bitmask(single);
+        m.to() == single
+            || (m.to() == double
+                && single_mask & double_rank != 0
+                && self.occupancy() & single_mask == 0)
+    }
+
+    fn generate_pieces(
+        &self,
+        mg: &MoveGenerator,
+        moves: &mut MoveList,
+        targets: u64,
+        captures: bool,
+        quiets: bool,
+    ) {
+        for piece in NON_PAWN_PIECES {
+            self.generate_piece(mg, moves, piece, targets, captures, quiets);
+        }
+    }
+
+    fn generate_piece(
+        &self,
+        mg: &MoveGenerator,
+        moves: &mut MoveList,
+        piece: Pieces,
+        targets: u64,
+        captures: bool,
+        quiets: bool,
+    ) {
+        let occupancy = self.occupancy();
+        let mut allowed = EMPTY;
+        if captures {
+            allowed |= self.side[self.opponent() as usize];
+        }
+        if quiets {
+            allowed |= !occupancy;
+        }
+        for from in Bitboard::new(self.get_pieces(self.we(), piece)) {
+            let to_squares = mg.attacks(piece, from, occupancy) & allowed & targets;
+            for to in Bitboard::new(to_squares) {
+                self.push_move(moves, piece, from, to);
+            }
+        }
+    }
+
+    fn generate_pawns(
+        &self,
+        mg: &MoveGenerator,
+        moves: &mut MoveList,
+        targets: u64,
+        captures: bool,
+        quiets: bool,
+    ) {
+        let we = self.we();
+        let empty = !self.occupancy();
+        let opponent = self.side[self.opponent() as usize];
+        let pawns = self.get_pieces(we, Pieces::Pawn);
+        let (promotion_rank, double_rank) = if we == WHITE {
+            (RANK_8, RANK_3)
+        } else {
+            (RANK_1, RANK_6)
+        };
+        let forward = |bb: u64| if we == WHITE { bb << 8 } else { bb >> 8 };
+
+        let single = forward(pawns) & empty;
+        let double = forward(single & double_rank) & empty;
+
+        // promotions belong to the capture stage
+        if captures {
+            for to in Bitboard::new(single & promotion_rank & targets) {
+                let from = if we == WHITE { to - 8 } else { to + 8 };
+                for promoted in PROMOTION_PIECES {
+                    moves.push(Move::new(Pieces::Pawn, from, to, Promotion(promoted)));
+                }
+            }
+            for from in Bitboard::new(pawns) {
+                let to_squares = mg.pawn_attacks(we, from) & opponent & targets;
+                for to in Bitboard::new(to_squares) {
+                    let captured = self.piece_list[to as usize];
+                    if promotion_rank & get_bitmask(to) != 0 {
+                        for promoted in PROMOTION_PIECES {
+                            moves.push(Move::new(
+                                Pieces::Pawn,
+                                from,
+                                to,
+                                PromotionCapture(promoted, captured),
+                            ));
+                        }
+                    } else {
+                        moves.push(Move::new(Pieces::Pawn, from, to, Capture(captured)));
+                    }
+                }
+            }
+            // during evasions en-passant is handled separately
+            if targets == !EMPTY {
+                self.generate_en_passant(mg, moves);
+            }
+        }
+        if quiets {
+            for to in Bitboard::new(single & !promotion_rank & targets) {
+                let from = if we == WHITE { to - 8 } else { to + 8 };
+                moves.push(Move::new(Pieces::Pawn, from, to, Regular));
+            }
+            for to in Bitboard::new(double & targets) {
+                let from = if we == WHITE { to - 16 } else { to + 16 };
+                moves.push(Move::new(Pieces::Pawn, from, to, Regular));
+            }
+        }
+    }
+
+    fn generate_en_passant(&self, mg: &MoveGenerator, moves: &mut MoveList) {
+        if let Some(ep) = self.game_state.en_passant {
+            let we = self.we();
+            // our pawns which attack the ep square are those a pawn of the
+            // opponent would attack from it
+            let attackers = mg.pawn_attacks(we ^ 1, ep) & self.get_pieces(we, Pieces::Pawn);
+            for from in Bitboard::new(attackers) {
+                moves.push(Move::new(Pieces::Pawn, from, ep, EnPassant));
+            }
+        }
+    }
+
+    fn generate_castling(&self, mg: &MoveGenerator, moves: &mut MoveList) {
+        let we = self.we();
+        let opponent = self.opponent();
+        let occupancy = self.occupancy();
+        let (king_start, kingside, queenside) = if we == WHITE {
+            (Squares::E1, Castling::WK, Castling::WQ)
+        } else {
+            (Squares::E8, Castling::BK, Castling::BQ)
+        };
+        let castling = self.game_state.castling;
+        if castling & (kingside | queenside) == 0
+            || self.king(we) != king_start
+            || self.square_attacked(mg, king_start, opponent)
+        {
+            return;
+        }
+        let rooks = self.get_pieces(we, Pieces::Rook);
+        if castling & kingside != 0
+            && rooks & get_bitmask(king_start + 3) != 0
+            && occupancy & (get_bitmask(king_start + 1) | get_bitmask(king_start + 2)) == 0
+            && !self.square_attacked(mg, king_start + 1, opponent)
+            && !self.square_attacked(mg, king_start + 2, opponent)
+        {
+            moves.push(Move::new(
+                Pieces::King,
+                king_start,
+                king_start + 2,
+                Castle(Kingside),
+            ));
+        }
+        if castling & queenside != 0
+            && rooks & get_bitmask(king_start - 4) != 0
+            && occupancy
+                & (get_bitmask(king_start - 1)
+                    | get_bitmask(king_start - 2)
+                    | get_bitmask(king_start - 3))
+                == 0
+            && !self.square_attacked(mg, king_start - 1, opponent)
+            && !self.square_attacked(mg, king_start - 2, opponent)
+        {
+            moves.push(Move::new(
+                Pieces::King,
+                king_start,
+                king_start - 2,
+                Castle(Queenside),
+            ));
+        }
+    }
+
+    // regular move or capture, depending on the destination square
+    fn push_move(&self, moves: &mut MoveList, piece: Pieces, from: Square, to: Square) {
+        if self.side[self.opponent() as usize] & get_bitmask(to) != 0 {
+            let captured = self.piece_list[to as usize];
+            moves.push(Move::new(piece, from, to, Capture(captured)));
+        } else {
+            moves.push(Move::new(piece, from, to, Regular));
+        }
+    }
+
+    // square of the pawn which is captured when moving to the ep square
+    fn ep_victim(&self, ep: Square) -> Square {
+        if self.we() == WHITE {
+            ep - 8
+        } else {
+            ep + 8
+        }
+    }
+
+    // squares strictly between two squares on a common line, otherwise 0
+    pub fn between(&self, a: Square, b: Square) -> u64 {
+        let occupancy = get_bitmask(a) | get_bitmask(b);
+        let same_rank = a / 8 == b / 8;
+        let same_file = a % 8 == b % 8;
+        if same_rank || same_file {
+            let rays = MoveGenerator::rook_attacks(a, occupancy)
+                & MoveGenerator::rook_attacks(b, occupancy);
+            if same_rank {
+                rays & (0xFF << (8 * (a / 8)))
+            } else {
+                rays & (0x0101_0101_0101_0101 << (a % 8))
+            }
+        } else if (a / 8).abs_diff(b / 8) == (a % 8).abs_diff(b % 8) {
+            MoveGenerator::bishop_attacks(a, occupancy)
+                & MoveGenerator::bishop_attacks(b, occupancy)
+        } else {
+            EMPTY
+        }
+    }
+
+    // All pieces of both sides attacking `square` given an occupancy.
+    pub fn attackers_to(&self, mg: &MoveGenerator, square: Square, occupancy: u64) -> u64 {
+        let w = &self.pieces[WHITE as usize];
+        let b = &self.pieces[BLACK as usize];
+        let knights = w[Pieces::Knight as usize] | b[Pieces::Knight as usize];
+        let kings = w[Pieces::King as usize] | b[Pieces::King as usize];
+        let diagonal = w[Pieces::Bishop as usize]
+            | b[Pieces::Bishop as usize]
+            | w[Pieces::Queen as usize]
+            | b[Pieces::Queen as usize];
+        let orthogonal = w[Pieces::Rook as usize]
+            | b[Pieces::Rook as usize]
+            | w[Pieces::Queen as usize]
+            | b[Pieces::Queen as usize];
+
+        (mg.pawn_attacks(BLACK, square) & w[Pieces::Pawn as usize])
+            | (mg.pawn_attacks(WHITE, square) & b[Pieces::Pawn as usize])
+            | (mg.knight_attacks(square) & knights)
+            | (mg.king_attacks(square) & kings)
+            | (MoveGenerator::bishop_attacks(square, occupancy) & diagonal)
+            | (MoveGenerator::rook_attacks(square, occupancy) & orthogonal)
+    }
+
+    pub fn square_attacked(&self, mg: &MoveGenerator, square: Square, attacker: u8) -> bool {
+        self.square_attacked_with(mg, square, attacker, self.occupancy())
+    }
+
+    fn square_attacked_with(
+        &self,
+        mg: &MoveGenerator,
+        square: Square,
+        attacker: u8,
+        occupancy: u64,
+    ) -> bool {
+        let pieces = &self.pieces[attacker as usize];
+        let queens = pieces[Pieces::Queen as usize];
+        mg.pawn_attacks(attacker ^ 1, square) & pieces[Pieces::Pawn as usize] != 0
+            || mg.knight_attacks(square) & pieces[Pieces::Knight as usize] != 0
+            || mg.king_attacks(square) & pieces[Pieces::King as usize] != 0
+            || MoveGenerator::bishop_attacks(square, occupancy)
+                & (pieces[Pieces::Bishop as usize] | queens)
+                != 0
+            || MoveGenerator::rook_attacks(square, occupancy)
+                & (pieces[Pieces::Rook as usize] | queens)
+                != 0
+    }
+
+    pub fn in_check(&self, mg: &MoveGenerator) -> bool {
+        self.square_attacked(mg, self.king(self.we()), self.opponent())
+    }
+
+    // Whether a pseudo-legal move checks the opponent king, directly or
+    // by discovery.
+    pub fn gives_check(&self, mg: &MoveGenerator, m: Move) -> bool {
+        let we = self.we();
+        let their_king = self.king(self.opponent());
+        let from = get_bitmask(m.from());
+        let to = get_bitmask(m.to());
+        let mut occupancy = (self.occupancy() & !from) | to;
+        if let EnPassant = m.kind() {
+            occupancy &= !get_bitmask(self.ep_victim(m.to()));
+        }
+
+        // direct check by the moved (or promoted, or castled) piece
+        let (piece, square) = match m.kind() {
+            Castle(Kingside) => (Pieces::Rook, m.to() - 1),
+            Castle(Queenside) => (Pieces::Rook, m.to() + 1),
+            _ => (m.promoted().unwrap_or(m.piece()), m.to()),
+        };
+        if let Castle(_) = m.kind() {
+            let rook_from = if m.to() > m.from() {
+                m.to() + 1
+            } else {
+                m.to() - 2
+            };
+            occupancy = (occupancy & !get_bitmask(rook_from)) | get_bitmask(square);
+        }
+        let direct = match piece {
+            Pieces::Pawn => mg.pawn_attacks(we, square),
+            _ => mg.attacks(piece, square, occupancy),
+        };
+        if direct & get_bitmask(their_king) != 0 {
+            return true;
+        }
+
+        // discovered check by a slider behind the moved piece
+        let ours = &self.pieces[we as usize];
+        let queens = ours[Pieces::Queen as usize];
+        let diagonal = (ours[Pieces::Bishop as usize] | queens) & !from;
+        let orthogonal = (ours[Pieces::Rook as usize] | queens) & !from;
+        MoveGenerator::bishop_attacks(their_king, occupancy) & diagonal != 0
+            || MoveGenerator::rook_attacks(their_king, occupancy) & orthogonal != 0
+    }
+
+    pub fn perft(&mut self, mg: &MoveGenerator, depth: u8) -> u64 {
+        if depth == 0 {
+            return 1;
+        }
+        let mut moves = MoveList::new();
+        self.get_moves(mg, &mut moves);
+        let mut nodes = 0;
+        for m in moves.iter() {
+            if self.make_legal(*m, mg) {
+                nodes += self.perft(mg, depth - 1);
+                self.unmake();
+            }
+        }
+        nodes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn perft(fen: &str, depth: u8) -> u64 {
+        let mg = MoveGenerator::new();
+        let mut board = Board::fen(Some(fen.to_string()));
+        board.perft(&mg, depth)
+    }
+
+    #[test]
+    fn test_perft_start_position() {
+        assert_eq!(perft(FEN_START_POSITION, 1), 20);
+        assert_eq!(perft(FEN_START_POSITION, 2), 400);
+        assert_eq!(perft(FEN_START_POSITION, 3), 8902);
+    }
+
+    #[test]
+    fn test_perft_kiwipete() {
+        let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        assert_eq!(perft(fen, 1), 48);
+        assert_eq!(perft(fen, 2), 2039);
+        assert_eq!(perft(fen, 3), 97862);
+    }
+
+    #[test]
+    fn test_perft_tricky_positions() {
+        assert_eq!(perft("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", 4), 43238);
+        let fen = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+        assert_eq!(perft(fen, 3), 9467);
+        let fen = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+        assert_eq!(perft(fen, 3), 62379);
+    }
+
+    // every stage together must produce exactly the full move list
+    fn assert_stages_complete(fen: &str) {
+        let mg = MoveGenerator::new();
+        let board = Board::fen(Some(fen.to_string()));
+        let mut all = MoveList::new();
+        board.get_moves(&mg, &mut all);
+        let mut staged = MoveList::new();
+        board.generate_captures(&mg, &mut staged);
+        let captures = staged.len();
+        board.generate_quiets(&mg, &mut staged);
+        assert_eq!(all.len(), staged.len());
+        for (i, m) in staged.iter().enumerate() {
+            assert!(all.contains(*m));
+            assert_eq!(i < captures, !m.is_quiet());
+        }
+    }
+
+    #[test]
+    fn test_staged_generation() {
+        assert_stages_complete(FEN_START_POSITION);
+        assert_stages_complete(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        );
+        assert_stages_complete("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1");
+    }
+
+    // evasions followed by the legality check must equal all legal moves
+    fn assert_evasions_complete(fen: &str) {
+        let mg = MoveGenerator::new();
+        let mut board = Board::fen(Some(fen.to_string()));
+        assert!(board.in_check(&mg));
+        let mut legal = MoveList::new();
+        board.get_legal_moves(&mg, &mut legal);
+        let mut evasions = MoveList::new();
+        board.generate_evasions(&mg, &mut evasions);
+        let mut count = 0;
+        for m in evasions.iter() {
+            if board.make_legal(*m, &mg) {
+                board.unmake();
+                assert!(legal.contains(*m));
+                count += 1;
+            }
+        }
+        assert_eq!(count, legal.len());
+    }
+
+    #[test]
+    fn test_evasions() {
+        // rook check, can block, capture or step aside
+        assert_evasions_complete("4k3/8/8/8/8/2n5/8/r3K2R w K - 0 1");
+        // double check
+        assert_evasions_complete("4k3/8/8/8/1b6/3n4/8/4K3 w - - 0 1");
+        // pawn check which can be captured en-passant
+        assert_evasions_complete("8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1");
+    }
+
+    #[test]
+    fn test_quiet_checks() {
+        let mg = MoveGenerator::new();
+        let board = Board::fen(Some("4k3/8/8/8/8/8/8/R3K1N1 w Q - 0 1".to_string()));
+        let mut checks = MoveList::new();
+        board.generate_quiet_checks(&mg, &mut checks);
+        // Ra8+ checks along the back rank, castling puts the rook on d1
+        assert!(!checks.contains(Move::new(Pieces::King, 4, 2, Castle(Queenside))));
+        assert!(checks.contains(Move::new(Pieces::Rook, 0, 56, Regular)));
+        for m in checks.iter() {
+            assert!(board.gives_check(&mg, *m));
+            assert!(m.is_quiet());
+        }
+        // discovered check by moving the bishop out of the rook's line
+        let board = Board::fen(Some("4k3/8/8/8/4B3/8/8/4R1K1 w - - 0 1".to_string()));
+        let mut checks = MoveList::new();
+        board.generate_quiet_checks(&mg, &mut checks);
+        assert!(checks.contains(Move::new(Pieces::Bishop, 28, 19, Regular)));
+        assert!(!checks.contains(Move::new(Pieces::King, 6, 7, Regular)));
+    }
+
+    #[test]
+    fn test_is_pseudo_legal() {
+        let mg = MoveGenerator::new();
+        let fens = [
+            FEN_START_POSITION,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        ];
+        // moves of one position checked against every other position
+        for source in fens {
+            let mut candidates = MoveList::new();
+            Board::fen(Some(source.to_string())).get_moves(&mg, &mut candidates);
+            for target in fens {
+                let board = Board::fen(Some(target.to_string()));
+                let mut moves = MoveList::new();
+                board.get_moves(&mg, &mut moves);
+                for m in candidates.iter() {
+                    assert_eq!(
+                        board.is_pseudo_legal(&mg, *m),
+                        moves.contains(*m),
+                        "{:?}",
+                        m
+                    );
+                }
+            }
+        }
+    }
+}
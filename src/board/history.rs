@@ -1,4 +1,4 @@
-use super::{gamestate, GameState};
+use super::GameState;
 use crate::defs::*;
 
 #[derive(Clone, Copy, Debug)]
@@ -16,7 +16,7 @@ impl GameHistory {
 
     // just changing count var - not deleting values
     pub fn push(&mut self, new_state: GameState) {
-        self.list[self.count + 1] = new_state;
+        self.list[self.count] = new_state;
         self.count += 1;
     }
     pub fn pop(&mut self) -> Option<GameState> {
@@ -30,15 +30,11 @@ impl GameHistory {
     pub fn count(&self) -> usize {
         self.count
     }
-    pub fn get_current(&self) -> GameState {
-        self.list[self.count]
-    }
-    pub fn unmake(&mut self) -> GameState {
-        self.count -= 1;
-        self.get_current()
+    pub fn get_current(&self) -> Option<&GameState> {
+        self.count.checked_sub(1).map(|last| &self.list[last])
     }
     pub fn get_ref(&self, num: usize) -> Option<&GameState> {
-        if num <= self.count {
+        if num < self.count {
             Some(&self.list[num])
         } else {
             None
@@ -50,7 +46,4 @@ impl GameHistory {
     pub fn clear(&mut self) {
         self.count = 0;
     }
-    pub fn previous(&self) -> Option<&GameState> {
-        self.get_ref(self.count - 1)
-    }
 }
@@ -0,0 +1,162 @@
+use std::ops::{Index, IndexMut};
+
+use super::moves::Move;
+use crate::defs::MAX_LEGAL_MOVES;
+
+// Fixed capacity list of moves living on the stack. The move generator
+// writes into a list handed to it by the caller, so generating moves
+// millions of times during search never touches the allocator. Every
+// move carries a score slot which move ordering can fill in.
+#[derive(Clone, Copy)]
+pub struct MoveList {
+    list: [Move; MAX_LEGAL_MOVES],
+    scores: [i32; MAX_LEGAL_MOVES],
+    count: usize,
+}
+
+impl Default for MoveList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MoveList {
+    pub fn new() -> Self {
+        Self {
+            list: [Move::default(); MAX_LEGAL_MOVES],
+            scores: [0; MAX_LEGAL_MOVES],
+            count: 0,
+        }
+    }
+
+    pub fn push(&mut self, m: Move) {
+        debug_assert!(self.count < MAX_LEGAL_MOVES, "MoveList is full");
+        self.list[self.count] = m;
+        self.scores[self.count] = 0;
+        self.count += 1;
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    // just resetting count - not deleting values
+    pub fn clear(&mut self) {
+        self.count = 0;
+    }
+
+    pub fn get(&self, index: usize) -> Move {
+        self.list[index]
+    }
+
+    pub fn score(&self, index: usize) -> i32 {
+        self.scores[index]
+    }
+
+    pub fn set_score(&mut self, index: usize, score: i32) {
+        self.scores[index] = score;
+    }
+
+    // swaps both the moves and their scores, used for ordering
+    pub fn swap(&mut self, a: usize, b: usize) {
+        self.list.swap(a, b);
+        self.scores.swap(a, b);
+    }
+
+    // Selection sort step: finds the highest scored move in [index..len]
+    // and swaps it into `index`. Cheaper than sorting the whole list when
+    // a cutoff happens after the first few moves.
+    pub fn pick(&mut self, index: usize) -> Move {
+        let mut best = index;
+        for i in (index + 1)..self.count {
+            if self.scores[i] > self.scores[best] {
+                best = i;
+            }
+        }
+        self.swap(index, best);
+        self.list[index]
+    }
+
+    pub fn contains(&self, m: Move) -> bool {
+        self.iter().any(|&x| x == m)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Move> {
+        self.list[..self.count].iter()
+    }
+
+    pub fn as_slice(&self) -> &[Move] {
+        &self.list[..self.count]
+    }
+}
+
+impl Index<usize> for MoveList {
+    type Output = Move;
+    fn index(&self, index: usize) -> &Move {
+        &self.list[..self.count][index]
+    }
+}
+
+impl IndexMut<usize> for MoveList {
+    fn index_mut(&mut self, index: usize) -> &mut Move {
+        &mut self.list[..self.count][index]
+    }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+    type Item = &'a Move;
+    type IntoIter = std::slice::Iter<'a, Move>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::moves::MoveType;
+    use crate::board::pieces::Pieces;
+
+    #[test]
+    fn test_push_and_index() {
+        let mut list = MoveList::new();
+        assert!(list.is_empty());
+        let a = Move::new(Pieces::Pawn, 12, 28, MoveType::Regular);
+        let b = Move::new(Pieces::Knight, 1, 18, MoveType::Regular);
+        list.push(a);
+        list.push(b);
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0], a);
+        assert_eq!(list.get(1), b);
+        assert!(list.contains(b));
+        assert_eq!(list.iter().count(), 2);
+        list.clear();
+        assert!(list.is_empty());
+        assert!(!list.contains(a));
+    }
+
+    #[test]
+    fn test_swap_and_pick() {
+        let mut list = MoveList::new();
+        let a = Move::new(Pieces::Pawn, 12, 28, MoveType::Regular);
+        let b = Move::new(Pieces::Knight, 1, 18, MoveType::Regular);
+        let c = Move::new(Pieces::Knight, 6, 21, MoveType::Regular);
+        list.push(a);
+        list.push(b);
+        list.push(c);
+        list.set_score(0, 10);
+        list.set_score(1, 5);
+        list.set_score(2, 30);
+        assert_eq!(list.pick(0), c);
+        assert_eq!(list.score(0), 30);
+        assert_eq!(list.pick(1), a);
+        assert_eq!(list.pick(2), b);
+        list.swap(0, 2);
+        assert_eq!(list[0], b);
+        assert_eq!(list.score(0), 5);
+    }
+}
@@ -1,6 +1,7 @@
 use core::fmt;
 
-use crate::{pieces::Pieces, Square, Squares};
+use crate::helper::square_num_to_algebraic;
+use crate::pieces::Pieces;
 
 #[derive(Clone, Copy, PartialEq)]
 pub struct Move {
@@ -14,6 +15,9 @@ pub enum MoveType {
     Regular,
     Capture(Pieces),
     Promotion(Pieces),
+    // promoted piece, captured piece
+    PromotionCapture(Pieces, Pieces),
+    EnPassant,
     Castle(Castle),
 }
 
@@ -23,8 +27,8 @@ pub enum Castle {
     Queenside,
 }
 
-impl Move {
-    pub fn default() -> Self {
+impl Default for Move {
+    fn default() -> Self {
         Self {
             piece: Pieces::Empty,
             from: 0,
@@ -32,6 +36,9 @@ impl Move {
             kind: MoveType::Regular,
         }
     }
+}
+
+impl Move {
     pub fn new(piece: Pieces, from: u8, to: u8, kind: MoveType) -> Self {
         Self {
             piece,
@@ -56,6 +63,90 @@ impl Move {
     pub fn kind(&self) -> MoveType {
         self.kind
     }
+
+    pub fn captured(&self) -> Option<Pieces> {
+        match self.kind {
+            MoveType::Capture(piece) | MoveType::PromotionCapture(_, piece) => Some(piece),
+            MoveType::EnPassant => Some(Pieces::Pawn),
+            _ => None,
+        }
+    }
+
+    pub fn promoted(&self) -> Option<Pieces> {
+        match self.kind {
+            MoveType::Promotion(piece) | MoveType::PromotionCapture(piece, _) => Some(piece),
+            _ => None,
+        }
+    }
+
+    pub fn is_capture(&self) -> bool {
+        self.captured().is_some()
+    }
+
+    // neither a capture nor a promotion
+    pub fn is_quiet(&self) -> bool {
+        !self.is_capture() && self.promoted().is_none()
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.piece == Pieces::Empty
+    }
+
+    // All of the move in 24 bits, so it fits into a transposition table
+    // entry: from (6), to (6), piece (3), kind (3) and the up to two
+    // pieces of the kind (3 each).
+    pub fn pack(self) -> u32 {
+        let (kind, a, b) = match self.kind {
+            MoveType::Regular => (0, Pieces::Empty, Pieces::Empty),
+            MoveType::Capture(captured) => (1, captured, Pieces::Empty),
+            MoveType::Promotion(promoted) => (2, promoted, Pieces::Empty),
+            MoveType::PromotionCapture(promoted, captured) => (3, promoted, captured),
+            MoveType::EnPassant => (4, Pieces::Empty, Pieces::Empty),
+            MoveType::Castle(Castle::Kingside) => (5, Pieces::Empty, Pieces::Empty),
+            MoveType::Castle(Castle::Queenside) => (6, Pieces::Empty, Pieces::Empty),
+        };
+        self.from as u32
+            | (self.to as u32) << 6
+            | (self.piece as u32) << 12
+            | kind << 15
+            | (a as u32) << 18
+            | (b as u32) << 21
+    }
+
+    pub fn unpack(packed: u32) -> Self {
+        let piece = |shift: u32| Pieces::try_from(((packed >> shift) & 7) as usize).unwrap();
+        let kind = match (packed >> 15) & 7 {
+            0 => MoveType::Regular,
+            1 => MoveType::Capture(piece(18)),
+            2 => MoveType::Promotion(piece(18)),
+            3 => MoveType::PromotionCapture(piece(18), piece(21)),
+            4 => MoveType::EnPassant,
+            5 => MoveType::Castle(Castle::Kingside),
+            _ => MoveType::Castle(Castle::Queenside),
+        };
+        Self {
+            piece: piece(12),
+            from: (packed & 63) as u8,
+            to: ((packed >> 6) & 63) as u8,
+            kind,
+        }
+    }
+
+    // Long algebraic notation as used by UCI, e.g. `e2e4` or `e7e8q`.
+    pub fn to_uci(self) -> String {
+        if self.is_null() {
+            return String::from("0000");
+        }
+        let mut uci = format!(
+            "{}{}",
+            square_num_to_algebraic(self.from),
+            square_num_to_algebraic(self.to)
+        );
+        if let Some(piece) = self.promoted() {
+            uci.push_str(&piece.to_string().to_lowercase());
+        }
+        uci
+    }
 }
 
 impl fmt::Debug for Move {
@@ -70,3 +161,31 @@ impl fmt::Debug for Move {
 
 // TODO: Optimizations:
 // 1. use a single u64 to store all data by bitshifting etc. -> create functions to retrieve data
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pack_round_trip() {
+        let moves = [
+            Move::default(),
+            Move::new(Pieces::Knight, 6, 21, MoveType::Regular),
+            Move::new(Pieces::Queen, 63, 0, MoveType::Capture(Pieces::Rook)),
+            Move::new(Pieces::Pawn, 52, 60, MoveType::Promotion(Pieces::Knight)),
+            Move::new(
+                Pieces::Pawn,
+                14,
+                7,
+                MoveType::PromotionCapture(Pieces::Queen, Pieces::Bishop),
+            ),
+            Move::new(Pieces::Pawn, 36, 43, MoveType::EnPassant),
+            Move::new(Pieces::King, 4, 6, MoveType::Castle(Castle::Kingside)),
+            Move::new(Pieces::King, 60, 58, MoveType::Castle(Castle::Queenside)),
+        ];
+        for m in moves {
+            assert!(m.pack() < 1 << 24);
+            assert_eq!(Move::unpack(m.pack()), m);
+        }
+    }
+}
@@ -1,18 +1,12 @@
+use crate::evaluation::{psqt::psqt, MATERIAL, PHASE};
+use crate::move_generator::MoveGenerator;
 use crate::moves::{
-    self,
     Castle::{Kingside, Queenside},
     Move, MoveType,
 };
-use crate::{Squares, BLACK, WHITE};
+use crate::{Castling, Squares, BLACK, WHITE};
 
-use super::{
-    gamestate::{self, GameState},
-    get_bitmask,
-};
-use super::{
-    Board, Pieces, Square, EN_PASSANT_END_SQUARES_BLACK, EN_PASSANT_END_SQUARES_WHITE,
-    EN_PASSANT_START_SQUARES_BLACK, EN_PASSANT_START_SQUARES_WHITE,
-};
+use super::{get_bitmask, Board, Pieces, Square};
 
 impl Board {
     pub fn remove_piece(&mut self, side: u8, piece: Pieces, square: Square) {
@@ -21,6 +15,15 @@ impl Board {
         self.side[side as usize] ^= mask;
         self.piece_list[square as usize] = Pieces::Empty;
         self.game_state.zobrist_key ^= self.zobrist_randoms.pieces(side, piece, square);
+        self.game_state.material[side as usize] -= MATERIAL[piece as usize];
+        self.game_state.psqt[side as usize] -= psqt(side, piece, square);
+        self.game_state.game_phase -= PHASE[piece as usize] as i16;
+        if let Some(nnue) = &mut self.nnue {
+            nnue.remove(side, piece, square);
+        }
+        if piece == Pieces::Pawn {
+            self.game_state.pawn_key ^= self.zobrist_randoms.pieces(side, piece, square);
+        }
     }
 
     pub fn put_piece(&mut self, side: u8, piece: Pieces, square: Square) {
@@ -29,6 +32,15 @@ impl Board {
         self.side[side as usize] ^= mask;
         self.piece_list[square as usize] = piece;
         self.game_state.zobrist_key ^= self.zobrist_randoms.pieces(side, piece, square);
+        self.game_state.material[side as usize] += MATERIAL[piece as usize];
+        self.game_state.psqt[side as usize] += psqt(side, piece, square);
+        self.game_state.game_phase += PHASE[piece as usize] as i16;
+        if let Some(nnue) = &mut self.nnue {
+            nnue.add(side, piece, square);
+        }
+        if piece == Pieces::Pawn {
+            self.game_state.pawn_key ^= self.zobrist_randoms.pieces(side, piece, square);
+        }
     }
 
     pub fn regular_move(&mut self, side: u8, piece: Pieces, from: Square, to: Square) {
@@ -37,204 +49,210 @@ impl Board {
     }
 
     pub fn set_ep_square(&mut self, square: Square) {
-        // Updates happening here
+        self.game_state.zobrist_key ^= self.zobrist_randoms.en_passant(self.game_state.en_passant);
         self.game_state.en_passant = Some(square);
-        // Updates happening here
+        self.game_state.zobrist_key ^= self.zobrist_randoms.en_passant(self.game_state.en_passant);
     }
     pub fn clear_ep_square(&mut self) {
-        // Updates happening here
+        self.game_state.zobrist_key ^= self.zobrist_randoms.en_passant(self.game_state.en_passant);
         self.game_state.en_passant = None;
-        // Updates happening here
     }
     pub fn switch_side(&mut self) {
-        // Updates happening here
+        self.game_state.zobrist_key ^= self.zobrist_randoms.sides(self.game_state.active_color);
         self.game_state.active_color ^= 1;
-        // Updates happening here
+        self.game_state.zobrist_key ^= self.zobrist_randoms.sides(self.game_state.active_color);
     }
     pub fn update_castling_permissions(&mut self, new_permissions: u8) {
-        // Updates happening here
+        self.game_state.zobrist_key ^= self.zobrist_randoms.castling(self.game_state.castling);
         self.game_state.castling = new_permissions;
-        // Updates happening here
+        self.game_state.zobrist_key ^= self.zobrist_randoms.castling(self.game_state.castling);
     }
+
+    // Takes back the last move. The game state (castling, ep, clocks, the
+    // zobrist key...) comes back from the history; only the pieces have to
+    // be moved back.
     pub fn unmake(&mut self) {
-        // go back in history by one half move(i think it's a half move)
-        let previous_zobrist = self.game_state.zobrist_key;
-        self.game_state = self.history.unmake();
-        self.game_state.zobrist_key = previous_zobrist;
-        // now unmake the move
-        let move_to_unmake = self.game_state.next_move;
-        match move_to_unmake.kind() {
-            MoveType::Regular => self.regular_move(
-                self.we(),
-                move_to_unmake.piece(),
-                move_to_unmake.to(),
-                move_to_unmake.from(),
-            ),
-            MoveType::Capture(piece) => {
-                self.regular_move(
-                    self.we(),
-                    move_to_unmake.piece(),
-                    move_to_unmake.to(),
-                    move_to_unmake.from(),
-                );
-                self.put_piece(self.opponent(), piece, move_to_unmake.to())
+        let previous = self
+            .history
+            .pop()
+            .expect("unmake called without a move to take back");
+        // the accumulators from before the move are still there
+        let mut nnue = self.nnue.take();
+        let we = previous.active_color;
+        let opponent = we ^ 1;
+        let m = previous.next_move;
+        match m.kind() {
+            MoveType::Regular => self.regular_move(we, m.piece(), m.to(), m.from()),
+            MoveType::Capture(captured) => {
+                self.regular_move(we, m.piece(), m.to(), m.from());
+                self.put_piece(opponent, captured, m.to());
             }
-
-            MoveType::Promotion(piece) => {
-                self.promotion_move(piece, move_to_unmake.to(), move_to_unmake.piece());
-                self.regular_move(
-                    self.we(),
-                    Pieces::Pawn,
-                    move_to_unmake.to(),
-                    move_to_unmake.from(),
-                );
+            MoveType::EnPassant => {
+                self.regular_move(we, Pieces::Pawn, m.to(), m.from());
+                self.put_piece(opponent, Pieces::Pawn, ep_victim(we, m.to()));
+            }
+            MoveType::Promotion(promoted) => {
+                self.remove_piece(we, promoted, m.to());
+                self.put_piece(we, Pieces::Pawn, m.from());
+            }
+            MoveType::PromotionCapture(promoted, captured) => {
+                self.remove_piece(we, promoted, m.to());
+                self.put_piece(opponent, captured, m.to());
+                self.put_piece(we, Pieces::Pawn, m.from());
             }
             MoveType::Castle(castle) => {
-                // move rook back
-                let rooks = get_rook_start_squares(&self.game_state);
-                let kingside_rook = rooks[0];
-                let queenside_rook = rooks[1];
-                match castle {
-                    Kingside => self.regular_move(
-                        self.we(),
-                        Pieces::Rook,
-                        self.king(self.we()) - 1,
-                        kingside_rook,
-                    ),
-                    Queenside => self.regular_move(
-                        self.we(),
-                        Pieces::Rook,
-                        self.king(self.we()) + 1,
-                        queenside_rook,
-                    ),
-                }
-                // move king back
-                self.regular_move(
-                    self.we(),
-                    move_to_unmake.piece(),
-                    move_to_unmake.to(),
-                    move_to_unmake.from(),
-                );
+                let (rook_from, rook_to) = castling_rook_squares(we, castle);
+                self.regular_move(we, Pieces::Rook, rook_to, rook_from);
+                self.regular_move(we, Pieces::King, m.to(), m.from());
             }
         };
+        self.game_state = previous;
+        let computed = nnue.as_mut().is_none_or(|nnue| nnue.pop());
+        self.nnue = nnue;
+        if !computed {
+            self.refresh_network();
+        }
     }
+
+    // Plays a pseudo-legal move. Legality is not checked; see `make_legal`.
     pub fn make(&mut self, m: Move) {
-        print!("Making move: {:?}", m);
+        let we = self.we();
+        let opponent = self.opponent();
         // create a respawn point
         self.game_state.next_move = m;
         self.history.push(self.game_state);
         self.game_state.next_move = Move::default();
-        // try out the move
+        if let Some(nnue) = &mut self.nnue {
+            nnue.push();
+        }
+
         let piece = m.piece();
-        // actually move on the board
         self.game_state.halfmove_clock += 1;
+        if self.game_state.en_passant.is_some() {
+            self.clear_ep_square();
+        }
+        // actually move on the board
         match m.kind() {
-            MoveType::Regular => self.regular_move(self.we(), piece, m.from(), m.to()),
-            MoveType::Capture(captured_piece) => {
-                self.capture_move(piece, m.from(), m.to(), captured_piece);
-                // resetting halfmove_clock
-                self.game_state.halfmove_clock = 0;
+            MoveType::Regular => self.regular_move(we, piece, m.from(), m.to()),
+            MoveType::Capture(captured) => {
+                self.remove_piece(opponent, captured, m.to());
+                self.regular_move(we, piece, m.from(), m.to());
+            }
+            MoveType::EnPassant => {
+                self.remove_piece(opponent, Pieces::Pawn, ep_victim(we, m.to()));
+                self.regular_move(we, Pieces::Pawn, m.from(), m.to());
             }
-            MoveType::Promotion(promoted_piece) => {
-                self.regular_move(self.we(), piece, m.from(), m.to());
-                self.promotion_move(piece, m.to(), promoted_piece);
+            MoveType::Promotion(promoted) => {
+                self.remove_piece(we, Pieces::Pawn, m.from());
+                self.put_piece(we, promoted, m.to());
             }
-            MoveType::Castle(direction) => {
-                self.castle(direction);
-                self.update_castling_permissions(0);
+            MoveType::PromotionCapture(promoted, captured) => {
+                self.remove_piece(opponent, captured, m.to());
+                self.remove_piece(we, Pieces::Pawn, m.from());
+                self.put_piece(we, promoted, m.to());
+            }
+            MoveType::Castle(castle) => {
+                let (rook_from, rook_to) = castling_rook_squares(we, castle);
+                self.regular_move(we, Pieces::King, m.from(), m.to());
+                self.regular_move(we, Pieces::Rook, rook_from, rook_to);
             }
         }
-        // swap side
-        self.switch_side();
-        // set/remove en_passant
-        if piece == Pieces::Pawn {
-            // resetting halfmove_clock
+
+        // moving the king or a rook, or capturing a rook, drops permissions
+        let permissions = self.game_state.castling
+            & castling_permission_mask(m.from())
+            & castling_permission_mask(m.to());
+        if permissions != self.game_state.castling {
+            self.update_castling_permissions(permissions);
+        }
+
+        if m.is_capture() || piece == Pieces::Pawn {
             self.game_state.halfmove_clock = 0;
-            if EN_PASSANT_START_SQUARES_WHITE.contains(&m.from())
-                || EN_PASSANT_START_SQUARES_BLACK.contains(&m.from())
-                    && (EN_PASSANT_END_SQUARES_WHITE.contains(&m.to())
-                        || EN_PASSANT_END_SQUARES_BLACK.contains(&m.to()))
-            {
-                // set ep square as square on same file in next rank
-                self.set_ep_square(&m.from() + 8);
-            } else {
-                self.clear_ep_square();
-            }
         }
+        // a double step makes the skipped square available for en-passant
+        if piece == Pieces::Pawn && m.from().abs_diff(m.to()) == 16 {
+            self.set_ep_square((m.from() + m.to()) / 2);
+        }
+        if we == BLACK {
+            self.game_state.fullmove_number += 1;
+        }
+        self.switch_side();
+    }
+
+    // Plays a pseudo-legal move and keeps it only if the own king is not
+    // left in check. Returns false (with the board unchanged) otherwise.
+    pub fn make_legal(&mut self, m: Move, mg: &MoveGenerator) -> bool {
+        let we = self.we();
+        self.make(m);
+        if self.square_attacked(mg, self.king(we), self.we()) {
+            self.unmake();
+            return false;
+        }
+        true
+    }
+}
 
-        // assuming the move is valid, store the new position
+impl Board {
+    // Passes the turn, for null-move pruning. Only side to move, en-passant
+    // and the zobrist key change; the previous state is pushed to the
+    // history like a normal move, with an empty `next_move`.
+    pub fn make_null(&mut self) {
+        self.game_state.next_move = Move::default();
         self.history.push(self.game_state);
-        // TODO:
-        // all validity checks:
-        // - castling
-        // - checks
-        // - pins
-        println!(" -> Success");
-    }
-    fn capture_move(&mut self, piece: Pieces, from: Square, to: Square, captured_piece: Pieces) {
-        // remove piece that will be captured
-        self.remove_piece(self.opponent(), captured_piece, to);
-        // move piece regularely
-        self.regular_move(self.we(), piece, from, to);
+        if self.game_state.en_passant.is_some() {
+            self.clear_ep_square();
+        }
+        self.game_state.halfmove_clock += 1;
+        self.switch_side();
     }
 
-    fn promotion_move(&mut self, piece: Pieces, square: Square, promoted_piece: Pieces) {
-        self.remove_piece(self.we(), piece, square);
-        self.put_piece(self.we(), promoted_piece, square);
+    pub fn unmake_null(&mut self) {
+        self.game_state = self
+            .history
+            .pop()
+            .expect("unmake_null called without a null move to take back");
     }
+}
 
-    fn castle(&mut self, direction: moves::Castle) {
-        // move king as per move
-        let king = self.king(self.we());
-        // move rook in relation to king
-        let rooks = get_rook_start_squares(&self.game_state);
-        let kingside_rook = rooks[0];
-        let queenside_rook = rooks[1];
-        match direction {
-            Kingside => {
-                self.regular_move(self.we(), Pieces::King, king, king + 2);
-                self.regular_move(
-                    self.we(),
-                    Pieces::Rook,
-                    kingside_rook,
-                    self.king(self.we()) - 1,
-                );
-            }
-            Queenside => {
-                self.regular_move(self.we(), Pieces::King, king, king - 2);
-                self.regular_move(
-                    self.we(),
-                    Pieces::Rook,
-                    queenside_rook,
-                    self.king(self.we()) + 1,
-                );
-            }
-        }
+fn ep_victim(side: u8, ep_square: Square) -> Square {
+    if side == WHITE {
+        ep_square - 8
+    } else {
+        ep_square + 8
     }
 }
 
-fn get_rook_start_squares(state: &GameState) -> [Square; 2] {
-    let kingside_rook: Square;
-    let queenside_rook: Square;
-    match state.active_color {
-        WHITE => {
-            queenside_rook = Squares::A1;
-            kingside_rook = Squares::H1;
-        }
-        BLACK => {
-            queenside_rook = Squares::A8;
-            kingside_rook = Squares::H8;
-        }
-        x => panic!(
+// rook (from, to) for a castling move of the given side
+fn castling_rook_squares(side: u8, castle: crate::moves::Castle) -> (Square, Square) {
+    match (side, castle) {
+        (WHITE, Kingside) => (Squares::H1, Squares::F1),
+        (WHITE, Queenside) => (Squares::A1, Squares::D1),
+        (BLACK, Kingside) => (Squares::H8, Squares::F8),
+        (BLACK, Queenside) => (Squares::A8, Squares::D8),
+        (x, _) => panic!(
             "Unknown side in active color. (expected 0 or 1 - found {})",
             x
         ),
-    };
-    [kingside_rook, queenside_rook]
+    }
+}
+
+// permissions which survive a move touching this square
+fn castling_permission_mask(square: Square) -> u8 {
+    match square {
+        Squares::A1 => Castling::ALL & !Castling::WQ,
+        Squares::E1 => Castling::ALL & !(Castling::WK | Castling::WQ),
+        Squares::H1 => Castling::ALL & !Castling::WK,
+        Squares::A8 => Castling::ALL & !Castling::BQ,
+        Squares::E8 => Castling::ALL & !(Castling::BK | Castling::BQ),
+        Squares::H8 => Castling::ALL & !Castling::BK,
+        _ => Castling::ALL,
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::movelist::MoveList;
     use crate::{
         moves::{
             Castle::{Kingside, Queenside},
@@ -311,4 +329,55 @@ mod tests {
         board.unmake();
         assert_eq!(board_copy, board);
     }
+
+    #[test]
+    fn test_incremental_zobrist_key() {
+        let mg = MoveGenerator::new();
+        let mut board = Board::fen(Some(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1".to_string(),
+        ));
+        let mut moves = MoveList::new();
+        board.get_moves(&mg, &mut moves);
+        for m in moves.iter() {
+            let before = board.clone();
+            if board.make_legal(*m, &mg) {
+                assert_eq!(board.game_state.zobrist_key, board.init_zobrist_key());
+                assert_eq!(board.game_state.pawn_key, board.init_pawn_key());
+                board.unmake();
+            }
+            assert_eq!(board.game_state.zobrist_key, before.game_state.zobrist_key);
+            assert_eq!(board.pieces, before.pieces);
+        }
+    }
+
+    #[test]
+    fn test_castling_permissions_and_en_passant() {
+        let mut board = Board::fen(Some("r3k2r/8/8/8/8/8/4P3/R3K2R w KQkq - 0 1".to_string()));
+        board.make(Move::new(Pieces::Rook, 0, 8, MoveType::Regular));
+        assert_eq!(
+            board.game_state.castling,
+            Castling::WK | Castling::BK | Castling::BQ
+        );
+        board.unmake();
+        board.make(Move::new(Pieces::Pawn, 12, 28, MoveType::Regular));
+        assert_eq!(board.game_state.en_passant, Some(20));
+        board.make(Move::new(Pieces::King, 60, 61, MoveType::Regular));
+        assert_eq!(board.game_state.en_passant, None);
+        assert_eq!(board.game_state.castling, Castling::WK | Castling::WQ);
+        assert_eq!(board.game_state.fullmove_number, 2);
+    }
+
+    #[test]
+    fn test_null_move() {
+        let mut board = Board::fen(Some(
+            "rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3".to_string(),
+        ));
+        let before = board.clone();
+        board.make_null();
+        assert_eq!(board.we(), WHITE);
+        assert_eq!(board.game_state.en_passant, None);
+        assert_eq!(board.game_state.zobrist_key, board.init_zobrist_key());
+        board.unmake_null();
+        assert_eq!(board, before);
+    }
 }
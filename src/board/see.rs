@@ -0,0 +1,178 @@
+use super::moves::{Move, MoveType};
+use super::{get_bitmask, Board, Pieces};
+use crate::bitboard::Bitboard;
+use crate::defs::*;
+use crate::move_generator::MoveGenerator;
+
+// Piece values used by the static exchange evaluation, in the order of
+// `Pieces`. The king is worth more than everything else combined, so it
+// only ever captures last.
+pub const SEE_VALUES: [i32; NrOf::PIECE_TYPES + 1] = [100, 325, 325, 500, 1000, 20_000, 0];
+
+// Cheapest first, to find the least valuable attacker.
+const SEE_ORDER: [Pieces; NrOf::PIECE_TYPES] = [
+    Pieces::Pawn,
+    Pieces::Knight,
+    Pieces::Bishop,
+    Pieces::Rook,
+    Pieces::Queen,
+    Pieces::King,
+];
+
+impl Board {
+    // Static exchange evaluation: the material balance of the capture
+    // sequence on the destination square, both sides always recapturing
+    // with their least valuable piece and free to stop when it no longer
+    // pays. Sliders hidden behind other attackers (x-rays) join in as soon
+    // as the piece in front of them has captured. Pins are ignored.
+    pub fn see(&self, mg: &MoveGenerator, m: Move) -> i32 {
+        if let MoveType::Castle(_) = m.kind() {
+            return 0;
+        }
+        let to = m.to();
+        let from_mask = get_bitmask(m.from());
+        let mut side = if self.side[WHITE as usize] & from_mask != 0 {
+            WHITE
+        } else {
+            BLACK
+        };
+
+        let mut gain = [0i32; 32];
+        let mut piece_on_to = m.piece();
+        gain[0] = m.captured().map_or(0, |piece| SEE_VALUES[piece as usize]);
+        if let Some(promoted) = m.promoted() {
+            gain[0] += SEE_VALUES[promoted as usize] - SEE_VALUES[Pieces::Pawn as usize];
+            piece_on_to = promoted;
+        }
+
+        let mut occupancy = self.occupancy() ^ from_mask;
+        if let MoveType::EnPassant = m.kind() {
+            let victim = if side == WHITE { to - 8 } else { to + 8 };
+            occupancy ^= get_bitmask(victim);
+        }
+        let mut attackers = self.attackers_to(mg, to, occupancy) & occupancy;
+
+        let mut depth = 0;
+        loop {
+            depth += 1;
+            // what the side to recapture wins if it does
+            gain[depth] = SEE_VALUES[piece_on_to as usize] - gain[depth - 1];
+            side ^= 1;
+
+            let ours = attackers & self.side[side as usize];
+            let Some((piece, square)) = self.least_valuable(ours) else {
+                break;
+            };
+            // the king can't capture into a defended square
+            if piece == Pieces::King && attackers & self.side[(side ^ 1) as usize] != 0 {
+                break;
+            }
+            occupancy ^= get_bitmask(square);
+            attackers = self.attackers_to(mg, to, occupancy) & occupancy;
+            piece_on_to = piece;
+            if depth == gain.len() - 1 {
+                break;
+            }
+        }
+
+        // negamax the speculative gains back to the root; either side may
+        // stop capturing if that is better
+        while depth > 1 {
+            depth -= 1;
+            gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+        }
+        gain[0]
+    }
+
+    // Whether the exchange started by `m` wins at least `threshold`.
+    pub fn see_ge(&self, mg: &MoveGenerator, m: Move, threshold: i32) -> bool {
+        self.see(mg, m) >= threshold
+    }
+
+    // Pieces of `side` the opponent can win material against.
+    pub fn hanging_pieces(&self, mg: &MoveGenerator, side: u8) -> u64 {
+        let mut hanging = EMPTY;
+        let own = self.side[side as usize] & !self.get_pieces(side, Pieces::King);
+        for square in Bitboard::new(own) {
+            let attackers =
+                self.attackers_to(mg, square, self.occupancy()) & self.side[(side ^ 1) as usize];
+            if let Some((piece, from)) = self.least_valuable(attackers) {
+                let victim = self.piece_list[square as usize];
+                let capture = Move::new(piece, from, square, MoveType::Capture(victim));
+                if self.see(mg, capture) > 0 {
+                    hanging |= get_bitmask(square);
+                }
+            }
+        }
+        hanging
+    }
+
+    fn least_valuable(&self, attackers: u64) -> Option<(Pieces, Square)> {
+        for piece in SEE_ORDER {
+            let bb = attackers & (self.get_pieces(WHITE, piece) | self.get_pieces(BLACK, piece));
+            if bb != 0 {
+                return Some((piece, bb.trailing_zeros() as Square));
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::algebraic_to_square;
+
+    fn see(fen: &str, piece: Pieces, from: &str, to: &str) -> i32 {
+        let mg = MoveGenerator::new();
+        let board = Board::fen(Some(fen.to_string()));
+        let from = algebraic_to_square(from).unwrap();
+        let to = algebraic_to_square(to).unwrap();
+        let kind = match board.piece_list[to as usize] {
+            Pieces::Empty => MoveType::Regular,
+            victim => MoveType::Capture(victim),
+        };
+        board.see(&mg, Move::new(piece, from, to, kind))
+    }
+
+    #[test]
+    fn test_see_simple() {
+        // undefended pawn
+        let fen = "1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1";
+        assert_eq!(see(fen, Pieces::Rook, "e1", "e5"), 100);
+        // knight defended by a pawn
+        let fen = "4k3/8/3p4/4n3/3P4/8/8/4K3 w - - 0 1";
+        assert_eq!(see(fen, Pieces::Pawn, "d4", "e5"), 225);
+        // quiet move onto a square attacked by a pawn
+        let fen = "4k3/8/3p4/8/8/8/8/4KQ2 w - - 0 1";
+        assert_eq!(see(fen, Pieces::Queen, "f1", "e5"), -1000);
+    }
+
+    #[test]
+    fn test_see_xrays() {
+        // Nxe5 loses the knight for a pawn
+        let fen = "1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1";
+        assert_eq!(see(fen, Pieces::Knight, "d3", "e5"), -225);
+        // doubled rooks win the pawn against a single defender
+        let fen = "4k3/4r3/8/4p3/8/8/4R3/4RK2 w - - 0 1";
+        assert_eq!(see(fen, Pieces::Rook, "e2", "e5"), 100);
+        // the king may not recapture on a defended square
+        let fen = "8/8/8/3k4/4p3/5P2/8/3K4 w - - 0 1";
+        assert_eq!(see(fen, Pieces::Pawn, "f3", "e4"), 0);
+        let fen = "8/8/8/3k4/4p3/5P2/6B1/3K4 w - - 0 1";
+        assert_eq!(see(fen, Pieces::Pawn, "f3", "e4"), 100);
+    }
+
+    #[test]
+    fn test_see_ge_and_hanging_pieces() {
+        let mg = MoveGenerator::new();
+        let board = Board::fen(Some("4k3/8/3p4/4n3/3P4/8/2q5/4K1R1 w - - 0 1".to_string()));
+        let capture = Move::new(Pieces::Pawn, 27, 36, MoveType::Capture(Pieces::Knight));
+        assert!(board.see_ge(&mg, capture, 225));
+        assert!(!board.see_ge(&mg, capture, 226));
+        // the rook on g1 is attacked by nothing, d4 is covered by nothing black
+        assert_eq!(board.hanging_pieces(&mg, WHITE), EMPTY);
+        // the black knight can be won by the pawn
+        assert_eq!(board.hanging_pieces(&mg, BLACK), get_bitmask(36));
+    }
+}
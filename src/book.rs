@@ -0,0 +1,204 @@
+mod keys;
+pub mod pgn;
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use rand::Rng;
+
+pub use self::keys::polyglot_key;
+use crate::board::movelist::MoveList;
+use crate::board::moves::{Castle, Move, MoveType};
+use crate::board::pieces::Pieces;
+use crate::board::Board;
+use crate::move_generator::MoveGenerator;
+
+const ENTRY_SIZE: usize = 16;
+
+// One move of a Polyglot book: 16 bytes, big endian. The move has the
+// target square in bits 0-5, the origin in bits 6-11 and the promotion
+// piece in bits 12-14. Castling is written as the king taking its rook.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BookEntry {
+    pub key: u64,
+    pub mv: u16,
+    pub weight: u16,
+    pub learn: u32,
+}
+
+// An opening book in the Polyglot format, read into memory. The entries
+// are sorted by key, so the moves of a position are found by binary
+// search.
+pub struct Book {
+    entries: Vec<BookEntry>,
+}
+
+impl Book {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        if bytes.len() % ENTRY_SIZE != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a Polyglot book",
+            ));
+        }
+        let entries = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(|bytes| BookEntry {
+                key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+                mv: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+                weight: u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+                learn: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+            })
+            .collect();
+        Ok(Self { entries })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // The book moves of the position which are legal, with their weights.
+    pub fn moves(&self, board: &mut Board, mg: &MoveGenerator) -> Vec<(Move, u16)> {
+        let key = polyglot_key(board, mg);
+        let first = self.entries.partition_point(|entry| entry.key < key);
+        let mut legal = MoveList::new();
+        board.get_legal_moves(mg, &mut legal);
+        self.entries[first..]
+            .iter()
+            .take_while(|entry| entry.key == key)
+            .filter_map(|entry| {
+                let m = legal.iter().find(|&&m| encode_move(m) == entry.mv)?;
+                Some((*m, entry.weight))
+            })
+            .collect()
+    }
+
+    // A move of the book for the position: the one with the highest
+    // weight if `best`, else one picked at random in proportion to the
+    // weights. Moves of weight 0 are never played.
+    pub fn probe(&self, board: &mut Board, mg: &MoveGenerator, best: bool) -> Option<Move> {
+        let moves: Vec<_> = self
+            .moves(board, mg)
+            .into_iter()
+            .filter(|&(_, weight)| weight > 0)
+            .collect();
+        if best {
+            return moves
+                .iter()
+                .max_by_key(|&&(_, weight)| weight)
+                .map(|&(m, _)| m);
+        }
+        let total: u32 = moves.iter().map(|&(_, weight)| weight as u32).sum();
+        if total == 0 {
+            return None;
+        }
+        let mut pick = rand::rng().random_range(0..total);
+        for (m, weight) in moves {
+            if pick < weight as u32 {
+                return Some(m);
+            }
+            pick -= weight as u32;
+        }
+        None
+    }
+}
+
+// Writes the entries as a book, sorted as Polyglot expects: by key, and
+// within a position by falling weight.
+pub fn write_book(path: &Path, entries: &mut [BookEntry]) -> io::Result<()> {
+    entries.sort_by_key(|entry| (entry.key, std::cmp::Reverse(entry.weight)));
+    let mut bytes = Vec::with_capacity(entries.len() * ENTRY_SIZE);
+    for entry in entries.iter() {
+        bytes.extend_from_slice(&entry.key.to_be_bytes());
+        bytes.extend_from_slice(&entry.mv.to_be_bytes());
+        bytes.extend_from_slice(&entry.weight.to_be_bytes());
+        bytes.extend_from_slice(&entry.learn.to_be_bytes());
+    }
+    fs::write(path, bytes)
+}
+
+pub fn encode_move(m: Move) -> u16 {
+    let to = match m.kind() {
+        // to the rook's corner
+        MoveType::Castle(Castle::Kingside) => m.to() + 1,
+        MoveType::Castle(Castle::Queenside) => m.to() - 2,
+        _ => m.to(),
+    };
+    let promotion = match m.promoted() {
+        Some(Pieces::Knight) => 1,
+        Some(Pieces::Bishop) => 2,
+        Some(Pieces::Rook) => 3,
+        Some(Pieces::Queen) => 4,
+        _ => 0,
+    };
+    to as u16 | (m.from() as u16) << 6 | promotion << 12
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uci::parse_move;
+
+    #[test]
+    fn test_encode_move() {
+        let mg = MoveGenerator::new();
+        let mut board = Board::fen(Some("r3k2r/1P6/8/8/8/8/8/R3K2R w KQkq - 0 1".to_string()));
+        let mut encode = |text| encode_move(parse_move(&mut board, &mg, text).unwrap());
+        // e1h1, e1a1, the fields of b7a8q
+        assert_eq!(encode("e1g1"), 4 << 6 | 7);
+        assert_eq!(encode("e1c1"), 4 << 6);
+        assert_eq!(encode("b7a8q"), 4 << 12 | 49 << 6 | 56);
+        assert_eq!(encode("a1a8"), 56);
+    }
+
+    #[test]
+    fn test_write_and_probe() {
+        let mg = MoveGenerator::new();
+        let mut board = Board::fen(None);
+        let key = polyglot_key(&board, &mg);
+        let entry = |text, weight| BookEntry {
+            key,
+            mv: encode_move(parse_move(&mut Board::fen(None), &mg, text).unwrap()),
+            weight,
+            learn: 0,
+        };
+        let mut entries = vec![
+            entry("d2d4", 10),
+            entry("e2e4", 30),
+            entry("g1f3", 0),
+            BookEntry {
+                key: key - 1,
+                ..entry("a2a3", 99)
+            },
+            BookEntry {
+                key: key + 1,
+                ..entry("h2h3", 99)
+            },
+        ];
+        let path = std::env::temp_dir().join(format!("rust_chess_{}.bin", std::process::id()));
+        write_book(&path, &mut entries).unwrap();
+        let book = Book::open(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(book.len(), 5);
+        let moves: Vec<String> = book
+            .moves(&mut board, &mg)
+            .iter()
+            .map(|(m, _)| m.to_uci())
+            .collect();
+        assert_eq!(moves, ["e2e4", "d2d4", "g1f3"]);
+        assert_eq!(book.probe(&mut board, &mg, true).unwrap().to_uci(), "e2e4");
+        for _ in 0..20 {
+            let m = book.probe(&mut board, &mg, false).unwrap().to_uci();
+            assert!(m == "e2e4" || m == "d2d4");
+        }
+        let mut other = Board::fen(Some("8/8/8/8/8/8/8/K1k5 w - - 0 1".to_string()));
+        assert_eq!(book.probe(&mut other, &mg, false), None);
+    }
+}
@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use super::{encode_move, polyglot_key, write_book, BookEntry};
+use crate::board::movelist::MoveList;
+use crate::board::moves::{Castle, Move, MoveType};
+use crate::board::pieces::Pieces;
+use crate::board::Board;
+use crate::defs::*;
+use crate::helper::algebraic_to_square;
+use crate::move_generator::MoveGenerator;
+
+// Which moves of the games make it into a book.
+pub struct BookFilter {
+    // only the first plies of every game
+    pub plies: usize,
+    // moves played less often are left out
+    pub min_count: u32,
+    // and those scoring less for the side which played them, 0 to 1
+    pub min_win_rate: f64,
+}
+
+impl Default for BookFilter {
+    fn default() -> Self {
+        Self {
+            plies: 30,
+            min_count: 1,
+            min_win_rate: 0.0,
+        }
+    }
+}
+
+// One game of a PGN file: its moves in SAN, the result from White's side
+// in half points and the start position if not the usual one.
+struct Game {
+    fen: Option<String>,
+    moves: Vec<String>,
+    result: Option<u32>,
+}
+
+// How often a move was played and the half points it scored.
+#[derive(Default)]
+struct Stats {
+    count: u32,
+    score: u32,
+}
+
+// Builds the entries of a book from the games of a PGN file. The weight of
+// a move is twice its wins plus its draws. Games without a result are
+// skipped, as are those from a position which can't be set up.
+pub fn build_book(pgn: &str, mg: &MoveGenerator, filter: &BookFilter) -> Vec<BookEntry> {
+    let mut stats: HashMap<(u64, u16), Stats> = HashMap::new();
+    for game in games(pgn) {
+        let Some(result) = game.result else { continue };
+        let mut board = Board::new();
+        let fen = game.fen.as_deref().unwrap_or(FEN_START_POSITION);
+        if board.fen_setup(fen).is_err() {
+            continue;
+        }
+        for san in game.moves.iter().take(filter.plies) {
+            let Some(m) = parse_san(&mut board, mg, san) else {
+                break;
+            };
+            let key = polyglot_key(&board, mg);
+            let entry = stats.entry((key, encode_move(m))).or_default();
+            entry.count += 1;
+            entry.score += if board.we() == WHITE {
+                result
+            } else {
+                2 - result
+            };
+            board.make(m);
+        }
+    }
+    stats
+        .into_iter()
+        .filter(|(_, stats)| {
+            let win_rate = stats.score as f64 / (2 * stats.count) as f64;
+            stats.count >= filter.min_count && win_rate >= filter.min_win_rate
+        })
+        .map(|((key, mv), stats)| BookEntry {
+            key,
+            mv,
+            weight: stats.score.min(u16::MAX as u32) as u16,
+            learn: 0,
+        })
+        .collect()
+}
+
+// `rust_chess book <games.pgn> <book.bin> [plies <n>] [mincount <n>]
+// [winrate <0-1>]`
+pub fn run(args: &[String]) {
+    let (Some(pgn), Some(book)) = (args.first(), args.get(1)) else {
+        println!("usage: book <games.pgn> <book.bin> [plies <n>] [mincount <n>] [winrate <0-1>]");
+        return;
+    };
+    let value = |name: &str| {
+        let i = args.iter().position(|arg| arg == name)?;
+        args.get(i + 1)
+    };
+    let defaults = BookFilter::default();
+    let filter = BookFilter {
+        plies: value("plies")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(defaults.plies),
+        min_count: value("mincount")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(defaults.min_count),
+        min_win_rate: value("winrate")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(defaults.min_win_rate),
+    };
+    let text = match fs::read(pgn) {
+        // PGN files are often Latin-1 rather than UTF-8
+        Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        Err(error) => return println!("could not read {pgn}: {error}"),
+    };
+    let mg = MoveGenerator::new();
+    let mut entries = build_book(&text, &mg, &filter);
+    match write_book(Path::new(book), &mut entries) {
+        Ok(()) => println!("wrote {} entries to {book}", entries.len()),
+        Err(error) => println!("could not write {book}: {error}"),
+    }
+}
+
+// Splits a PGN file into games. Comments, variations, numeric annotations
+// and move numbers are dropped.
+fn games(pgn: &str) -> Vec<Game> {
+    let mut games = Vec::new();
+    let mut game = Game {
+        fen: None,
+        moves: Vec::new(),
+        result: None,
+    };
+    let mut chars = pgn.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            '[' => {
+                let tag: String = chars.by_ref().take_while(|&c| c != ']').collect();
+                let (name, value) = tag[1..].split_once(' ').unwrap_or_default();
+                let value = value.trim().trim_matches('"').to_string();
+                match name {
+                    "FEN" => game.fen = Some(value),
+                    "Result" => game.result = parse_result(&value),
+                    _ => (),
+                }
+            }
+            '{' => chars.by_ref().take_while(|&c| c != '}').for_each(drop),
+            ';' => chars.by_ref().take_while(|&c| c != '\n').for_each(drop),
+            '(' => {
+                let mut depth = 0;
+                for c in chars.by_ref() {
+                    depth += match c {
+                        '(' => 1,
+                        ')' => -1,
+                        _ => 0,
+                    };
+                    if depth == 0 {
+                        break;
+                    }
+                }
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            _ => {
+                let mut token = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "[{;(".contains(c) {
+                        break;
+                    }
+                    token.push(c);
+                    chars.next();
+                }
+                if let Some(result) = parse_result(&token).or((token == "*").then_some(3)) {
+                    // the tag wins if they disagree; "*" is no result
+                    let result = game.result.or(Some(result)).filter(|&r| r <= 2);
+                    games.push(Game {
+                        fen: game.fen.take(),
+                        moves: std::mem::take(&mut game.moves),
+                        result,
+                    });
+                    game.result = None;
+                    continue;
+                }
+                // `12.`, `12...` and `e4` out of `12.e4`, but castling may
+                // be written with zeros
+                let san = if token.starts_with("0-0") {
+                    &token
+                } else {
+                    token.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.')
+                };
+                if !san.is_empty() && !san.starts_with('$') {
+                    game.moves.push(san.to_string());
+                }
+            }
+        }
+    }
+    games
+}
+
+fn parse_result(text: &str) -> Option<u32> {
+    match text {
+        "1-0" => Some(2),
+        "1/2-1/2" => Some(1),
+        "0-1" => Some(0),
+        _ => None,
+    }
+}
+
+// Finds the legal move written in standard algebraic notation, like `e4`,
+// `Nbd7`, `exd8=Q+` or `O-O`.
+pub fn parse_san(board: &mut Board, mg: &MoveGenerator, san: &str) -> Option<Move> {
+    let san = san.trim_end_matches(['+', '#', '!', '?']);
+    let mut moves = MoveList::new();
+    board.get_legal_moves(mg, &mut moves);
+    let castle = match san {
+        "O-O" | "0-0" => Some(Castle::Kingside),
+        "O-O-O" | "0-0-0" => Some(Castle::Queenside),
+        _ => None,
+    };
+    if let Some(castle) = castle {
+        return moves
+            .iter()
+            .copied()
+            .find(|m| m.kind() == MoveType::Castle(castle));
+    }
+
+    let (san, promotion) = match san.char_indices().rev().find(|&(_, c)| "QRBN".contains(c)) {
+        Some((i, letter))
+            if i > 0 && san[..i].ends_with(|c: char| c == '=' || c.is_ascii_digit()) =>
+        {
+            (san[..i].trim_end_matches('='), Some(piece_of(letter)?))
+        }
+        _ => (san, None),
+    };
+    let (piece, rest) = match san.chars().next()? {
+        letter @ ('K' | 'Q' | 'R' | 'B' | 'N') => (piece_of(letter)?, &san[1..]),
+        _ => (Pieces::Pawn, san),
+    };
+    let rest = rest.replace('x', "");
+    if rest.len() < 2 {
+        return None;
+    }
+    let to = algebraic_to_square(&rest[rest.len() - 2..])?;
+    // what's left tells the origin apart: a file, a rank or both
+    let hint = &rest[..rest.len() - 2];
+    moves.iter().copied().find(|m| {
+        let from = crate::helper::square_num_to_algebraic(m.from());
+        m.piece() == piece
+            && m.to() == to
+            && m.promoted() == promotion
+            && hint.chars().all(|c| from.contains(c))
+    })
+}
+
+fn piece_of(letter: char) -> Option<Pieces> {
+    match letter {
+        'K' => Some(Pieces::King),
+        'Q' => Some(Pieces::Queen),
+        'R' => Some(Pieces::Rook),
+        'B' => Some(Pieces::Bishop),
+        'N' => Some(Pieces::Knight),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_san() {
+        let mg = MoveGenerator::new();
+        let mut board = Board::fen(Some(
+            "r3k2r/1P1n4/8/8/8/5N2/8/R3K1NR w KQkq - 0 1".to_string(),
+        ));
+        let mut uci = |san| parse_san(&mut board, &mg, san).map(|m| m.to_uci());
+        assert_eq!(uci("O-O-O").as_deref(), Some("e1c1"));
+        assert_eq!(uci("Ngh3").as_deref(), Some("g1h3"));
+        assert_eq!(uci("Nd4").as_deref(), Some("f3d4"));
+        assert_eq!(uci("N1h3").as_deref(), Some("g1h3"));
+        assert_eq!(uci("Nf3h4").as_deref(), Some("f3h4"));
+        assert_eq!(uci("N3h3"), None);
+        assert_eq!(uci("bxa8=Q+").as_deref(), Some("b7a8q"));
+        assert_eq!(uci("b8N").as_deref(), Some("b7b8n"));
+        assert_eq!(uci("Rxa8").as_deref(), Some("a1a8"));
+        assert_eq!(uci("Qd1"), None);
+    }
+
+    #[test]
+    fn test_build_book() {
+        let pgn = r#"
+[Event "a"]
+[Result "1-0"]
+
+1. e4 e5 {a comment} 2. Nf3 (2. f4 exf4) Nc6 $1 3. Bb5 1-0
+
+[Event "b"]
+[Result "0-1"]
+
+1. e4 c5 2. Nf3 d6 0-1
+
+[Event "c"]
+
+1. d4 d5 1/2-1/2
+
+1. e4 *
+"#;
+        let mg = MoveGenerator::new();
+        let games = games(pgn);
+        assert_eq!(games.len(), 4);
+        assert_eq!(games[0].moves, ["e4", "e5", "Nf3", "Nc6", "Bb5"]);
+        assert_eq!(games[3].result, None);
+
+        let mut board = Board::fen(None);
+        let key = polyglot_key(&board, &mg);
+        let mut weight = |entries: &[BookEntry], key, san| {
+            let mv = encode_move(parse_san(&mut board, &mg, san).unwrap());
+            entries
+                .iter()
+                .find(|entry| entry.key == key && entry.mv == mv)
+                .map(|entry| entry.weight)
+        };
+        let entries = build_book(pgn, &mg, &BookFilter::default());
+        assert_eq!(weight(&entries, key, "e4"), Some(2));
+        assert_eq!(weight(&entries, key, "d4"), Some(1));
+        assert_eq!(entries.len(), 10);
+
+        // e4 was played twice and won once, d4 once
+        let filter = BookFilter {
+            min_count: 2,
+            ..Default::default()
+        };
+        let entries = build_book(pgn, &mg, &filter);
+        assert_eq!(entries.len(), 1);
+        let filter = BookFilter {
+            min_win_rate: 0.6,
+            plies: 1,
+            ..Default::default()
+        };
+        assert!(build_book(pgn, &mg, &filter).is_empty());
+    }
+}
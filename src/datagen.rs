@@ -0,0 +1,258 @@
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+
+use rand::Rng;
+
+use crate::board::movelist::MoveList;
+use crate::board::Board;
+use crate::defs::*;
+use crate::evaluation::endgame::drawn_by_material;
+use crate::move_generator::MoveGenerator;
+use crate::search::limits::SearchLimits;
+use crate::search::{Search, MATE_BOUND, THREAD_STACK_SIZE};
+
+// each thread searches with a table of its own
+const HASH_MB: usize = 8;
+// games still this unbalanced after the random opening teach little
+const MAX_OPENING_SCORE: i32 = 400;
+// longer games are called a draw
+const MAX_PLIES: usize = 400;
+
+// How the self-play games are played.
+#[derive(Clone)]
+pub struct DatagenSettings {
+    pub games: usize,
+    pub threads: usize,
+    // random moves before the engine takes over
+    pub random_plies: usize,
+    // the search of every move, usually a fixed number of nodes or depth
+    pub limits: SearchLimits,
+}
+
+impl Default for DatagenSettings {
+    fn default() -> Self {
+        Self {
+            games: 1000,
+            threads: 1,
+            random_plies: 8,
+            limits: SearchLimits::nodes(5000),
+        }
+    }
+}
+
+// The recorded positions of a game with the scores of the search, from
+// White's side, and the result: 1.0 for a White win, 0.5 for a draw.
+pub struct GameRecord {
+    pub positions: Vec<(String, i32)>,
+    pub result: f32,
+}
+
+impl GameRecord {
+    // One line per position, `<fen> | <score> | <result>`, the text
+    // format most trainers read.
+    pub fn lines(&self) -> String {
+        self.positions
+            .iter()
+            .map(|(fen, score)| format!("{fen} | {score} | {:.1}\n", self.result))
+            .collect()
+    }
+}
+
+// Plays one game against itself from a random opening. None if the
+// opening ended the game or left one side too far ahead.
+pub fn play_game(
+    search: &mut Search,
+    mg: &MoveGenerator,
+    settings: &DatagenSettings,
+    rng: &mut impl Rng,
+) -> Option<GameRecord> {
+    let mut board = Board::fen(None);
+    let mut moves = MoveList::new();
+    for _ in 0..settings.random_plies {
+        board.get_legal_moves(mg, &mut moves);
+        if moves.is_empty() {
+            return None;
+        }
+        let m = moves.as_slice()[rng.random_range(0..moves.len())];
+        board.make(m);
+    }
+
+    search.clear();
+    let mut positions = Vec::new();
+    let mut plies = 0;
+    let result = loop {
+        board.get_legal_moves(mg, &mut moves);
+        let in_check = board.in_check(mg);
+        if moves.is_empty() {
+            break if in_check {
+                win_for(board.opponent())
+            } else {
+                0.5
+            };
+        }
+        if board.is_repetition() || board.draw_by_fifty_moves() || drawn_by_material(&board) {
+            break 0.5;
+        }
+        if plies == MAX_PLIES {
+            break 0.5;
+        }
+
+        let found = search.iterative_deepening(&board, &settings.limits, |_| ());
+        let score = found.score;
+        if plies == 0 && score.abs() > MAX_OPENING_SCORE {
+            return None;
+        }
+        // a mate is found: no need to play it out
+        if score.abs() > MATE_BOUND {
+            break if score > 0 {
+                win_for(board.we())
+            } else {
+                win_for(board.opponent())
+            };
+        }
+        if is_quiet(&board, mg, &moves, found.best_move.is_quiet(), in_check) {
+            let white_score = if board.we() == WHITE { score } else { -score };
+            positions.push((board.to_fen(), white_score));
+        }
+        board.make(found.best_move);
+        plies += 1;
+    };
+    Some(GameRecord { positions, result })
+}
+
+fn win_for(side: u8) -> f32 {
+    if side == WHITE {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+// Only quiet positions are worth learning from: the score of the search
+// should be what the evaluation can see. So none in check, none where the
+// best move captures or promotes and none with a capture winning material.
+fn is_quiet(
+    board: &Board,
+    mg: &MoveGenerator,
+    moves: &MoveList,
+    quiet_best: bool,
+    in_check: bool,
+) -> bool {
+    !in_check
+        && quiet_best
+        && !moves
+            .iter()
+            .any(|&m| m.is_capture() && board.see_ge(mg, m, 1))
+}
+
+// `rust_chess datagen <out.txt> [games <n>] [threads <n>] [nodes <n> |
+// depth <n>] [random <n>]` plays self-play games and appends their quiet
+// positions to the file.
+pub fn run(args: &[String]) {
+    let Some(path) = args.first() else {
+        println!("usage: datagen <out.txt> [games <n>] [threads <n>] [nodes <n> | depth <n>] [random <plies>]");
+        return;
+    };
+    let value = |name: &str| {
+        let i = args.iter().position(|arg| arg == name)?;
+        args.get(i + 1)?.parse::<u64>().ok()
+    };
+    let defaults = DatagenSettings::default();
+    let limits = match (value("depth"), value("nodes")) {
+        (Some(depth), _) => SearchLimits::depth(depth.clamp(1, 100) as i8),
+        (None, Some(nodes)) => SearchLimits::nodes(nodes),
+        (None, None) => defaults.limits,
+    };
+    let settings = DatagenSettings {
+        games: value("games").map_or(defaults.games, |n| n as usize),
+        threads: value("threads").map_or(defaults.threads, |n| (n as usize).max(1)),
+        random_plies: value("random").map_or(defaults.random_plies, |n| n as usize),
+        limits,
+    };
+    let file = match OpenOptions::new().create(true).append(true).open(path) {
+        Ok(file) => file,
+        Err(error) => return println!("could not open {path}: {error}"),
+    };
+    let mut out = BufWriter::new(file);
+
+    let mg = Arc::new(MoveGenerator::new());
+    let started = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = mpsc::channel();
+    let workers: Vec<_> = (0..settings.threads)
+        .map(|_| {
+            let (mg, started, sender) = (Arc::clone(&mg), Arc::clone(&started), sender.clone());
+            let settings = settings.clone();
+            thread::Builder::new()
+                .name(String::from("datagen"))
+                .stack_size(THREAD_STACK_SIZE)
+                .spawn(move || {
+                    let mut search = Search::new(Arc::clone(&mg), HASH_MB);
+                    let mut rng = rand::rng();
+                    while started.fetch_add(1, Ordering::Relaxed) < settings.games {
+                        // an unusable opening doesn't count as a game
+                        let record = loop {
+                            if let Some(record) = play_game(&mut search, &mg, &settings, &mut rng) {
+                                break record;
+                            }
+                        };
+                        if sender.send(record).is_err() {
+                            return;
+                        }
+                    }
+                })
+                .expect("failed to spawn a datagen thread")
+        })
+        .collect();
+    drop(sender);
+
+    let (mut games, mut positions) = (0, 0);
+    for record in receiver {
+        if let Err(error) = out.write_all(record.lines().as_bytes()) {
+            println!("could not write {path}: {error}");
+            break;
+        }
+        games += 1;
+        positions += record.positions.len();
+        if games % 100 == 0 || games == settings.games {
+            println!("{games} games, {positions} positions");
+        }
+    }
+    let _ = out.flush();
+    for worker in workers {
+        let _ = worker.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_play_game() {
+        let mg = Arc::new(MoveGenerator::new());
+        let mut search = Search::new(Arc::clone(&mg), 1);
+        let settings = DatagenSettings {
+            limits: SearchLimits::depth(2),
+            ..Default::default()
+        };
+        let mut rng = rand::rng();
+        let record = loop {
+            if let Some(record) = play_game(&mut search, &mg, &settings, &mut rng) {
+                break record;
+            }
+        };
+        assert!([0.0, 0.5, 1.0].contains(&record.result));
+        assert!(!record.positions.is_empty());
+        for (fen, _) in &record.positions {
+            let board = Board::fen(Some(fen.clone()));
+            assert!(!board.in_check(&mg));
+        }
+        let lines = record.lines();
+        let first = lines.lines().next().unwrap();
+        assert_eq!(first.split(" | ").count(), 3);
+    }
+}
@@ -7,6 +7,7 @@ pub const EMPTY: u64 = 0;
 // MAX MOVES
 pub const MAX_GAME_MOVES: usize = 1024;
 pub const MAX_MOVE_RULE: usize = 100;
+pub const MAX_LEGAL_MOVES: usize = 256;
 
 // FEN STRINGS
 pub const SHORT_FEN_LENGTH: usize = 4;
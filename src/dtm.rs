@@ -0,0 +1,424 @@
+mod generate;
+pub mod position;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use self::position::Material;
+use crate::bitboard::Bitboard;
+use crate::board::pieces::Pieces;
+use crate::board::Board;
+use crate::defs::*;
+use crate::move_generator::MoveGenerator;
+
+// Each position is stored as one byte: 0 for a draw, 255 for positions
+// which can't occur and otherwise the number of plies to mate plus one.
+// An odd number of plies is a win for the side to move, an even one a
+// loss.
+const DRAW: u8 = 0;
+const INVALID: u8 = u8::MAX;
+const MAX_PLIES: usize = INVALID as usize - 2;
+
+const MAGIC: &[u8; 4] = b"DTM2";
+
+// Distance to mate of the side to move, in plies.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Dtm {
+    Win(u32),
+    Draw,
+    Loss(u32),
+}
+
+// A distance to mate table of one material, made by our own generator.
+// Unlike the Syzygy tables these know how far the mate is and not just
+// who wins, which makes them useful to study endgames, but they ignore
+// the fifty move rule. Generating takes two bytes a position: a few
+// megabytes for four pieces, about 700 MB for five without pawns and
+// 2 GB for five with pawns.
+pub struct DtmTable {
+    material: Material,
+    values: Vec<u8>,
+}
+
+impl DtmTable {
+    pub fn name(&self) -> String {
+        self.material.name()
+    }
+
+    pub fn probe(&self, board: &Board) -> Option<Dtm> {
+        if board.game_state.castling != 0 || board.game_state.en_passant.is_some() {
+            return None;
+        }
+        let (pieces, stm) = board_pieces(board);
+        self.dtm(&pieces, stm)
+    }
+
+    // The distance to mate of the pieces with `stm` to move, None if they
+    // aren't of this material or can't occur.
+    pub fn dtm(&self, pieces: &[(u8, Pieces, Square)], stm: u8) -> Option<Dtm> {
+        match self.value(pieces, stm)? {
+            DRAW => Some(Dtm::Draw),
+            INVALID => None,
+            value if value % 2 == 0 => Some(Dtm::Win(value as u32 - 1)),
+            value => Some(Dtm::Loss(value as u32 - 1)),
+        }
+    }
+
+    // The stored value of the pieces with `stm` to move, flipping the
+    // colours if the table has them the other way around.
+    fn value(&self, pieces: &[(u8, Pieces, Square)], stm: u8) -> Option<u8> {
+        let position = match self.material.arrange(pieces, stm) {
+            Some(position) => position,
+            None => {
+                let flipped: Vec<_> = pieces
+                    .iter()
+                    .map(|&(side, piece, square)| (side ^ 1, piece, square ^ 56))
+                    .collect();
+                self.material.arrange(&flipped, stm ^ 1)?
+            }
+        };
+        Some(self.values[self.material.index(&position)])
+    }
+
+    // The longest win with the stronger side to move, in plies.
+    pub fn longest_win(&self) -> u32 {
+        // White to move comes first
+        self.values[..self.values.len() / NrOf::SIDES]
+            .iter()
+            .filter(|&&value| value != DRAW && value != INVALID && value % 2 == 0)
+            .map(|&value| value as u32 - 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    // The file holds the magic, the length of the name and the name, and
+    // then the values run-length encoded: each run is a value followed by
+    // its length as a little endian base 128 number. Runs are long since
+    // drawn and invalid positions come in blocks.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let name = self.name();
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(name.len() as u8);
+        bytes.extend_from_slice(name.as_bytes());
+        let mut i = 0;
+        while i < self.values.len() {
+            let value = self.values[i];
+            let run = self.values[i..].iter().take_while(|&&v| v == value).count();
+            bytes.push(value);
+            let mut length = run;
+            while length >= 0x80 {
+                bytes.push(length as u8 | 0x80);
+                length >>= 7;
+            }
+            bytes.push(length as u8);
+            i += run;
+        }
+        fs::File::create(path)?.write_all(&bytes)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut bytes = Vec::new();
+        fs::File::open(path)?.read_to_end(&mut bytes)?;
+        if !bytes.starts_with(MAGIC) || bytes.len() <= MAGIC.len() {
+            return Err(invalid("not a distance to mate table"));
+        }
+        let length = bytes[MAGIC.len()] as usize;
+        let start = MAGIC.len() + 1;
+        let name = bytes
+            .get(start..start + length)
+            .and_then(|name| std::str::from_utf8(name).ok())
+            .ok_or_else(|| invalid("bad material name"))?;
+        let material = Material::parse(name).ok_or_else(|| invalid("bad material name"))?;
+        let mut values = Vec::with_capacity(material.size());
+        let mut runs = bytes[start + length..].iter();
+        while let Some(&value) = runs.next() {
+            let (mut run, mut shift) = (0, 0);
+            loop {
+                let &byte = runs.next().ok_or_else(|| invalid("truncated table"))?;
+                run |= ((byte & 0x7f) as usize) << shift;
+                shift += 7;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            values.resize(values.len() + run, value);
+        }
+        if values.len() != material.size() {
+            return Err(invalid("wrong table size"));
+        }
+        Ok(Self { material, values })
+    }
+}
+
+// The tables of several materials by name, with those they depend on.
+pub struct DtmTables {
+    tables: HashMap<String, DtmTable>,
+}
+
+impl Default for DtmTables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DtmTables {
+    pub fn new() -> Self {
+        Self {
+            tables: HashMap::new(),
+        }
+    }
+
+    // Generates the table of `name` (like `KRvKN`) and, first, every table
+    // a capture or promotion leads to. Tables already there are kept.
+    pub fn generate(&mut self, mg: &MoveGenerator, name: &str) -> Option<&DtmTable> {
+        let material = Material::parse(name)?.stored();
+        self.generate_material(mg, &material);
+        self.tables.get(&material.name())
+    }
+
+    fn generate_material(&mut self, mg: &MoveGenerator, material: &Material) {
+        if self.tables.contains_key(&material.name()) {
+            return;
+        }
+        for successor in material.successors() {
+            self.generate_material(mg, &successor);
+        }
+        let values = generate::generate(mg, material, self);
+        let table = DtmTable {
+            material: material.clone(),
+            values,
+        };
+        self.tables.insert(table.name(), table);
+    }
+
+    // The stored value of a position the generator reaches by a capture
+    // or promotion. Its table has to be there.
+    fn value(&self, pieces: &[(u8, Pieces, Square)], stm: u8) -> u8 {
+        let table = &self.tables[&table_name(pieces)];
+        table.value(pieces, stm).unwrap()
+    }
+}
+
+// `rust_chess dtm KQvK KRvKN` generates the tables and writes them to
+// `KQvK.dtm` and `KRvKN.dtm` in the working directory, and
+// `rust_chess dtm probe <fen>` looks the position up in them.
+pub fn run(args: &[String]) {
+    if args.first().map(String::as_str) == Some("probe") {
+        probe(&args[1..].join(" "));
+        return;
+    }
+    let mg = MoveGenerator::new();
+    let mut tables = DtmTables::new();
+    for name in args {
+        let Some(table) = tables.generate(&mg, name) else {
+            println!(
+                "{name}: not a material of at most {} pieces, with pawns on one side only",
+                position::MAX_PIECES
+            );
+            continue;
+        };
+        let file = format!("{}.dtm", table.name());
+        match table.save(Path::new(&file)) {
+            Ok(()) => println!(
+                "{}: longest win {} plies, written to {file}",
+                table.name(),
+                table.longest_win()
+            ),
+            Err(error) => println!("{}: could not write {file}: {error}", table.name()),
+        }
+    }
+}
+
+fn probe(fen: &str) {
+    let board = Board::fen(Some(fen.to_string()));
+    let (pieces, _) = board_pieces(&board);
+    let file = format!("{}.dtm", table_name(&pieces));
+    let table = match DtmTable::load(Path::new(&file)) {
+        Ok(table) => table,
+        Err(error) => return println!("could not read {file}: {error}"),
+    };
+    match table.probe(&board) {
+        Some(Dtm::Win(plies)) => println!("win, mate in {} moves", plies.div_ceil(2)),
+        Some(Dtm::Loss(plies)) => println!("loss, mated in {} moves", plies / 2),
+        Some(Dtm::Draw) => println!("draw"),
+        None => println!("not in the table"),
+    }
+}
+
+fn table_name(pieces: &[(u8, Pieces, Square)]) -> String {
+    Material::of(pieces).stored().name()
+}
+
+fn board_pieces(board: &Board) -> (Vec<(u8, Pieces, Square)>, u8) {
+    let mut pieces = Vec::new();
+    for side in [WHITE, BLACK] {
+        for square in Bitboard::new(board.get_side(side)) {
+            pieces.push((side, board.piece_list[square as usize], square));
+        }
+    }
+    (pieces, board.we())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::position::Successor;
+    use super::*;
+    use crate::board::movelist::MoveList;
+
+    fn board(fen: &str) -> Board {
+        Board::fen(Some(fen.to_string()))
+    }
+
+    #[test]
+    fn test_material() {
+        let material = Material::parse("KNRvK").unwrap();
+        assert_eq!(material.name(), "KRNvK");
+        assert_eq!(Material::parse("KvKQ").unwrap().stored().name(), "KQvK");
+        assert_eq!(Material::parse("KRvKN").unwrap().stored().name(), "KRvKN");
+        assert!(Material::parse("KQQQvKQ").is_none());
+        assert!(Material::parse("KQvQ").is_none());
+        assert!(Material::parse("KPvKP").is_none());
+        let names: Vec<String> = Material::parse("KPvK")
+            .unwrap()
+            .successors()
+            .iter()
+            .map(Material::name)
+            .collect();
+        assert_eq!(names, ["KvK", "KQvK", "KRvK", "KBvK", "KNvK"]);
+    }
+
+    // The longest mates are well known: 10 moves with the queen and 16
+    // with the rook.
+    #[test]
+    fn test_kqk_krk() {
+        let mg = MoveGenerator::new();
+        let mut tables = DtmTables::new();
+        assert_eq!(tables.generate(&mg, "KQvK").unwrap().longest_win(), 19);
+        assert_eq!(tables.generate(&mg, "KvKR").unwrap().longest_win(), 31);
+
+        let kqk = &tables.tables["KQvK"];
+        assert_eq!(
+            kqk.probe(&board("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1")),
+            Some(Dtm::Win(1))
+        );
+        assert_eq!(
+            kqk.probe(&board("k6Q/8/1K6/8/8/8/8/8 b - - 0 1")),
+            Some(Dtm::Loss(0))
+        );
+        assert_eq!(
+            kqk.probe(&board("k7/1Q6/8/8/8/8/8/7K b - - 0 1")),
+            Some(Dtm::Draw)
+        );
+        assert_eq!(kqk.probe(&board("k7/8/1K6/8/8/8/8/6R1 w - - 0 1")), None);
+        // the colours flipped
+        assert_eq!(
+            kqk.probe(&board("6q1/8/8/8/8/1k6/8/K7 b - - 0 1")),
+            Some(Dtm::Win(1))
+        );
+        // stalemate
+        assert_eq!(
+            kqk.probe(&board("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1")),
+            Some(Dtm::Draw)
+        );
+
+        // mirrored along the diagonal, and on it
+        let krk = &tables.tables["KRvK"];
+        let a1 = krk.probe(&board("8/8/8/8/8/2k5/8/K6R w - - 0 1"));
+        assert_eq!(a1, Some(Dtm::Win(27)));
+        assert_eq!(krk.probe(&board("R7/8/8/8/8/2k5/8/K7 w - - 0 1")), a1);
+        assert_eq!(
+            krk.probe(&board("8/8/8/8/3k4/8/1R6/K7 b - - 0 1")),
+            Some(Dtm::Loss(32))
+        );
+    }
+
+    // The moves back are our own, the moves forward the board's: going back
+    // from each quiet move has to find the position again.
+    #[test]
+    fn test_predecessors_match_moves() {
+        let mg = MoveGenerator::new();
+        let mut board = Board::new();
+        for name in ["KPvK", "KRvK"] {
+            let material = Material::parse(name).unwrap();
+            for index in (0..material.size()).step_by(3) {
+                let position = material.position(index);
+                if material.index(&position) != index || !material.is_valid(&mg, &position) {
+                    continue;
+                }
+                material.successors_of(&mg, &mut board, &position, |successor| {
+                    let Successor::Quiet(next) = successor else {
+                        return;
+                    };
+                    let mut found = false;
+                    material.predecessors_of(&mg, &next, |previous| {
+                        found |= material.index(&previous) == index;
+                    });
+                    assert!(found, "{name}: no way back to position {index}");
+                });
+            }
+        }
+    }
+
+    // Each value has to follow from the values after the board's moves:
+    // the fastest win over a move to a loss, else a draw if there is one or
+    // stalemate, else the longest loss.
+    #[test]
+    fn test_values_match_moves() {
+        let mg = MoveGenerator::new();
+        let mut tables = DtmTables::new();
+        tables.generate(&mg, "KPvK");
+        let table = &tables.tables["KPvK"];
+        let mut board = Board::new();
+        let mut moves = MoveList::new();
+        for index in (0..table.values.len()).step_by(3) {
+            if table.values[index] == INVALID {
+                continue;
+            }
+            let position = table.material.position(index);
+            board.setup(&table.material.pieces_of(&position), position.stm);
+            board.get_legal_moves(&mg, &mut moves);
+            let mut after = Vec::new();
+            for &m in moves.iter() {
+                board.make(m);
+                let (pieces, stm) = board_pieces(&board);
+                board.unmake();
+                after.push(tables.tables[&table_name(&pieces)].dtm(&pieces, stm));
+            }
+            let losses = after.iter().filter_map(|&dtm| match dtm {
+                Some(Dtm::Loss(plies)) => Some(plies),
+                _ => None,
+            });
+            let wins = after.iter().filter_map(|&dtm| match dtm {
+                Some(Dtm::Win(plies)) => Some(plies),
+                _ => None,
+            });
+            let expected = if let Some(plies) = losses.min() {
+                Dtm::Win(plies + 1)
+            } else if after.contains(&Some(Dtm::Draw)) || moves.is_empty() && !board.in_check(&mg) {
+                Dtm::Draw
+            } else {
+                Dtm::Loss(wins.max().map_or(0, |plies| plies + 1))
+            };
+            assert_eq!(
+                table.dtm(&table.material.pieces_of(&position), position.stm),
+                Some(expected)
+            );
+        }
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mg = MoveGenerator::new();
+        let mut tables = DtmTables::new();
+        let table = tables.generate(&mg, "KRvK").unwrap();
+        let path = std::env::temp_dir().join(format!("rust_chess_{}.dtm", std::process::id()));
+        table.save(&path).unwrap();
+        let loaded = DtmTable::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.name(), "KRvK");
+        assert!(loaded.values == table.values);
+    }
+}
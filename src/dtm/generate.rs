@@ -0,0 +1,151 @@
+use super::position::{Material, Position, Successor};
+use super::{DtmTables, DRAW, INVALID, MAX_PLIES};
+use crate::board::Board;
+use crate::move_generator::MoveGenerator;
+
+// In place of the number of moves left: the position can't lose any more,
+// it has a move to a draw or a win.
+const SAFE: u8 = u8::MAX;
+
+// What the captures and promotions of a position lead to.
+struct Exits {
+    // the shortest win
+    win: Option<u8>,
+    draw: bool,
+    // the longest loss
+    loss: u8,
+}
+
+// Retrograde analysis of one material. The tables of all materials a
+// capture or promotion leads to must already be in `tables`.
+//
+// Positions are resolved in order of their distance to mate, so the
+// first time one is reached is the shortest win. A position is resolved
+// as a win one ply after any successor is resolved as a loss, and as a
+// loss when the last of its moves is resolved as a win for the opponent,
+// which makes that the longest defence. Moves into other tables are known
+// up front and only decide when that happens.
+//
+// Besides the values, only the number of moves left is kept for each
+// position, two bytes in all. The longest loss through a capture or
+// promotion is looked up again when it is needed.
+pub fn generate(mg: &MoveGenerator, material: &Material, tables: &DtmTables) -> Vec<u8> {
+    let size = material.size();
+    let mut values = vec![DRAW; size];
+    // moves within the table not yet known to lose, or SAFE
+    let mut remaining = vec![0u8; size];
+    let mut queue: Vec<Vec<u32>> = vec![Vec::new(); INVALID as usize + 1];
+    let mut board = Board::new();
+
+    for index in 0..size {
+        let position = material.position(index);
+        // the other form of a symmetric position is stored instead
+        if material.index(&position) != index || !material.is_valid(mg, &position) {
+            values[index] = INVALID;
+            continue;
+        }
+        let (moves, quiet, exits) = successors(mg, &mut board, material, tables, &position);
+        remaining[index] = if exits.win.is_some() || exits.draw {
+            SAFE
+        } else if quiet.iter().any(|next| material.is_symmetric(next))
+            || material.is_symmetric(&position)
+        {
+            pairs_to(mg, material, index, &quiet)
+        } else {
+            quiet.len() as u8
+        };
+        if let Some(plies) = exits.win {
+            queue[plies as usize].push(index as u32);
+        } else if moves == 0 {
+            if material.in_check(mg, &position) {
+                queue[0].push(index as u32);
+            }
+        } else if quiet.is_empty() && !exits.draw {
+            queue[exits.loss as usize].push(index as u32);
+        }
+    }
+
+    for plies in 0..queue.len() {
+        let resolved = std::mem::take(&mut queue[plies]);
+        for index in resolved {
+            let index = index as usize;
+            if values[index] != DRAW {
+                continue;
+            }
+            assert!(plies <= MAX_PLIES, "mate too long for the table format");
+            values[index] = plies as u8 + 1;
+            let position = material.position(index);
+            material.predecessors_of(mg, &position, |previous| {
+                let previous_index = material.index(&previous);
+                if values[previous_index] != DRAW {
+                    return;
+                }
+                if plies % 2 == 0 {
+                    queue[plies + 1].push(previous_index as u32);
+                    return;
+                }
+                if remaining[previous_index] == SAFE {
+                    return;
+                }
+                remaining[previous_index] -= 1;
+                if remaining[previous_index] == 0 {
+                    let (_, _, exits) = successors(mg, &mut board, material, tables, &previous);
+                    let longest = (plies + 1).max(exits.loss as usize);
+                    queue[longest].push(previous_index as u32);
+                }
+            });
+        }
+    }
+    values
+}
+
+// The number of legal moves, the positions the quiet ones lead to and
+// where the others lead.
+fn successors(
+    mg: &MoveGenerator,
+    board: &mut Board,
+    material: &Material,
+    tables: &DtmTables,
+    position: &Position,
+) -> (u8, Vec<Position>, Exits) {
+    let (mut moves, mut quiet) = (0, Vec::new());
+    let mut exits = Exits {
+        win: None,
+        draw: false,
+        loss: 0,
+    };
+    material.successors_of(mg, board, position, |successor| {
+        moves += 1;
+        match successor {
+            Successor::Quiet(next) => quiet.push(next),
+            Successor::Exit(pieces, stm) => match tables.value(&pieces, stm) {
+                DRAW => exits.draw = true,
+                // the opponent is mated in `value - 1` plies
+                value if value % 2 == 1 => {
+                    exits.win = Some(exits.win.map_or(value, |w| w.min(value)))
+                }
+                value => exits.loss = exits.loss.max(value),
+            },
+        }
+    });
+    (moves, quiet, exits)
+}
+
+// How often the position at `index` will be counted down: once for each
+// way back from a stored successor. That is one per quiet move, unless a
+// position is its own mirror image, where two moves can lead to the same
+// stored successor, or two ways back to the same move.
+fn pairs_to(mg: &MoveGenerator, material: &Material, index: usize, quiet: &[Position]) -> u8 {
+    let mut stored: Vec<usize> = quiet.iter().map(|next| material.index(next)).collect();
+    stored.sort_unstable();
+    stored.dedup();
+    let mut pairs = 0;
+    for next in stored {
+        material.predecessors_of(mg, &material.position(next), |previous| {
+            if material.index(&previous) == index {
+                pairs += 1;
+            }
+        });
+    }
+    pairs
+}
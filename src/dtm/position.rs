@@ -0,0 +1,456 @@
+use std::cmp::Ordering;
+
+use super::board_pieces;
+use crate::bitboard::Bitboard;
+use crate::board::movelist::MoveList;
+use crate::board::pieces::Pieces;
+use crate::board::Board;
+use crate::defs::*;
+use crate::helper::get_bitmask;
+use crate::move_generator::MoveGenerator;
+
+pub const MAX_PIECES: usize = 5;
+
+const LETTERS: [(char, Pieces); 6] = [
+    ('K', Pieces::King),
+    ('Q', Pieces::Queen),
+    ('R', Pieces::Rook),
+    ('B', Pieces::Bishop),
+    ('N', Pieces::Knight),
+    ('P', Pieces::Pawn),
+];
+const PROMOTIONS: [Pieces; 4] = [Pieces::Queen, Pieces::Rook, Pieces::Bishop, Pieces::Knight];
+// where the white king of a table without pawns is: a1-d1-d4
+const TRIANGLE: [Square; 10] = [0, 1, 2, 3, 9, 10, 11, 18, 19, 27];
+
+// A material signature like `KRvKN`. The pieces are kept in table order:
+// the white king, the other white pieces, the black king and the other
+// black pieces, each side sorted as in the name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Material {
+    pub pieces: Vec<(u8, Pieces)>,
+}
+
+// One position of a table: the square of each piece in table order.
+#[derive(Clone, Copy)]
+pub struct Position {
+    pub squares: [Square; MAX_PIECES],
+    pub stm: u8,
+}
+
+// A mirror image of the board: the squares are XORed with `mirror` and
+// then, if `diagonal`, reflected along the a1-h8 diagonal.
+#[derive(Clone, Copy)]
+struct Symmetry {
+    mirror: Square,
+    diagonal: bool,
+}
+
+impl Symmetry {
+    fn apply(self, square: Square) -> Square {
+        let square = square ^ self.mirror;
+        if self.diagonal {
+            square % 8 * 8 + square / 8
+        } else {
+            square
+        }
+    }
+}
+
+// What a legal move leads to: a position of the same material, or the
+// pieces and side to move after a capture or promotion, which are looked
+// up in another table.
+pub enum Successor {
+    Quiet(Position),
+    Exit(Vec<(u8, Pieces, Square)>, u8),
+}
+
+impl Material {
+    pub fn parse(name: &str) -> Option<Self> {
+        let (white, black) = name.split_once('v')?;
+        let mut pieces = Vec::new();
+        for (side, name) in [(WHITE, white), (BLACK, black)] {
+            let mut letters = name.chars();
+            if letters.next() != Some('K') {
+                return None;
+            }
+            pieces.push((side, Pieces::King));
+            for letter in letters {
+                let &(_, piece) = LETTERS[1..].iter().find(|(c, _)| *c == letter)?;
+                pieces.push((side, piece));
+            }
+        }
+        // Positions right after a double push can have an en passant
+        // capture, which the tables don't store.
+        let pawns = |side| pieces.contains(&(side, Pieces::Pawn));
+        if pawns(WHITE) && pawns(BLACK) {
+            return None;
+        }
+        (pieces.len() <= MAX_PIECES).then(|| Self::sorted(pieces))
+    }
+
+    // The material of a list of pieces in any order.
+    pub fn of(pieces: &[(u8, Pieces, Square)]) -> Self {
+        Self::sorted(
+            pieces
+                .iter()
+                .map(|&(side, piece, _)| (side, piece))
+                .collect(),
+        )
+    }
+
+    fn sorted(mut pieces: Vec<(u8, Pieces)>) -> Self {
+        let order = |piece| LETTERS.iter().position(|&(_, p)| p == piece).unwrap();
+        pieces.sort_by_key(|&(side, piece)| (side, order(piece)));
+        Self { pieces }
+    }
+
+    pub fn name(&self) -> String {
+        let letter = |piece| LETTERS.iter().find(|&&(_, p)| p == piece).unwrap().0;
+        let side = |side| {
+            self.pieces
+                .iter()
+                .filter(|&&(s, _)| s == side)
+                .map(|&(_, piece)| letter(piece))
+                .collect::<String>()
+        };
+        format!("{}v{}", side(WHITE), side(BLACK))
+    }
+
+    // Tables are only made with the stronger side as White; the other
+    // colouring is probed by flipping the board.
+    pub fn needs_flip(&self) -> bool {
+        let key = |side| {
+            let value: u32 = self
+                .pieces
+                .iter()
+                .filter(|&&(s, _)| s == side)
+                .map(|&(_, piece)| match piece {
+                    Pieces::Queen => 9,
+                    Pieces::Rook => 5,
+                    Pieces::Bishop | Pieces::Knight => 3,
+                    Pieces::Pawn => 1,
+                    _ => 0,
+                })
+                .sum();
+            let names = self.name();
+            let (white, black) = names.split_once('v').unwrap();
+            let name = if side == WHITE { white } else { black };
+            (value, name.to_string())
+        };
+        key(BLACK) > key(WHITE)
+    }
+
+    // The material with the colours as its table has them.
+    pub fn stored(self) -> Self {
+        if self.needs_flip() {
+            self.flipped()
+        } else {
+            self
+        }
+    }
+
+    pub fn flipped(&self) -> Self {
+        Self::sorted(
+            self.pieces
+                .iter()
+                .map(|&(side, piece)| (side ^ 1, piece))
+                .collect(),
+        )
+    }
+
+    pub fn has_pawns(&self) -> bool {
+        self.pieces.iter().any(|&(_, piece)| piece == Pieces::Pawn)
+    }
+
+    // The materials one capture or promotion away, as they are stored.
+    pub fn successors(&self) -> Vec<Material> {
+        let mut successors = Vec::new();
+        let mut add = |pieces: Vec<(u8, Pieces)>| {
+            let material = Self::sorted(pieces).stored();
+            if !successors.contains(&material) {
+                successors.push(material);
+            }
+        };
+        let without = |skip: Option<usize>| -> Vec<(u8, Pieces)> {
+            (0..self.pieces.len())
+                .filter(|&i| Some(i) != skip)
+                .map(|i| self.pieces[i])
+                .collect()
+        };
+        for (i, &(side, piece)) in self.pieces.iter().enumerate() {
+            if piece != Pieces::King {
+                add(without(Some(i)));
+            }
+            if piece != Pieces::Pawn {
+                continue;
+            }
+            for promotion in PROMOTIONS {
+                for captured in std::iter::once(None).chain((0..self.pieces.len()).map(Some)) {
+                    let capturable = captured.is_none_or(|j| {
+                        self.pieces[j].0 != side && self.pieces[j].1 != Pieces::King
+                    });
+                    if capturable {
+                        let mut pieces = without(captured);
+                        let at = pieces.iter().position(|&p| p == (side, piece)).unwrap();
+                        pieces[at] = (side, promotion);
+                        add(pieces);
+                    }
+                }
+            }
+        }
+        successors
+    }
+
+    // Only positions with the white king on the a- to d-files are stored,
+    // the others are their mirror images. Without pawns the board can also
+    // be mirrored vertically and along the a1-h8 diagonal, which leaves the
+    // king in the a1-d1-d4 triangle. With the king on the diagonal, the
+    // first piece off it decides: it is kept below.
+    fn king_squares(&self) -> usize {
+        if self.has_pawns() {
+            32
+        } else {
+            TRIANGLE.len()
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        NrOf::SIDES * self.king_squares() * NrOf::SQUARES.pow(self.pieces.len() as u32 - 1)
+    }
+
+    // How the squares of the position are mapped to its stored form.
+    fn symmetry(&self, squares: &[Square]) -> Symmetry {
+        let white_king = squares[0];
+        let mut mirror = 0;
+        if white_king % 8 > 3 {
+            mirror ^= 7;
+        }
+        if self.has_pawns() {
+            return Symmetry {
+                mirror,
+                diagonal: false,
+            };
+        }
+        if white_king / 8 > 3 {
+            mirror ^= 56;
+        }
+        let king = white_king ^ mirror;
+        let diagonal = match (king / 8).cmp(&(king % 8)) {
+            Ordering::Greater => true,
+            Ordering::Less => false,
+            Ordering::Equal => squares[1..]
+                .iter()
+                .map(|&square| square ^ mirror)
+                .find(|&square| square / 8 != square % 8)
+                .is_some_and(|square| square / 8 > square % 8),
+        };
+        Symmetry { mirror, diagonal }
+    }
+
+    // The position is its own mirror image: without pawns, everything is on
+    // the a1-h8 diagonal.
+    pub fn is_symmetric(&self, position: &Position) -> bool {
+        let squares = &position.squares[..self.pieces.len()];
+        let symmetry = self.symmetry(squares);
+        !self.has_pawns()
+            && squares
+                .iter()
+                .map(|&square| symmetry.apply(square))
+                .all(|square| square / 8 == square % 8)
+    }
+
+    pub fn index(&self, position: &Position) -> usize {
+        let squares = &position.squares[..self.pieces.len()];
+        let symmetry = self.symmetry(squares);
+        let king = symmetry.apply(squares[0]);
+        let king = if self.has_pawns() {
+            king as usize / 8 * 4 + king as usize % 8
+        } else {
+            TRIANGLE.iter().position(|&square| square == king).unwrap()
+        };
+        let mut index = position.stm as usize * self.king_squares() + king;
+        for &square in &squares[1..] {
+            index = index * NrOf::SQUARES + symmetry.apply(square) as usize;
+        }
+        index
+    }
+
+    pub fn position(&self, mut index: usize) -> Position {
+        let mut squares = [0; MAX_PIECES];
+        for square in squares[1..self.pieces.len()].iter_mut().rev() {
+            *square = (index % NrOf::SQUARES) as Square;
+            index /= NrOf::SQUARES;
+        }
+        let king = index % self.king_squares();
+        squares[0] = if self.has_pawns() {
+            (king / 4 * 8 + king % 4) as Square
+        } else {
+            TRIANGLE[king]
+        };
+        let stm = (index / self.king_squares()) as u8;
+        Position { squares, stm }
+    }
+
+    // The position of pieces given in any order, None if they don't make
+    // up this material.
+    pub fn arrange(&self, pieces: &[(u8, Pieces, Square)], stm: u8) -> Option<Position> {
+        if pieces.len() != self.pieces.len() {
+            return None;
+        }
+        let mut squares = [0; MAX_PIECES];
+        let mut used = [false; MAX_PIECES];
+        for (slot, &(side, piece)) in self.pieces.iter().enumerate() {
+            let found = (0..pieces.len())
+                .find(|&i| !used[i] && pieces[i].0 == side && pieces[i].1 == piece)?;
+            used[found] = true;
+            squares[slot] = pieces[found].2;
+        }
+        Some(Position { squares, stm })
+    }
+
+    pub fn pieces_of(&self, position: &Position) -> Vec<(u8, Pieces, Square)> {
+        self.pieces
+            .iter()
+            .zip(position.squares)
+            .map(|(&(side, piece), square)| (side, piece, square))
+            .collect()
+    }
+
+    fn king(&self, side: u8) -> usize {
+        self.pieces
+            .iter()
+            .position(|&p| p == (side, Pieces::King))
+            .unwrap()
+    }
+
+    fn occupancy(&self, position: &Position) -> u64 {
+        (0..self.pieces.len()).fold(0, |bb, i| bb | get_bitmask(position.squares[i]))
+    }
+
+    // Does `side` attack `square`?
+    fn attacks(
+        &self,
+        mg: &MoveGenerator,
+        position: &Position,
+        square: Square,
+        side: u8,
+        occupancy: u64,
+    ) -> bool {
+        (0..self.pieces.len())
+            .filter(|&i| self.pieces[i].0 == side)
+            .any(|i| {
+                let from = position.squares[i];
+                let attacks = match self.pieces[i].1 {
+                    Pieces::Pawn => mg.pawn_attacks(side, from),
+                    piece => mg.attacks(piece, from, occupancy),
+                };
+                attacks & get_bitmask(square) != 0
+            })
+    }
+
+    // Distinct squares, no pawns on the first or last rank, and the side
+    // which just moved isn't in check.
+    pub fn is_valid(&self, mg: &MoveGenerator, position: &Position) -> bool {
+        let occupancy = self.occupancy(position);
+        if occupancy.count_ones() as usize != self.pieces.len() {
+            return false;
+        }
+        let pawn_on_edge = (0..self.pieces.len())
+            .any(|i| self.pieces[i].1 == Pieces::Pawn && matches!(position.squares[i] / 8, 0 | 7));
+        let king = position.squares[self.king(position.stm ^ 1)];
+        !pawn_on_edge && !self.attacks(mg, position, king, position.stm, occupancy)
+    }
+
+    pub fn in_check(&self, mg: &MoveGenerator, position: &Position) -> bool {
+        let king = position.squares[self.king(position.stm)];
+        let occupancy = self.occupancy(position);
+        self.attacks(mg, position, king, position.stm ^ 1, occupancy)
+    }
+
+    // Calls `f` for every legal move of the side to move, as the board
+    // generates them.
+    pub fn successors_of(
+        &self,
+        mg: &MoveGenerator,
+        board: &mut Board,
+        position: &Position,
+        mut f: impl FnMut(Successor),
+    ) {
+        board.setup(&self.pieces_of(position), position.stm);
+        let mut moves = MoveList::new();
+        board.get_legal_moves(mg, &mut moves);
+        for &m in moves.iter() {
+            if m.is_quiet() {
+                // the piece keeps its slot
+                let slot = (0..self.pieces.len())
+                    .find(|&i| position.squares[i] == m.from())
+                    .unwrap();
+                let mut next = *position;
+                next.squares[slot] = m.to();
+                next.stm ^= 1;
+                f(Successor::Quiet(next));
+            } else {
+                board.make(m);
+                let (pieces, stm) = board_pieces(board);
+                board.unmake();
+                f(Successor::Exit(pieces, stm));
+            }
+        }
+    }
+
+    // Calls `f` for every valid position from which a quiet move of the
+    // side which just moved leads to `position`.
+    pub fn predecessors_of(
+        &self,
+        mg: &MoveGenerator,
+        position: &Position,
+        mut f: impl FnMut(Position),
+    ) {
+        let them = position.stm ^ 1;
+        let occupancy = self.occupancy(position);
+        let king = position.squares[self.king(position.stm)];
+        for slot in (0..self.pieces.len()).filter(|&i| self.pieces[i].0 == them) {
+            let piece = self.pieces[slot].1;
+            let to = position.squares[slot];
+            let origins = match piece {
+                Pieces::Pawn => pawn_origins(them, to, occupancy),
+                _ => mg.attacks(piece, to, occupancy) & !occupancy,
+            };
+            for from in Bitboard::new(origins) {
+                let mut previous = *position;
+                previous.squares[slot] = from;
+                previous.stm = them;
+                let occupancy = occupancy & !get_bitmask(to) | get_bitmask(from);
+                if !self.attacks(mg, &previous, king, them, occupancy) {
+                    f(previous);
+                }
+            }
+        }
+    }
+}
+
+// Squares a pawn of `side` on `to` can have been pushed from.
+fn pawn_origins(side: u8, to: Square, occupancy: u64) -> u64 {
+    let rank = if side == WHITE { to / 8 } else { 7 - to / 8 };
+    if rank < 2 {
+        return 0;
+    }
+    let single = if side == WHITE {
+        get_bitmask(to - 8)
+    } else {
+        get_bitmask(to + 8)
+    };
+    if single & occupancy != 0 {
+        return 0;
+    }
+    if rank != 3 {
+        return single;
+    }
+    let double = if side == WHITE {
+        single >> 8
+    } else {
+        single << 8
+    };
+    single | double & !occupancy
+}
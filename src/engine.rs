@@ -1 +1,202 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
+use crate::board::Board;
+use crate::move_generator::MoveGenerator;
+use crate::search::limits::SearchLimits;
+use crate::search::params::SearchParams;
+use crate::search::transposition::TT;
+use crate::search::{Search, SearchInfo, SearchResult, THREAD_STACK_SIZE};
+use crate::syzygy::Tablebases;
+
+// Everything the main loop reacts to: input lines from the reader thread,
+// and reports from the search thread.
+pub enum Event {
+    Command(String),
+    Info(SearchInfo),
+    BestMove(SearchResult),
+}
+
+// Runs the search in a worker thread, so the thread handling the protocol
+// stays free to answer `isready` or to `stop` the search. The searches and
+// their tables move into the worker for the duration of a search and come
+// back when it is joined. They are boxed, as they are too big to be moved
+// around on the stack.
+//
+// With more than one thread, the worker starts the helper searches of a
+// Lazy SMP search next to the main one and stops them when it is done.
+pub struct Engine {
+    // the main search first, then the helpers
+    searches: Vec<Box<Search>>,
+    worker: Option<JoinHandle<Vec<Box<Search>>>>,
+    stop: Arc<AtomicBool>,
+    pondering: Arc<AtomicBool>,
+    events: Sender<Event>,
+}
+
+impl Engine {
+    pub fn new(mg: Arc<MoveGenerator>, hash_mb: usize, events: Sender<Event>) -> Self {
+        let search = Box::new(Search::new(mg, hash_mb));
+        Self {
+            stop: search.stop_flag(),
+            pondering: search.ponder_flag(),
+            searches: vec![search],
+            worker: None,
+            events,
+        }
+    }
+
+    pub fn is_searching(&self) -> bool {
+        self.worker.is_some()
+    }
+
+    pub fn threads(&self) -> usize {
+        self.searches.len()
+    }
+
+    // Must not be searching.
+    pub fn set_threads(&mut self, threads: usize) {
+        let threads = threads.max(1);
+        self.searches.truncate(threads);
+        while self.threads() < threads {
+            let helper = self.searches[0].helper(self.threads());
+            self.searches.push(Box::new(helper));
+        }
+    }
+
+    pub fn params(&self) -> SearchParams {
+        self.searches[0].params()
+    }
+
+    // Must not be searching.
+    pub fn set_params(&mut self, params: SearchParams) {
+        for search in &mut self.searches {
+            search.set_params(params);
+        }
+    }
+
+    // A new, empty transposition table of the given size, shared by all
+    // threads. Must not be searching.
+    pub fn set_hash(&mut self, hash_mb: usize) {
+        let tt = Arc::new(TT::new(hash_mb));
+        for search in &mut self.searches {
+            search.set_tt(Arc::clone(&tt));
+        }
+    }
+
+    // Must not be searching.
+    pub fn set_tablebases(&mut self, tb: Option<Arc<Tablebases>>) {
+        for search in &mut self.searches {
+            search.set_tablebases(tb.clone());
+        }
+    }
+
+    // Starts searching in the background. Progress of the main search is
+    // sent as `Info` events, the result as one `BestMove` event once the
+    // search is over, after which `join` has to be called.
+    pub fn start(&mut self, board: &Board, limits: SearchLimits) {
+        self.join();
+        let mut searches = std::mem::take(&mut self.searches);
+        let board = Box::new(board.clone());
+        let events = self.events.clone();
+        let stop = Arc::clone(&self.stop);
+        let pondering = Arc::clone(&self.pondering);
+        stop.store(false, Ordering::Relaxed);
+        pondering.store(limits.ponder, Ordering::Relaxed);
+
+        let worker = thread::Builder::new()
+            .name(String::from("search"))
+            .stack_size(THREAD_STACK_SIZE)
+            .spawn(move || {
+                // helpers run until the main search stops them
+                let helper_limits = SearchLimits {
+                    depth: limits.depth,
+                    search_moves: limits.search_moves.clone(),
+                    ..Default::default()
+                };
+                let result = thread::scope(|scope| {
+                    let (main, helpers) = searches.split_first_mut().unwrap();
+                    let helpers: Vec<_> = helpers
+                        .iter_mut()
+                        .map(|helper| {
+                            let (board, limits) = (&board, &helper_limits);
+                            thread::Builder::new()
+                                .name(String::from("helper"))
+                                .stack_size(THREAD_STACK_SIZE)
+                                .spawn_scoped(scope, move || {
+                                    helper.iterative_deepening(board, limits, |_| ())
+                                })
+                                .expect("failed to spawn a helper thread")
+                        })
+                        .collect();
+
+                    let result = main.iterative_deepening(&board, &limits, |info| {
+                        let _ = events.send(Event::Info(info.clone()));
+                    });
+                    // the best move may only be sent once the GUI has ended
+                    // an infinite or ponder search
+                    while !stop.load(Ordering::Relaxed)
+                        && (limits.infinite || pondering.load(Ordering::Relaxed))
+                    {
+                        thread::sleep(Duration::from_millis(1));
+                    }
+                    stop.store(true, Ordering::Relaxed);
+
+                    helpers
+                        .into_iter()
+                        .map(|helper| helper.join().expect("helper thread panicked"))
+                        .fold(result, best_result)
+                });
+                let _ = events.send(Event::BestMove(result));
+                searches
+            })
+            .expect("failed to spawn the search thread");
+        self.worker = Some(worker);
+    }
+
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    pub fn ponderhit(&self) {
+        self.pondering.store(false, Ordering::Relaxed);
+    }
+
+    // Waits for the worker to finish and takes the searches back.
+    pub fn join(&mut self) {
+        if let Some(worker) = self.worker.take() {
+            self.searches = worker.join().expect("search thread panicked");
+        }
+    }
+
+    // Forget everything learned, e.g. when a new game starts. Must not be
+    // searching.
+    pub fn clear(&mut self) {
+        for search in &mut self.searches {
+            search.clear();
+        }
+    }
+}
+
+// Of the results of the threads, the one which got deepest wins, and the
+// better score among those as deep. A thread which didn't finish any
+// iteration has nothing to offer.
+fn best_result(best: SearchResult, other: SearchResult) -> SearchResult {
+    if other.best_move.is_null() || other.depth == 0 {
+        return best;
+    }
+    if (other.depth, other.score) > (best.depth, best.score) {
+        SearchResult {
+            nodes: best.nodes + other.nodes,
+            ..other
+        }
+    } else {
+        SearchResult {
+            nodes: best.nodes + other.nodes,
+            ..best
+        }
+    }
+}
@@ -0,0 +1,232 @@
+pub mod endgame;
+pub mod king_safety;
+pub mod kpk;
+pub mod nnue;
+pub mod pawns;
+pub mod pieces;
+pub mod psqt;
+pub mod score;
+pub mod trace;
+pub mod weights;
+
+use crate::bitboard::Bitboard;
+use crate::board::pieces::Pieces;
+use crate::board::Board;
+use crate::defs::*;
+use crate::move_generator::MoveGenerator;
+use pawns::{PawnEntry, PawnTable};
+use psqt::psqt;
+use score::{Score, MAX_PHASE};
+use std::sync::Arc;
+use trace::{Coefficients, NoTrace, Term, Trace};
+pub use weights::MATERIAL;
+
+// How much each piece adds to the game phase. All pieces on the board make
+// up `MAX_PHASE`.
+pub const PHASE: [i32; NrOf::PIECE_TYPES] = [0, 1, 1, 2, 4, 0];
+
+// Material, piece squares and phase of all pieces of one side, counted
+// from scratch. The board keeps them up to date incrementally.
+pub fn count_material(board: &Board, side: u8) -> (Score, Score, i32) {
+    let mut material = Score::default();
+    let mut positional = Score::default();
+    let mut phase = 0;
+    for piece in Pieces::iter() {
+        for square in Bitboard::new(board.get_pieces(side, *piece)) {
+            material += MATERIAL[*piece as usize];
+            positional += psqt(side, *piece, square);
+            phase += PHASE[*piece as usize];
+        }
+    }
+    (material, positional, phase)
+}
+
+// Evaluates positions for one search thread, with the tables caching
+// what is expensive to compute.
+pub struct Evaluator {
+    mg: Arc<MoveGenerator>,
+    pawns: PawnTable,
+}
+
+impl Evaluator {
+    pub fn new(mg: Arc<MoveGenerator>) -> Self {
+        Self {
+            mg,
+            pawns: PawnTable::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.pawns.clear();
+    }
+
+    // The evaluation from the point of view of the side to move. All terms
+    // have a middlegame and an endgame value, which are blended by how
+    // much material is left on the board.
+    pub fn evaluate(&mut self, board: &Board) -> i32 {
+        debug_assert!(incremental_is_correct(board));
+        if let Some(nnue) = &board.nnue {
+            debug_assert!(nnue.is_correct(board));
+            return nnue.evaluate(board.we());
+        }
+        let score = self.evaluate_white(board);
+        if board.we() == WHITE {
+            score
+        } else {
+            -score
+        }
+    }
+
+    fn evaluate_white(&mut self, board: &Board) -> i32 {
+        if let Some(score) = endgame::evaluate(board) {
+            return score;
+        }
+        let pawns = self.pawns.probe(board);
+        let score = terms(board, &self.mg, &pawns, &mut NoTrace);
+        let phase = (board.game_state.game_phase as i32).min(MAX_PHASE);
+        let score = score.taper(phase);
+        let strong = if score > 0 { WHITE } else { BLACK };
+        score * endgame::scale_factor(board, strong) / endgame::SCALE_NORMAL
+    }
+}
+
+// All terms of the evaluation before tapering, from White's point of view.
+fn terms(board: &Board, mg: &MoveGenerator, pawns: &PawnEntry, trace: &mut impl Trace) -> Score {
+    let state = &board.game_state;
+    let mut score = state.material[WHITE as usize] + state.psqt[WHITE as usize]
+        - state.material[BLACK as usize]
+        - state.psqt[BLACK as usize];
+    score += pawns.score + pawns::passed_pawns(board, pawns, trace);
+    score += king_safety::king_safety(board, mg, trace);
+    score += pieces::pieces(board, mg, trace);
+    score
+}
+
+// The evaluation of a position taken apart for the tuner: how often each
+// weight counts, the terms summed up before tapering, and the phase and
+// scale factor they are tapered and scaled with. None for positions one
+// of the special endgame evaluations takes care of.
+pub struct Traced {
+    pub coefficients: Coefficients,
+    pub score: Score,
+    pub phase: i32,
+    pub scale: i32,
+}
+
+pub fn trace(board: &Board, mg: &MoveGenerator) -> Option<Traced> {
+    if endgame::evaluate(board).is_some() {
+        return None;
+    }
+    let mut coefficients = Coefficients::new();
+    for side in [WHITE, BLACK] {
+        for piece in Pieces::iter() {
+            let p = *piece as usize;
+            for square in Bitboard::new(board.get_pieces(side, *piece)) {
+                coefficients.add(Term::Material, p, side, 1);
+                let index = p * NrOf::SQUARES + psqt::index(side, square);
+                coefficients.add(Term::Psqt, index, side, 1);
+            }
+        }
+    }
+    let pawns = PawnEntry::new(board, &mut coefficients);
+    let score = terms(board, mg, &pawns, &mut coefficients);
+    let phase = (board.game_state.game_phase as i32).min(MAX_PHASE);
+    let strong = if score.taper(phase) > 0 { WHITE } else { BLACK };
+    Some(Traced {
+        coefficients,
+        score,
+        phase,
+        scale: endgame::scale_factor(board, strong),
+    })
+}
+
+// Debug check of what make and unmake keep track of against a full count.
+fn incremental_is_correct(board: &Board) -> bool {
+    let state = &board.game_state;
+    let mut total_phase = 0;
+    for side in [WHITE, BLACK] {
+        let (material, positional, phase) = count_material(board, side);
+        if state.material[side as usize] != material || state.psqt[side as usize] != positional {
+            return false;
+        }
+        total_phase += phase;
+    }
+    state.game_phase as i32 == total_phase
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::movelist::MoveList;
+    use crate::move_generator::MoveGenerator;
+
+    fn eval(fen: &str) -> i32 {
+        Evaluator::new(Arc::new(MoveGenerator::new())).evaluate(&Board::fen(Some(fen.to_string())))
+    }
+
+    #[test]
+    fn test_start_position_is_balanced() {
+        let board = Board::fen(None);
+        assert_eq!(
+            Evaluator::new(Arc::new(MoveGenerator::new())).evaluate(&board),
+            0
+        );
+        let (white, white_psqt, _) = count_material(&board, WHITE);
+        let (black, black_psqt, _) = count_material(&board, BLACK);
+        assert_eq!((white, white_psqt), (black, black_psqt));
+        assert_eq!(board.game_state.game_phase as i32, MAX_PHASE);
+    }
+
+    #[test]
+    fn test_mirrored_positions_are_equal() {
+        let white = eval("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4");
+        let black = eval("rnbqk2r/pppp1ppp/5n2/2b1p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R b KQkq - 4 4");
+        assert_eq!(white, black);
+        // the same position seen from the other side
+        let other = eval("r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 4 4");
+        assert_eq!(white, -other);
+    }
+
+    #[test]
+    fn test_tapered_towards_the_endgame() {
+        // a passed pawn counts for more with fewer pieces on the board
+        let middlegame = eval("r2qk2r/8/8/P7/8/8/8/R2QK2R w - - 0 1")
+            - eval("r2qk2r/8/8/8/8/8/8/R2QK2R w - - 0 1");
+        let endgame =
+            eval("4k3/8/8/P7/8/8/8/4K3 w - - 0 1") - eval("4k3/8/8/8/8/8/8/4K3 w - - 0 1");
+        assert!(endgame > middlegame);
+        assert!(eval("4k3/8/8/8/8/8/8/4K2Q w - - 0 1") > 800);
+    }
+
+    #[test]
+    fn test_incremental_updates() {
+        let mg = MoveGenerator::new();
+        // castling, en passant, promotions with and without capture
+        let mut board = Board::fen(Some(
+            "r3k2r/pPppqpb1/bn2pnp1/3PN3/Pp2P3/2N2Q1p/1PPBBPPP/R3K2R b KQkq a3 0 1".to_string(),
+        ));
+        check_incremental(
+            &mut board,
+            &mg,
+            &mut Evaluator::new(Arc::new(MoveGenerator::new())),
+            2,
+        );
+    }
+
+    fn check_incremental(board: &mut Board, mg: &MoveGenerator, eval: &mut Evaluator, depth: u8) {
+        let mut moves = MoveList::new();
+        board.get_moves(mg, &mut moves);
+        for m in moves.iter() {
+            let before = eval.evaluate(board);
+            if board.make_legal(*m, mg) {
+                assert!(incremental_is_correct(board), "{}", m.to_uci());
+                if depth > 1 {
+                    check_incremental(board, mg, eval, depth - 1);
+                }
+                board.unmake();
+            }
+            assert!(incremental_is_correct(board));
+            assert_eq!(eval.evaluate(board), before);
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use super::kpk;
+use super::psqt::KING_EDGE;
+use super::MATERIAL;
+use crate::bitboard::{distance, front_span, relative_rank, relative_square, FILE_A, FILE_H};
+use crate::board::pieces::Pieces;
+use crate::board::Board;
+use crate::defs::*;
+
+// The evaluation is multiplied by the scale factor of the side that is
+// ahead, divided by `SCALE_NORMAL`.
+pub const SCALE_NORMAL: i32 = 64;
+pub const SCALE_DRAW: i32 = 0;
+// Endgames where material alone says little about the outcome.
+const SCALE_HARD_TO_WIN: i32 = 16;
+const SCALE_OPPOSITE_BISHOPS: i32 = 16;
+const SCALE_OPPOSITE_BISHOPS_PAWN_UP: i32 = 32;
+const SCALE_OPPOSITE_BISHOPS_WITH_PIECES: i32 = 48;
+
+// Added to the score of an endgame known to be won, so the search goes for
+// it when it can trade down into one.
+pub const KNOWN_WIN: i32 = 1000;
+
+// Piece values for deciding who can win what, in pawns.
+const UNITS: [i32; NrOf::PIECE_TYPES] = [1, 3, 3, 5, 9, 0];
+
+// The number of pieces of each type on the board.
+struct Material {
+    count: [[i32; NrOf::PIECE_TYPES]; NrOf::SIDES],
+    // bishops on both colours
+    bishop_pair: [bool; NrOf::SIDES],
+}
+
+impl Material {
+    fn new(board: &Board) -> Self {
+        let mut count = [[0; NrOf::PIECE_TYPES]; NrOf::SIDES];
+        let mut bishop_pair = [false; NrOf::SIDES];
+        for side in [WHITE, BLACK] {
+            for piece in Pieces::iter() {
+                count[side as usize][*piece as usize] =
+                    board.get_pieces(side, *piece).count_ones() as i32;
+            }
+            bishop_pair[side as usize] = board.has_bishop_pair(side);
+        }
+        Self { count, bishop_pair }
+    }
+
+    fn count(&self, side: u8, piece: Pieces) -> i32 {
+        self.count[side as usize][piece as usize]
+    }
+
+    // Material other than pawns, in pawn units.
+    fn pieces(&self, side: u8) -> i32 {
+        (1..Pieces::King as usize)
+            .map(|p| self.count[side as usize][p] * UNITS[p])
+            .sum()
+    }
+
+    fn pawns(&self, side: u8) -> i32 {
+        self.count(side, Pieces::Pawn)
+    }
+
+    fn bare_king(&self, side: u8) -> bool {
+        self.pieces(side) == 0 && self.pawns(side) == 0
+    }
+
+    // Exactly these pieces besides the king and any pawns.
+    fn only(&self, side: u8, pieces: &[(Pieces, i32)]) -> bool {
+        let total: i32 = pieces.iter().map(|(p, n)| n * UNITS[*p as usize]).sum();
+        self.pieces(side) == total && pieces.iter().all(|(p, n)| self.count(side, *p) == *n)
+    }
+
+    // Enough to mate a bare king without the help of pawns.
+    fn can_mate(&self, side: u8) -> bool {
+        self.count(side, Pieces::Queen) > 0
+            || self.count(side, Pieces::Rook) > 0
+            || (self.count(side, Pieces::Bishop) > 0 && self.count(side, Pieces::Knight) > 0)
+            || self.bishop_pair[side as usize]
+    }
+}
+
+// Exact evaluations of endgames the general evaluation doesn't understand,
+// from White's point of view.
+pub fn evaluate(board: &Board) -> Option<i32> {
+    let material = Material::new(board);
+    for strong in [WHITE, BLACK] {
+        let weak = strong ^ 1;
+        let sign = if strong == WHITE { 1 } else { -1 };
+        if material.bare_king(weak) && material.can_mate(strong) {
+            return Some(sign * mate_bare_king(board, &material, strong));
+        }
+        let krkp = material.only(strong, &[(Pieces::Rook, 1)])
+            && material.pawns(strong) == 0
+            && material.pieces(weak) == 0
+            && material.pawns(weak) == 1;
+        if krkp {
+            return Some(sign * rook_against_pawn(board, strong));
+        }
+        let kpk =
+            material.pieces(strong) == 0 && material.pawns(strong) == 1 && material.bare_king(weak);
+        if kpk {
+            return Some(sign * king_and_pawn(board, strong));
+        }
+    }
+    None
+}
+
+// KQK, KRK, KBNK and everything else with enough material to mate: drive
+// the bare king to the edge, with the strong king coming closer. Mate with
+// bishop and knight can only be forced in a corner of the bishop's colour.
+fn mate_bare_king(board: &Board, material: &Material, strong: u8) -> i32 {
+    let weak_king = board.king(strong ^ 1);
+    let strong_king = board.king(strong);
+    let material_value: i32 = Pieces::iter()
+        .iter()
+        .map(|p| material.count(strong, *p) * MATERIAL[*p as usize].eg)
+        .sum();
+    let mut score = KNOWN_WIN + material_value;
+    score -= KING_EDGE[weak_king as usize] as i32;
+    score += 10 * (7 - distance(strong_king, weak_king) as i32);
+
+    let bishop_and_knight = material.only(strong, &[(Pieces::Bishop, 1), (Pieces::Knight, 1)]);
+    if bishop_and_knight {
+        let bishop = board.get_pieces(strong, Pieces::Bishop).trailing_zeros() as Square;
+        let corners = if board.is_dark_square(bishop) {
+            [Squares::A1, Squares::H8]
+        } else {
+            [Squares::H1, Squares::A8]
+        };
+        let corner_distance = corners
+            .iter()
+            .map(|c| distance(weak_king, *c))
+            .min()
+            .unwrap();
+        score += 20 * (7 - corner_distance as i32);
+    }
+    score
+}
+
+// KRKP: won if the strong king is in front of the pawn, or the weak king is
+// too far away to support it. Otherwise it comes down to how close the
+// kings are to the pawn and its promotion square.
+fn rook_against_pawn(board: &Board, strong: u8) -> i32 {
+    let weak = strong ^ 1;
+    let pawn_bb = board.get_pieces(weak, Pieces::Pawn);
+    let pawn = pawn_bb.trailing_zeros() as Square;
+    let strong_king = board.king(strong);
+    let weak_king = board.king(weak);
+    let rook = board.get_pieces(strong, Pieces::Rook).trailing_zeros() as Square;
+    // the promotion square and the square in front of the pawn
+    let queening = relative_square(weak, Squares::A8 + pawn % 8);
+    let stop = if weak == WHITE { pawn + 8 } else { pawn - 8 };
+    let tempo = (board.we() == weak) as u8;
+    let rook_value = MATERIAL[Pieces::Rook as usize].eg;
+
+    if front_span(weak, pawn_bb) & (1u64 << strong_king) != 0 {
+        return rook_value - distance(strong_king, pawn) as i32;
+    }
+    if distance(weak_king, pawn) >= 3 + tempo && distance(weak_king, rook) >= 3 {
+        return rook_value - distance(strong_king, pawn) as i32;
+    }
+    let advanced = relative_rank(weak, weak_king) >= 5 && relative_rank(weak, pawn) >= 4;
+    if advanced && distance(strong_king, pawn) > 2 + (board.we() == strong) as u8 {
+        return 80 - 8 * distance(strong_king, pawn) as i32;
+    }
+    200 - 8
+        * (distance(strong_king, stop) as i32
+            - distance(weak_king, stop) as i32
+            - distance(pawn, queening) as i32)
+}
+
+// KPK: looked up in the bitbase, which has White as the side with the pawn.
+fn king_and_pawn(board: &Board, strong: u8) -> i32 {
+    let pawn = board.get_pieces(strong, Pieces::Pawn).trailing_zeros() as Square;
+    let view = |square: Square| relative_square(strong, square);
+    let stm = if board.we() == strong { WHITE } else { BLACK };
+    let strong_king = board.king(strong);
+    let weak_king = board.king(strong ^ 1);
+    if kpk::probe_kpk(view(strong_king), view(pawn), view(weak_king), stm) {
+        KNOWN_WIN + MATERIAL[Pieces::Pawn as usize].eg + 10 * relative_rank(strong, pawn) as i32
+    } else {
+        0
+    }
+}
+
+// How much of an advantage of the strong side counts, out of
+// `SCALE_NORMAL`. Without pawns a side needs a rook more than the other
+// to win; opposite-coloured bishops and some pawn endings are drawish.
+pub fn scale_factor(board: &Board, strong: u8) -> i32 {
+    let material = Material::new(board);
+    let weak = strong ^ 1;
+
+    if material.pawns(strong) == 0 {
+        let two_knights = material.only(strong, &[(Pieces::Knight, 2)]);
+        // bishops which all run on squares of one colour can't mate
+        let bishops = material.count(strong, Pieces::Bishop);
+        let one_coloured_bishops = material.only(strong, &[(Pieces::Bishop, bishops)])
+            && !material.bishop_pair[strong as usize];
+        if material.pieces(strong) <= UNITS[Pieces::Bishop as usize]
+            || two_knights
+            || one_coloured_bishops
+        {
+            return SCALE_DRAW;
+        }
+        if material.pieces(strong) - material.pieces(weak) <= UNITS[Pieces::Bishop as usize] {
+            return SCALE_HARD_TO_WIN;
+        }
+    }
+
+    if let Some(scale) = opposite_bishops(board, &material) {
+        return scale;
+    }
+
+    let bishop_only = material.only(strong, &[(Pieces::Bishop, 1)]);
+    if bishop_only && material.pieces(weak) == 0 && wrong_rook_pawns(board, strong) {
+        return SCALE_DRAW;
+    }
+
+    SCALE_NORMAL
+}
+
+// Neither side has the material to win, e.g. a minor piece against a bare
+// king or one against each other, so the game can be called a draw.
+pub fn drawn_by_material(board: &Board) -> bool {
+    [WHITE, BLACK]
+        .iter()
+        .all(|&side| scale_factor(board, side) == SCALE_DRAW)
+}
+
+// Bishops on squares of different colours can't fight each other, which
+// makes even a pawn or two more hard to convert.
+fn opposite_bishops(board: &Board, material: &Material) -> Option<i32> {
+    let white = board.get_pieces(WHITE, Pieces::Bishop);
+    let black = board.get_pieces(BLACK, Pieces::Bishop);
+    if white.count_ones() != 1 || black.count_ones() != 1 {
+        return None;
+    }
+    let white_dark = board.is_dark_square(white.trailing_zeros() as Square);
+    let black_dark = board.is_dark_square(black.trailing_zeros() as Square);
+    if white_dark == black_dark {
+        return None;
+    }
+    let bishops_only = material.only(WHITE, &[(Pieces::Bishop, 1)])
+        && material.only(BLACK, &[(Pieces::Bishop, 1)]);
+    if !bishops_only {
+        return Some(SCALE_OPPOSITE_BISHOPS_WITH_PIECES);
+    }
+    if (material.pawns(WHITE) - material.pawns(BLACK)).abs() <= 1 {
+        Some(SCALE_OPPOSITE_BISHOPS)
+    } else {
+        Some(SCALE_OPPOSITE_BISHOPS_PAWN_UP)
+    }
+}
+
+// A bishop and rook pawns on one file can't win if the bishop doesn't cover
+// the promotion square and the defending king gets into that corner.
+fn wrong_rook_pawns(board: &Board, strong: u8) -> bool {
+    let pawns = board.get_pieces(strong, Pieces::Pawn);
+    let file = if pawns & !FILE_A == 0 {
+        0
+    } else if pawns & !FILE_H == 0 {
+        7
+    } else {
+        return false;
+    };
+    let queening = relative_square(strong, Squares::A8 + file);
+    let bishop = board.get_pieces(strong, Pieces::Bishop).trailing_zeros() as Square;
+    board.is_dark_square(bishop) != board.is_dark_square(queening)
+        && distance(board.king(strong ^ 1), queening) <= 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        Board::fen(Some(fen.to_string()))
+    }
+
+    fn exact(fen: &str) -> Option<i32> {
+        evaluate(&board(fen))
+    }
+
+    fn scale(fen: &str, strong: u8) -> i32 {
+        scale_factor(&board(fen), strong)
+    }
+
+    #[test]
+    fn test_insufficient_material() {
+        assert_eq!(scale(FEN_START_POSITION, WHITE), SCALE_NORMAL);
+        assert_eq!(scale("8/8/8/5k2/8/7Q/1K6/8 w - - 0 1", WHITE), SCALE_NORMAL);
+        assert_eq!(scale("8/8/3b4/5k2/8/8/1K6/8 w - - 0 1", BLACK), SCALE_DRAW);
+        assert_eq!(scale("8/8/8/5k2/8/8/1K6/8 w - - 0 1", WHITE), SCALE_DRAW);
+        assert_eq!(scale("8/8/8/5k2/8/8/1K6/6NN w - - 0 1", WHITE), SCALE_DRAW);
+        assert_eq!(
+            scale("8/8/3b4/5k2/8/8/1K6/7R w - - 0 1", WHITE),
+            SCALE_HARD_TO_WIN
+        );
+
+        assert!(drawn_by_material(&board("8/8/4k3/8/8/2KB4/8/8 w - - 0 1")));
+        assert!(drawn_by_material(&board("8/8/4k3/8/8/2K5/8/8 b - - 0 1")));
+        assert!(drawn_by_material(&board("8/8/4kn2/8/8/2KB4/8/8 w - - 0 1")));
+        assert!(!drawn_by_material(&board("8/8/4k3/8/8/2KP4/8/8 w - - 0 1")));
+        assert!(!drawn_by_material(&board(
+            "8/8/4k3/8/8/2KB4/8/6N1 w - - 0 1"
+        )));
+    }
+
+    #[test]
+    fn test_mate_bare_king() {
+        let center = exact("8/8/8/4k3/8/8/1K6/7Q w - - 0 1").unwrap();
+        let edge = exact("4k3/8/8/8/8/8/1K6/7Q w - - 0 1").unwrap();
+        assert!(center > KNOWN_WIN);
+        assert!(edge > center);
+        assert!(exact("8/8/8/4k3/8/8/1K6/7q w - - 0 1").unwrap() < -KNOWN_WIN);
+        // bishop and knight mate in the corner of the bishop's colour
+        let right = exact("7k/8/8/8/8/8/1K6/B5N1 w - - 0 1").unwrap();
+        let wrong = exact("k7/8/8/8/8/8/1K6/B5N1 w - - 0 1").unwrap();
+        assert!(right > wrong);
+        assert_eq!(exact("8/8/8/4k3/8/8/1K6/6NN w - - 0 1"), None);
+        // two bishops mate only on squares of both colours
+        assert!(exact("8/8/8/4k3/8/8/1K6/B6B w - - 0 1").unwrap() > KNOWN_WIN);
+        assert_eq!(exact("8/8/8/4k3/8/8/1K6/B1B5 w - - 0 1"), None);
+    }
+
+    #[test]
+    fn test_rook_against_pawn() {
+        // the rook's king stops the pawn
+        let won = exact("8/8/8/8/8/2k5/3p4/3K3R w - - 0 1").unwrap();
+        // the pawn is about to promote with its king's support
+        let drawish = exact("K7/8/8/8/8/8/2kp4/7R w - - 0 1").unwrap();
+        assert!(won > 400);
+        assert!(drawish < won);
+    }
+
+    #[test]
+    fn test_king_and_pawn() {
+        assert_eq!(exact("8/4k3/8/4K3/4P3/8/8/8 w - - 0 1"), Some(0));
+        assert!(exact("8/4k3/8/4K3/4P3/8/8/8 b - - 0 1").unwrap() > KNOWN_WIN);
+        // the same with the colours reversed
+        assert_eq!(exact("8/8/8/4p3/4k3/8/4K3/8 b - - 0 1"), Some(0));
+        assert!(exact("8/8/8/4p3/4k3/8/4K3/8 w - - 0 1").unwrap() < -KNOWN_WIN);
+    }
+
+    #[test]
+    fn test_drawish_endings() {
+        let opposite = "4k3/8/2b5/4p3/4P3/8/5B2/4K3 w - - 0 1";
+        assert_eq!(scale(opposite, WHITE), SCALE_OPPOSITE_BISHOPS);
+        let with_rooks = "r3k3/8/2b5/4p3/4P3/8/5B2/R3K3 w - - 0 1";
+        assert_eq!(scale(with_rooks, WHITE), SCALE_OPPOSITE_BISHOPS_WITH_PIECES);
+        // a8 is light, the bishop on a dark square
+        let wrong = "k7/8/8/P7/8/8/8/2B1K3 w - - 0 1";
+        assert_eq!(scale(wrong, WHITE), SCALE_DRAW);
+        let right = "k7/8/8/P7/8/8/8/3BK3 w - - 0 1";
+        assert_eq!(scale(right, WHITE), SCALE_NORMAL);
+        // an underpromoted bishop on the colour of the other one
+        let same_colour = "8/8/8/4k3/8/8/1K6/B1B5 w - - 0 1";
+        assert_eq!(scale(same_colour, WHITE), SCALE_DRAW);
+    }
+}
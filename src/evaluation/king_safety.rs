@@ -0,0 +1,156 @@
+use super::score::Score;
+use super::trace::{Term, Trace};
+use super::weights::*;
+use crate::bitboard::{forward, front_span, rank_of, rearmost, relative_rank, Bitboard, FILE_A};
+use crate::board::pieces::Pieces;
+use crate::board::Board;
+use crate::defs::*;
+use crate::move_generator::MoveGenerator;
+
+// Attack units for each square around the king a piece attacks, in the
+// order of `Pieces`.
+const ATTACK_WEIGHT: [i32; NrOf::PIECE_TYPES] = [0, 2, 2, 3, 5, 0];
+// How much of the danger counts, in percent, by the number of attackers.
+// A single piece can't mate, each further one makes it more likely.
+const ATTACKER_SCALE: [i32; 8] = [0, 0, 50, 75, 88, 94, 97, 100];
+const MAX_DANGER: i32 = 600;
+
+// Both kings, from White's point of view.
+pub fn king_safety(board: &Board, mg: &MoveGenerator, trace: &mut impl Trace) -> Score {
+    let white = shelter(board, WHITE, trace) - danger(board, mg, WHITE, trace);
+    let black = shelter(board, BLACK, trace) - danger(board, mg, BLACK, trace);
+    white - black
+}
+
+// The pawns on the three files around the king: our own as a shield, theirs
+// coming closer to open lines, and files without our pawns as a way in for
+// rooks and queens. This is mostly for the middlegame; in the endgame the
+// king has to leave its shelter anyway.
+fn shelter(board: &Board, side: u8, trace: &mut impl Trace) -> Score {
+    let king = board.king(side);
+    let ours = board.get_pieces(side, Pieces::Pawn);
+    let theirs = board.get_pieces(side ^ 1, Pieces::Pawn);
+    let king_rank = relative_rank(side, king);
+    let in_front = front_span(side, rank_of(king));
+
+    let mut score = Score::default();
+    let center = (king % 8).clamp(1, 6);
+    for file in (center - 1..=center + 1).map(|file| FILE_A << file) {
+        let shield = match rearmost(side, ours & in_front & file) {
+            Some(pawn) => (relative_rank(side, pawn) - king_rank).min(3) as usize,
+            None => 0,
+        };
+        score += SHIELD[shield];
+        trace.add(Term::Shield, shield, side, 1);
+        if let Some(pawn) = rearmost(side, theirs & in_front & file) {
+            let storm = (relative_rank(side, pawn) - king_rank).min(4) as usize;
+            if forward(side ^ 1, 1u64 << pawn) & ours != 0 {
+                score += BLOCKED_STORM[storm];
+                trace.add(Term::BlockedStorm, storm, side, 1);
+            } else {
+                score += STORM[storm];
+                trace.add(Term::Storm, storm, side, 1);
+            }
+        }
+        if (ours | theirs) & file == 0 {
+            score += KING_OPEN_FILE;
+            trace.add(Term::KingOpenFile, 0, side, 1);
+        } else if ours & file == 0 {
+            score += KING_SEMI_OPEN_FILE;
+            trace.add(Term::KingSemiOpenFile, 0, side, 1);
+        }
+    }
+    score
+}
+
+// Enemy pieces attacking the squares around the king. Each attacked square
+// adds units by the type of the attacker, and the danger grows with the
+// square of the units: a lone attacker is harmless, a few together can
+// break through. That part isn't linear, so the tuner leaves it alone; it
+// only tunes `KING_ATTACKER`, which counts each attacking piece once.
+fn danger(board: &Board, mg: &MoveGenerator, side: u8, trace: &mut impl Trace) -> Score {
+    let king = board.king(side);
+    let around = mg.king_attacks(king);
+    let zone = around | forward(side, around);
+    let occupancy = board.occupancy();
+
+    let mut attackers = 0;
+    let mut units = 0;
+    let mut linear = Score::default();
+    for piece in [Pieces::Knight, Pieces::Bishop, Pieces::Rook, Pieces::Queen] {
+        for square in Bitboard::new(board.get_pieces(side ^ 1, piece)) {
+            let attacks = mg.attacks(piece, square, occupancy) & zone;
+            if attacks != 0 {
+                attackers += 1;
+                units += ATTACK_WEIGHT[piece as usize] * attacks.count_ones() as i32;
+                linear += KING_ATTACKER[piece as usize];
+                // the danger is subtracted: it counts for the attacker
+                trace.add(Term::KingAttacker, piece as usize, side ^ 1, 1);
+            }
+        }
+    }
+    let scale = ATTACKER_SCALE[attackers.min(ATTACKER_SCALE.len() - 1)];
+    let danger = (units * units / 4).min(MAX_DANGER) * scale / 100;
+    Score::new(danger, danger / 4) + linear
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluation::trace::NoTrace;
+
+    fn board(fen: &str) -> Board {
+        Board::fen(Some(fen.to_string()))
+    }
+
+    fn shelter_mg(board: &Board, side: u8) -> i32 {
+        shelter(board, side, &mut NoTrace).mg
+    }
+
+    #[test]
+    fn test_shelter() {
+        let intact = shelter_mg(&board("6k1/8/8/8/8/8/5PPP/6K1 w - - 0 1"), WHITE);
+        let advanced = shelter_mg(&board("6k1/8/8/8/8/6P1/5P1P/6K1 w - - 0 1"), WHITE);
+        let missing = shelter_mg(&board("6k1/8/8/8/8/8/5P1P/6K1 w - - 0 1"), WHITE);
+        let stormed = shelter_mg(&board("6k1/8/8/8/8/7p/5PP1/6K1 w - - 0 1"), WHITE);
+        assert!(intact > advanced);
+        assert!(advanced > missing);
+        assert!(intact > stormed);
+        // the black king is on the same squares, mirrored
+        let black = shelter_mg(&board("6k1/5ppp/8/8/8/8/8/6K1 w - - 0 1"), BLACK);
+        assert_eq!(black, intact);
+    }
+
+    #[test]
+    fn test_danger_grows_with_attackers() {
+        let mg = MoveGenerator::new();
+        let one = danger(
+            &board("6k1/8/8/8/8/5q2/5PPP/6K1 w - - 0 1"),
+            &mg,
+            WHITE,
+            &mut NoTrace,
+        );
+        let two = danger(
+            &board("6k1/8/8/8/8/5q1n/5PPP/6K1 w - - 0 1"),
+            &mg,
+            WHITE,
+            &mut NoTrace,
+        );
+        let three = danger(
+            &board("6k1/8/8/8/3b4/5q1n/5PPP/6K1 w - - 0 1"),
+            &mg,
+            WHITE,
+            &mut NoTrace,
+        );
+        assert_eq!(one, Score::default());
+        assert!(two.mg > 0);
+        assert!(three.mg > two.mg);
+        let away = danger(
+            &board("6k1/8/n7/8/8/8/q4PPP/6K1 w - - 0 1"),
+            &mg,
+            WHITE,
+            &mut NoTrace,
+        );
+        assert!(away.mg < two.mg);
+    }
+}
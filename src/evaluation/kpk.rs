@@ -0,0 +1,119 @@
+use std::sync::OnceLock;
+
+use crate::board::pieces::Pieces;
+use crate::defs::*;
+use crate::dtm::{Dtm, DtmTables};
+use crate::move_generator::MoveGenerator;
+
+// A bitbase of all king and pawn against king positions: one bit per
+// position, set if the side with the pawn wins. It is generated when first
+// needed, which takes a moment, so `init` should be called at startup.
+//
+// White has the pawn. Positions with the pawn on the e- to h-files are the
+// mirror images of those on the a- to d-files, so only those are stored.
+const PAWN_SQUARES: usize = 24; // files a-d, ranks 2-7
+const POSITIONS: usize = NrOf::SIDES * NrOf::SQUARES * NrOf::SQUARES * PAWN_SQUARES;
+
+static KPK: OnceLock<Vec<u64>> = OnceLock::new();
+
+pub fn init() {
+    KPK.get_or_init(generate);
+}
+
+// Does White, with the king on `wk` and the pawn on `wp`, win against the
+// black king on `bk` with `stm` to move? The position has to be legal.
+pub fn probe_kpk(wk: Square, wp: Square, bk: Square, stm: u8) -> bool {
+    let bits = KPK.get_or_init(generate);
+    // mirror to the a- to d-files
+    let (wk, wp, bk) = if wp % 8 > 3 {
+        (wk ^ 7, wp ^ 7, bk ^ 7)
+    } else {
+        (wk, wp, bk)
+    };
+    let i = index(stm, wk, wp, bk);
+    bits[i / 64] & (1 << (i % 64)) != 0
+}
+
+fn index(stm: u8, wk: Square, wp: Square, bk: Square) -> usize {
+    let pawn = (wp / 8 - 1) as usize * 4 + (wp % 8) as usize;
+    ((stm as usize * PAWN_SQUARES + pawn) * NrOf::SQUARES + wk as usize) * NrOf::SQUARES
+        + bk as usize
+}
+
+// The bitbase is read off the distance to mate table of KPvK, which is
+// made with the tables it leads to after a promotion or the capture of
+// the pawn. The fifty move rule is ignored, as it is there.
+fn generate() -> Vec<u64> {
+    let mg = MoveGenerator::new();
+    let mut tables = DtmTables::new();
+    let table = tables.generate(&mg, "KPvK").unwrap();
+    let mut bits = vec![0u64; POSITIONS.div_ceil(64)];
+    for i in 0..POSITIONS {
+        let bk = (i % NrOf::SQUARES) as Square;
+        let wk = (i / NrOf::SQUARES % NrOf::SQUARES) as Square;
+        let pawn = i / (NrOf::SQUARES * NrOf::SQUARES) % PAWN_SQUARES;
+        let stm = (i / (NrOf::SQUARES * NrOf::SQUARES * PAWN_SQUARES)) as u8;
+        let wp = ((pawn / 4 + 1) * 8 + pawn % 4) as Square;
+        let pieces = [
+            (WHITE, Pieces::King, wk),
+            (WHITE, Pieces::Pawn, wp),
+            (BLACK, Pieces::King, bk),
+        ];
+        let white_wins = match table.dtm(&pieces, stm) {
+            Some(Dtm::Win(_)) => stm == WHITE,
+            Some(Dtm::Loss(_)) => stm == BLACK,
+            _ => false,
+        };
+        if white_wins {
+            bits[i / 64] |= 1 << (i % 64);
+        }
+    }
+    bits
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::Board;
+
+    fn probe(fen: &str) -> bool {
+        let board = Board::fen(Some(fen.to_string()));
+        let wp = board.get_pieces(WHITE, Pieces::Pawn).trailing_zeros() as Square;
+        probe_kpk(board.king(WHITE), wp, board.king(BLACK), board.we())
+    }
+
+    #[test]
+    fn test_known_positions() {
+        // king in front of the pawn on the 6th rank
+        assert!(probe("4k3/8/4K3/4P3/8/8/8/8 w - - 0 1"));
+        assert!(probe("4k3/8/4K3/4P3/8/8/8/8 b - - 0 1"));
+        // the opposition decides
+        assert!(!probe("8/4k3/8/4K3/4P3/8/8/8 w - - 0 1"));
+        assert!(probe("8/4k3/8/4K3/4P3/8/8/8 b - - 0 1"));
+        // the same on the other wing
+        assert!(!probe("8/2k5/8/2K5/2P5/8/8/8 w - - 0 1"));
+        assert!(probe("8/2k5/8/2K5/2P5/8/8/8 b - - 0 1"));
+        // the rook pawn and the king in the corner
+        assert!(!probe("k7/8/K7/P7/8/8/8/8 w - - 0 1"));
+        assert!(!probe("7k/8/7K/7P/8/8/8/8 b - - 0 1"));
+        // stalemate
+        assert!(!probe("k7/P7/1K6/8/8/8/8/8 b - - 0 1"));
+        // the king is outside the square of the pawn
+        assert!(probe("8/8/8/8/k7/8/7P/7K w - - 0 1"));
+        assert!(!probe("8/8/8/8/8/3k4/7P/K7 b - - 0 1"));
+        // the king on a key square wins, whatever the other king does
+        assert!(probe("k7/8/3K4/8/4P3/8/8/8 b - - 0 1"));
+    }
+
+    #[test]
+    fn test_mirrored_files() {
+        for (wk, wp, bk) in [(20, 27, 44), (3, 9, 40), (33, 50, 36)] {
+            for stm in [WHITE, BLACK] {
+                assert_eq!(
+                    probe_kpk(wk, wp, bk, stm),
+                    probe_kpk(wk ^ 7, wp ^ 7, bk ^ 7, stm)
+                );
+            }
+        }
+    }
+}
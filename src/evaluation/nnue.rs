@@ -0,0 +1,304 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::bitboard::Bitboard;
+use crate::board::pieces::Pieces;
+use crate::board::Board;
+use crate::defs::*;
+
+// An efficiently updatable neural network: 768 inputs, one for each piece
+// of either colour on each square, a hidden layer of any size and one
+// output. The hidden layer is computed twice, once from each side's point
+// of view, and the side to move's half comes first at the output.
+//
+// The first layer is by far the most expensive, but only a few inputs
+// change with a move. So the board keeps its sums (the accumulators) up
+// to date as pieces come and go and only the output layer is computed
+// when evaluating.
+//
+// The weights are quantized as networks from the usual trainers are:
+// the first layer scaled by QA, the output layer by QB, with the squared
+// clipped ReLU as activation.
+const INPUTS: usize = 768;
+const QA: i32 = 255;
+const QB: i32 = 64;
+// from the network's win probability scale to centipawns
+const SCALE: i32 = 400;
+
+pub struct Network {
+    hidden: usize,
+    // `hidden` weights for each input, one input after the other
+    feature_weights: Vec<i16>,
+    feature_bias: Vec<i16>,
+    // for the side to move's half, then the other side's
+    output_weights: Vec<i16>,
+    output_bias: i16,
+}
+
+impl Network {
+    // The file holds little endian 16 bit numbers: the input weights, the
+    // hidden biases, the output weights and the output bias, padded with
+    // zeros to a multiple of 64 bytes. The size of the hidden layer
+    // follows from the length.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        let numbers: Vec<i16> = bytes
+            .chunks_exact(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        let hidden = numbers.len().saturating_sub(1) / (INPUTS + 3);
+        let used = hidden * (INPUTS + 3) + 1;
+        if hidden == 0 || bytes.len() % 2 != 0 || numbers.len() - used >= 32 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a 768xNx1 network",
+            ));
+        }
+        let (feature_weights, rest) = numbers.split_at(INPUTS * hidden);
+        let (feature_bias, rest) = rest.split_at(hidden);
+        let (output_weights, rest) = rest.split_at(2 * hidden);
+        Ok(Self {
+            hidden,
+            feature_weights: feature_weights.to_vec(),
+            feature_bias: feature_bias.to_vec(),
+            output_weights: output_weights.to_vec(),
+            output_bias: rest[0],
+        })
+    }
+
+    fn weights(&self, feature: usize) -> &[i16] {
+        &self.feature_weights[feature * self.hidden..(feature + 1) * self.hidden]
+    }
+}
+
+// The input of a piece as seen by `perspective`: own pieces first, and
+// for Black the board turned around.
+fn feature(perspective: u8, side: u8, piece: Pieces, square: Square) -> usize {
+    let kind = match piece {
+        Pieces::Pawn => 0,
+        Pieces::Knight => 1,
+        Pieces::Bishop => 2,
+        Pieces::Rook => 3,
+        Pieces::Queen => 4,
+        _ => 5,
+    };
+    let square = if perspective == WHITE {
+        square
+    } else {
+        square ^ 56
+    };
+    let colour = if side == perspective { 0 } else { 384 };
+    colour + 64 * kind + square as usize
+}
+
+// The accumulators of the board and of every position before it in the
+// history, White's half first. A move pushes a copy which is then
+// updated; taking it back just pops it.
+#[derive(Clone)]
+pub struct Accumulators {
+    network: Arc<Network>,
+    stack: Vec<i16>,
+    top: usize,
+}
+
+impl Accumulators {
+    pub fn new(network: Arc<Network>, board: &Board) -> Self {
+        let mut accumulators = Self {
+            stack: vec![0; 2 * network.hidden],
+            network,
+            top: 0,
+        };
+        accumulators.refresh(board);
+        accumulators
+    }
+
+    // Sums the inputs of all pieces from scratch, for the current
+    // position.
+    pub fn refresh(&mut self, board: &Board) {
+        let hidden = self.network.hidden;
+        let network = Arc::clone(&self.network);
+        let current = self.current_mut();
+        current[..hidden].copy_from_slice(&network.feature_bias);
+        current[hidden..].copy_from_slice(&network.feature_bias);
+        for side in [WHITE, BLACK] {
+            for square in Bitboard::new(board.get_side(side)) {
+                self.add(side, board.piece_list[square as usize], square);
+            }
+        }
+    }
+
+    fn current(&self) -> &[i16] {
+        let size = 2 * self.network.hidden;
+        &self.stack[self.top * size..(self.top + 1) * size]
+    }
+
+    fn current_mut(&mut self) -> &mut [i16] {
+        let size = 2 * self.network.hidden;
+        &mut self.stack[self.top * size..(self.top + 1) * size]
+    }
+
+    // Forgets the positions before, as when the history is cleared.
+    pub fn clear(&mut self) {
+        self.top = 0;
+    }
+
+    pub fn push(&mut self) {
+        let size = 2 * self.network.hidden;
+        let start = self.top * size;
+        if self.stack.len() < start + 2 * size {
+            self.stack.resize(start + 2 * size, 0);
+        }
+        self.stack.copy_within(start..start + size, start + size);
+        self.top += 1;
+    }
+
+    // False if the position before was never computed, as when the
+    // network was set in the middle of a game.
+    pub fn pop(&mut self) -> bool {
+        if self.top == 0 {
+            return false;
+        }
+        self.top -= 1;
+        true
+    }
+
+    pub fn add(&mut self, side: u8, piece: Pieces, square: Square) {
+        self.update(side, piece, square, |sum, weight| sum + weight);
+    }
+
+    pub fn remove(&mut self, side: u8, piece: Pieces, square: Square) {
+        self.update(side, piece, square, |sum, weight| sum - weight);
+    }
+
+    fn update(&mut self, side: u8, piece: Pieces, square: Square, f: impl Fn(i16, i16) -> i16) {
+        let hidden = self.network.hidden;
+        let network = Arc::clone(&self.network);
+        let current = self.current_mut();
+        for (perspective, half) in [WHITE, BLACK]
+            .into_iter()
+            .zip(current.chunks_exact_mut(hidden))
+        {
+            let weights = network.weights(feature(perspective, side, piece, square));
+            for (sum, &weight) in half.iter_mut().zip(weights) {
+                *sum = f(*sum, weight);
+            }
+        }
+    }
+
+    // The evaluation in centipawns for the side to move.
+    pub fn evaluate(&self, stm: u8) -> i32 {
+        let hidden = self.network.hidden;
+        let (white, black) = self.current().split_at(hidden);
+        let (us, them) = if stm == WHITE {
+            (white, black)
+        } else {
+            (black, white)
+        };
+        let (our_weights, their_weights) = self.network.output_weights.split_at(hidden);
+        let sum = screlu_dot(us, our_weights) + screlu_dot(them, their_weights);
+        let output = sum / QA as i64 + self.network.output_bias as i64;
+        (output * SCALE as i64 / (QA * QB) as i64) as i32
+    }
+
+    // Does the incremental update agree with a count from scratch?
+    pub fn is_correct(&self, board: &Board) -> bool {
+        let fresh = Self::new(Arc::clone(&self.network), board);
+        fresh.current() == self.current()
+    }
+}
+
+// Each term fits into an i32, the sum of many may not.
+fn screlu_dot(sums: &[i16], weights: &[i16]) -> i64 {
+    sums.iter()
+        .zip(weights)
+        .map(|(&sum, &weight)| {
+            let x = (sum as i32).clamp(0, QA);
+            (x * weight as i32 * x) as i64
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::movelist::MoveList;
+    use crate::move_generator::MoveGenerator;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaChaRng;
+
+    const HIDDEN: usize = 16;
+
+    // Random weights in the file format, as a trainer would write them.
+    fn network() -> Arc<Network> {
+        let mut rng = ChaChaRng::seed_from_u64(7);
+        let mut numbers: Vec<i16> = (0..INPUTS * HIDDEN)
+            .map(|_| rng.random_range(-60..=60))
+            .collect();
+        numbers.extend((0..HIDDEN).map(|_| rng.random_range(0..=80)));
+        numbers.extend((0..2 * HIDDEN).map(|_| rng.random_range(-100..=100)));
+        numbers.push(25);
+        numbers.resize(numbers.len().div_ceil(32) * 32, 0);
+        let bytes: Vec<u8> = numbers.iter().flat_map(|n| n.to_le_bytes()).collect();
+        let path = std::env::temp_dir().join(format!("rust_chess_{}.nnue", std::process::id()));
+        fs::write(&path, bytes).unwrap();
+        let network = Network::load(&path);
+        fs::remove_file(&path).unwrap();
+        Arc::new(network.unwrap())
+    }
+
+    #[test]
+    fn test_load() {
+        let network = network();
+        assert_eq!(network.hidden, HIDDEN);
+        assert_eq!(network.output_bias, 25);
+        let path = std::env::temp_dir().join(format!("rust_chess_{}.bad", std::process::id()));
+        fs::write(&path, [0u8; 100]).unwrap();
+        assert!(Network::load(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_incremental_updates() {
+        let mg = MoveGenerator::new();
+        let mut board = Board::fen(Some(
+            "r3k2r/pPppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1".to_string(),
+        ));
+        board.set_network(Some(network()));
+        let before = board.clone();
+        let mut moves = MoveList::new();
+        board.get_legal_moves(&mg, &mut moves);
+        for &m in moves.iter() {
+            board.make(m);
+            assert!(board.nnue.as_ref().unwrap().is_correct(&board), "{:?}", m);
+            let mut replies = MoveList::new();
+            board.get_legal_moves(&mg, &mut replies);
+            for &reply in replies.iter() {
+                board.make(reply);
+                assert!(board.nnue.as_ref().unwrap().is_correct(&board));
+                board.unmake();
+            }
+            board.unmake();
+        }
+        let nnue = board.nnue.as_ref().unwrap();
+        assert!(nnue.current() == before.nnue.as_ref().unwrap().current());
+        assert_eq!(nnue.top, 0);
+    }
+
+    #[test]
+    fn test_symmetry() {
+        let network = network();
+        let mut board = Board::fen(Some(
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4".to_string(),
+        ));
+        let mut mirrored = Board::fen(Some(
+            "rnbqk2r/pppp1ppp/5n2/2b1p3/4P3/2N2N2/PPPP1PPP/R1BQKB1R b KQkq - 4 4".to_string(),
+        ));
+        board.set_network(Some(Arc::clone(&network)));
+        mirrored.set_network(Some(network));
+        let eval = |board: &Board| board.nnue.as_ref().unwrap().evaluate(board.we());
+        assert_eq!(eval(&board), eval(&mirrored));
+        assert_ne!(eval(&board), 0);
+    }
+}
@@ -0,0 +1,253 @@
+use super::score::Score;
+use super::trace::{NoTrace, Term, Trace};
+use super::weights::*;
+use crate::bitboard::{
+    adjacent_files, backward, distance, file_fill, forward, forward_fill, front_span, pawn_attacks,
+    relative_rank, Bitboard,
+};
+use crate::board::pieces::Pieces;
+use crate::board::Board;
+use crate::defs::*;
+
+// Number of entries, a power of two.
+const PAWN_TABLE_SIZE: usize = 1 << 14;
+
+// The pawn structure of one side, each kind of pawn as a bitboard.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct PawnSets {
+    passed: u64,
+    candidates: u64,
+    supported: u64,
+    phalanx: u64,
+    doubled: u64,
+    isolated: u64,
+    backward: u64,
+}
+
+impl PawnSets {
+    fn new(side: u8, us: u64, them: u64) -> Self {
+        let our_attacks = pawn_attacks(side, us);
+        let their_attacks = pawn_attacks(side ^ 1, them);
+        // the squares their pawns are still going to pass
+        let their_spans = front_span(side ^ 1, them);
+
+        // the rear pawn of a doubled pawn is the one which counts as doubled
+        let doubled = us & front_span(side ^ 1, us);
+        let isolated = us & !adjacent_files(file_fill(us));
+        let passed = us & !(their_spans | adjacent_files(their_spans)) & !doubled;
+        // can't advance without being taken by a pawn, and no other pawn
+        // can come to help
+        let unsafe_stops = forward(side, us) & their_attacks;
+        let backward = backward(side, unsafe_stops & !forward_fill(side, our_attacks)) & !isolated;
+
+        // A pawn on a half-open file which has at least as many pawns next
+        // to or behind it as there are opposing pawns on its way, may well
+        // become a passed pawn by trading them off.
+        let mut candidates = 0;
+        for square in Bitboard::new(us & !their_spans & !passed & !doubled) {
+            let pawn = 1u64 << square;
+            let sentries = them & adjacent_files(front_span(side, pawn));
+            let helpers = us & adjacent_files(pawn | front_span(side ^ 1, pawn));
+            if helpers.count_ones() >= sentries.count_ones() {
+                candidates |= pawn;
+            }
+        }
+
+        Self {
+            passed,
+            candidates,
+            supported: us & our_attacks,
+            phalanx: us & adjacent_files(us),
+            doubled,
+            isolated,
+            backward,
+        }
+    }
+
+    fn score(&self, side: u8, trace: &mut impl Trace) -> Score {
+        let mut score = Score::default();
+        let by_rank = [
+            (self.passed, &PASSED, Term::Passed),
+            (self.candidates, &CANDIDATE, Term::Candidate),
+            (self.supported, &SUPPORTED, Term::Supported),
+            (self.phalanx, &PHALANX, Term::Phalanx),
+        ];
+        for (pawns, bonus, term) in by_rank {
+            for square in Bitboard::new(pawns) {
+                let rank = relative_rank(side, square) as usize;
+                score += bonus[rank];
+                trace.add(term, rank, side, 1);
+            }
+        }
+        let weaknesses = [
+            (self.doubled, DOUBLED, Term::Doubled),
+            (self.isolated, ISOLATED, Term::Isolated),
+            (self.backward, BACKWARD, Term::Backward),
+        ];
+        for (pawns, penalty, term) in weaknesses {
+            let count = pawns.count_ones() as i32;
+            score += penalty * count;
+            trace.add(term, 0, side, count);
+        }
+        score
+    }
+}
+
+// What is known about a pawn structure: its score from White's point of
+// view and the passed pawns of both sides.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PawnEntry {
+    key: u64,
+    pub score: Score,
+    pub passed: [u64; NrOf::SIDES],
+}
+
+impl PawnEntry {
+    pub fn new(board: &Board, trace: &mut impl Trace) -> Self {
+        let white = board.get_pieces(WHITE, Pieces::Pawn);
+        let black = board.get_pieces(BLACK, Pieces::Pawn);
+        let white_sets = PawnSets::new(WHITE, white, black);
+        let black_sets = PawnSets::new(BLACK, black, white);
+        Self {
+            key: board.game_state.pawn_key,
+            score: white_sets.score(WHITE, trace) - black_sets.score(BLACK, trace),
+            passed: [white_sets.passed, black_sets.passed],
+        }
+    }
+}
+
+// Pawns move rarely, so the same pawn structure comes up again and again
+// during a search. It is evaluated once and looked up by the pawn key after
+// that. An empty entry has key 0, the key of a board without pawns, which
+// is also what it correctly describes.
+pub struct PawnTable {
+    entries: Vec<PawnEntry>,
+}
+
+impl Default for PawnTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PawnTable {
+    pub fn new() -> Self {
+        Self {
+            entries: vec![PawnEntry::default(); PAWN_TABLE_SIZE],
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.fill(PawnEntry::default());
+    }
+
+    pub fn probe(&mut self, board: &Board) -> PawnEntry {
+        let key = board.game_state.pawn_key;
+        let entry = &mut self.entries[key as usize & (PAWN_TABLE_SIZE - 1)];
+        if entry.key != key {
+            *entry = PawnEntry::new(board, &mut NoTrace);
+        }
+        *entry
+    }
+}
+
+// The passed pawn terms that depend on the rest of the board, from White's
+// point of view, and so can't be cached: nothing in the way to the
+// promotion square, and the distance of both kings to the square in front
+// of the pawn. The king distances only count from the 4th rank on, and
+// more the further the pawn is.
+pub fn passed_pawns(board: &Board, entry: &PawnEntry, trace: &mut impl Trace) -> Score {
+    let occupancy = board.occupancy();
+    let mut score = [Score::default(); NrOf::SIDES];
+    for side in [WHITE, BLACK] {
+        let own_king = board.king(side);
+        let their_king = board.king(side ^ 1);
+        for square in Bitboard::new(entry.passed[side as usize]) {
+            let pawn = 1u64 << square;
+            let rank = relative_rank(side, square) as usize;
+            if front_span(side, pawn) & occupancy == 0 {
+                score[side as usize] += PASSED_FREE_PATH[rank];
+                trace.add(Term::PassedFreePath, rank, side, 1);
+            }
+            let stop = forward(side, pawn).trailing_zeros() as u8;
+            let weight = rank.saturating_sub(2) as i32;
+            let theirs = distance(their_king, stop) as i32 * weight;
+            let ours = -(distance(own_king, stop) as i32) * weight;
+            score[side as usize] += PASSED_THEIR_KING_DISTANCE * theirs;
+            score[side as usize] += PASSED_OWN_KING_DISTANCE * ours;
+            trace.add(Term::PassedTheirKingDistance, 0, side, theirs);
+            trace.add(Term::PassedOwnKingDistance, 0, side, ours);
+        }
+    }
+    score[WHITE as usize] - score[BLACK as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sets(fen: &str, side: u8) -> PawnSets {
+        let board = Board::fen(Some(fen.to_string()));
+        let us = board.get_pieces(side, Pieces::Pawn);
+        let them = board.get_pieces(side ^ 1, Pieces::Pawn);
+        PawnSets::new(side, us, them)
+    }
+
+    fn squares(names: &[&str]) -> u64 {
+        names.iter().fold(0, |bb, name| {
+            bb | 1u64 << crate::helper::algebraic_to_square(name).unwrap()
+        })
+    }
+
+    #[test]
+    fn test_doubled_and_isolated() {
+        let white = sets("4k3/8/8/8/8/2P5/2P5/4K3 w - - 0 1", WHITE);
+        assert_eq!(white.doubled, squares(&["c2"]));
+        assert_eq!(white.isolated, squares(&["c2", "c3"]));
+        assert_eq!(white.passed, squares(&["c3"]));
+    }
+
+    #[test]
+    fn test_backward() {
+        let fen = "4k3/8/8/4p3/2P5/3P4/8/4K3 w - - 0 1";
+        let white = sets(fen, WHITE);
+        assert_eq!(white.backward, squares(&["d3"]));
+        assert_eq!(white.isolated, 0);
+        assert_eq!(white.supported, squares(&["c4"]));
+        let black = sets(fen, BLACK);
+        assert_eq!(black.isolated, squares(&["e5"]));
+        assert_eq!(black.backward, 0);
+    }
+
+    #[test]
+    fn test_passed_and_candidates() {
+        let white = sets("4k3/8/8/p7/1PP5/8/8/4K3 w - - 0 1", WHITE);
+        assert_eq!(white.passed, squares(&["c4"]));
+        assert_eq!(white.candidates, squares(&["b4"]));
+        assert_eq!(white.phalanx, squares(&["b4", "c4"]));
+        let black = sets("4k3/8/8/p7/1PP5/8/8/4K3 w - - 0 1", BLACK);
+        assert_eq!(black.passed, 0);
+        assert_eq!(black.candidates, 0);
+    }
+
+    #[test]
+    fn test_pawn_table() {
+        let board = Board::fen(Some(
+            "4k3/pp3ppp/8/3P4/8/8/PP3PPP/4K3 w - - 0 1".to_string(),
+        ));
+        let mut table = PawnTable::new();
+        let entry = table.probe(&board);
+        assert_eq!(entry, PawnEntry::new(&board, &mut NoTrace));
+        assert_eq!(entry.passed, [squares(&["d5"]), 0]);
+        assert!(entry.score.eg > 0);
+        assert_eq!(table.probe(&board), entry);
+        // the king trying to catch the passer
+        let near = Board::fen(Some(
+            "8/pp3ppp/3k4/3P4/8/8/PP3PPP/4K3 w - - 0 1".to_string(),
+        ));
+        assert!(
+            passed_pawns(&board, &entry, &mut NoTrace).eg
+                > passed_pawns(&near, &entry, &mut NoTrace).eg
+        );
+    }
+}
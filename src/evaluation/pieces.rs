@@ -0,0 +1,187 @@
+use super::score::Score;
+use super::trace::{Term, Trace};
+use super::weights::*;
+use crate::bitboard::{
+    file_fill, forward_fill, pawn_attacks, relative_rank, relative_square, Bitboard,
+};
+use crate::board::pieces::Pieces;
+use crate::board::Board;
+use crate::defs::*;
+use crate::move_generator::MoveGenerator;
+
+// How many safe squares each piece has on average, in the order of
+// `Pieces`. The weights of mobility count from there.
+const MOBILITY_AVERAGE: [i32; NrOf::PIECE_TYPES] = [0, 6, 4, 7, 13, 0];
+// rooks with fewer moves may be trapped
+const TRAPPED_ROOK_MOBILITY: u32 = 3;
+
+// Where the pieces stand and what they attack, for both sides from White's
+// point of view.
+pub fn pieces(board: &Board, mg: &MoveGenerator, trace: &mut impl Trace) -> Score {
+    side_pieces(board, mg, WHITE, trace) - side_pieces(board, mg, BLACK, trace)
+}
+
+fn side_pieces(board: &Board, mg: &MoveGenerator, side: u8, trace: &mut impl Trace) -> Score {
+    let them = side ^ 1;
+    let occupancy = board.occupancy();
+    let our_pawns = board.get_pieces(side, Pieces::Pawn);
+    let their_pawns = board.get_pieces(them, Pieces::Pawn);
+    let our_pawn_attacks = pawn_attacks(side, our_pawns);
+    let their_pawn_attacks = pawn_attacks(them, their_pawns);
+    // squares the enemy pawns attack now, or may attack once they advance
+    let their_pawn_span = forward_fill(them, their_pawn_attacks);
+    let safe = !board.get_side(side) & !their_pawn_attacks;
+    let their_majors = board.get_pieces(them, Pieces::Rook) | board.get_pieces(them, Pieces::Queen);
+    let their_king = board.king(them);
+
+    let mut score = Score::default();
+    for piece in [Pieces::Knight, Pieces::Bishop, Pieces::Rook, Pieces::Queen] {
+        let p = piece as usize;
+        for square in Bitboard::new(board.get_pieces(side, piece)) {
+            let attacks = mg.attacks(piece, square, occupancy);
+            let mobility = (attacks & safe).count_ones();
+            let mobility_count = mobility as i32 - MOBILITY_AVERAGE[p];
+            score += MOBILITY[p] * mobility_count;
+            trace.add(Term::Mobility, p, side, mobility_count);
+
+            let bit = 1u64 << square;
+            let rank = relative_rank(side, square);
+            match piece {
+                Pieces::Knight | Pieces::Bishop => {
+                    let outpost = (3..=5).contains(&rank)
+                        && bit & our_pawn_attacks != 0
+                        && bit & their_pawn_span == 0;
+                    if outpost && piece == Pieces::Knight {
+                        score += KNIGHT_OUTPOST;
+                        trace.add(Term::KnightOutpost, 0, side, 1);
+                    } else if outpost {
+                        score += BISHOP_OUTPOST;
+                        trace.add(Term::BishopOutpost, 0, side, 1);
+                    }
+                    let threats = (attacks & their_majors).count_ones() as i32;
+                    score += MINOR_THREAT * threats;
+                    trace.add(Term::MinorThreat, 0, side, threats);
+                    if piece == Pieces::Bishop && bishop_trapped(side, square, their_pawns) {
+                        score += TRAPPED_BISHOP;
+                        trace.add(Term::TrappedBishop, 0, side, 1);
+                    }
+                }
+                Pieces::Rook => {
+                    let file = file_fill(bit);
+                    if file & (our_pawns | their_pawns) == 0 {
+                        score += ROOK_OPEN_FILE;
+                        trace.add(Term::RookOpenFile, 0, side, 1);
+                    } else if file & our_pawns == 0 {
+                        score += ROOK_SEMI_OPEN_FILE;
+                        trace.add(Term::RookSemiOpenFile, 0, side, 1);
+                    }
+                    let seventh = rank == 6
+                        && (relative_rank(side, their_king) == 7
+                            || their_pawns & seventh_rank(side) != 0);
+                    if seventh {
+                        score += ROOK_ON_SEVENTH;
+                        trace.add(Term::RookOnSeventh, 0, side, 1);
+                    }
+                    if mobility <= TRAPPED_ROOK_MOBILITY && rook_trapped(board, side, square) {
+                        score += TRAPPED_ROOK;
+                        trace.add(Term::TrappedRook, 0, side, 1);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    // pawns attacking pieces
+    let their_pieces = board.get_side(them) & !their_pawns;
+    let threats = (our_pawn_attacks & their_pieces).count_ones() as i32;
+    score += PAWN_THREAT * threats;
+    trace.add(Term::PawnThreat, 0, side, threats);
+
+    if board.has_bishop_pair(side) {
+        score += BISHOP_PAIR;
+        trace.add(Term::BishopPair, 0, side, 1);
+    }
+    score
+}
+
+fn seventh_rank(side: u8) -> u64 {
+    if side == WHITE {
+        0xff << 48
+    } else {
+        0xff << 8
+    }
+}
+
+// A rook on the back rank, between its king and the corner, with a king
+// that has lost the right to castle to that side.
+fn rook_trapped(board: &Board, side: u8, square: Square) -> bool {
+    let king = relative_square(side, board.king(side));
+    let rook = relative_square(side, square);
+    if king >= 8 || rook >= 8 {
+        return false;
+    }
+    let (kingside, queenside) = if side == WHITE {
+        (Castling::WK, Castling::WQ)
+    } else {
+        (Castling::BK, Castling::BQ)
+    };
+    let castling = board.game_state.castling;
+    (king > 4 && rook > king && castling & kingside == 0)
+        || (king < 4 && rook < king && castling & queenside == 0)
+}
+
+fn bishop_trapped(side: u8, square: Square, their_pawns: u64) -> bool {
+    let blocker = match relative_square(side, square) {
+        48 => 41, // a7, b6
+        55 => 46, // h7, g6
+        _ => return false,
+    };
+    their_pawns & (1u64 << relative_square(side, blocker)) != 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluation::trace::NoTrace;
+
+    fn eval(fen: &str, side: u8) -> Score {
+        let mg = MoveGenerator::new();
+        side_pieces(&Board::fen(Some(fen.to_string())), &mg, side, &mut NoTrace)
+    }
+
+    #[test]
+    fn test_bishop_pair_and_outpost() {
+        let pair = eval("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1", WHITE);
+        let same = eval("4k3/8/8/8/8/8/8/4KB1B w - - 0 1", WHITE);
+        assert!(pair.mg > same.mg + BISHOP_PAIR.mg / 2);
+
+        let outpost = eval("4k3/pp4pp/8/3N4/4P3/8/8/4K3 w - - 0 1", WHITE);
+        let chased = eval("4k3/pp2p1pp/8/3N4/4P3/8/8/4K3 w - - 0 1", WHITE);
+        assert!(outpost.mg >= chased.mg + KNIGHT_OUTPOST.mg);
+    }
+
+    #[test]
+    fn test_rooks() {
+        let open = eval("4k3/p5p1/8/8/8/8/P5P1/3RK3 w - - 0 1", WHITE);
+        let closed = eval("4k3/p2p2p1/8/8/8/8/P2P2P1/3RK3 w - - 0 1", WHITE);
+        assert!(open.mg > closed.mg);
+        let seventh = eval("4k3/p2R2p1/8/8/8/8/P5P1/4K3 w - - 0 1", WHITE);
+        let sixth = eval("4k3/p5p1/3R4/8/8/8/P5P1/4K3 w - - 0 1", WHITE);
+        assert!(seventh.eg > sixth.eg);
+
+        let trapped = eval("4k3/8/8/8/8/8/5PPP/5K1R w - - 0 1", WHITE);
+        assert!(trapped.mg <= TRAPPED_ROOK.mg);
+        let castling = eval("4k3/8/8/8/8/8/5PPP/4K2R w K - 0 1", WHITE);
+        assert!(castling.mg > trapped.mg);
+    }
+
+    #[test]
+    fn test_threats_and_trapped_bishop() {
+        let threat = eval("4k3/8/8/2n5/1P6/8/8/4K3 w - - 0 1", WHITE);
+        let none = eval("4k3/8/8/2n5/8/1P6/8/4K3 w - - 0 1", WHITE);
+        assert_eq!(threat - none, PAWN_THREAT);
+        let trapped = eval("4k3/B7/1p6/8/8/8/8/4K3 w - - 0 1", WHITE);
+        assert!(trapped.mg < TRAPPED_BISHOP.mg / 2);
+    }
+}
@@ -21,92 +21,35 @@ You should have received a copy of the GNU General Public License along
 with this program.  If not, see <http://www.gnu.org/licenses/>.
 ======================================================================= */
 
-// This file implements Piece Square Tables (PSQT) for each piece type. The
-// PSQT's are written from White's point of view, as if looking at a chess
-// diagram, with A1 on the lower left corner.
-
-use crate::{
-    board::Board,
-    defs::{NrOf, Sides},
-    misc::bits,
-};
+// This file implements Piece Square Tables (PSQT) for each piece type, one
+// for the middlegame and one for the endgame. The PSQT's are written from
+// White's point of view, as if looking at a chess diagram, with A1 on the
+// lower left corner. They only hold the positional part of a piece's
+// value: the material itself is in `MATERIAL`. Both are tuned, so they
+// live with the other weights in `weights.rs`.
+
+use super::score::Score;
+use super::weights::{PSQT_EG, PSQT_MG};
+use crate::board::pieces::Pieces;
+use crate::defs::*;
 
 type Psqt = [i16; NrOf::SQUARES];
 
-#[rustfmt::skip]
-const KING_MG: Psqt = [
-    0, 0,  0,   0,   0, 0,  0, 0,
-    0, 0,  0,   0,   0, 0,  0, 0,
-    0, 0,  0,   0,   0, 0,  0, 0,
-    0, 0,  0,  20,  20, 0,  0, 0,
-    0, 0,  0,  20,  20, 0,  0, 0,
-    0, 0,  0,   0,   0, 0,  0, 0,
-    0, 0,  0, -10, -10, 0,  0, 0,
-    0, 0, 20, -10, -10, 0, 20, 0,
-];
-
-#[rustfmt::skip]
-const QUEEN_MG: Psqt = [
-    870, 880, 890, 890, 890, 890, 880, 870,
-    880, 890, 895, 895, 895, 895, 890, 880,
-    890, 895, 910, 910, 910, 910, 895, 890,
-    890, 895, 910, 920, 920, 910, 895, 890,
-    890, 895, 910, 920, 920, 910, 895, 890,
-    890, 895, 895, 895, 895, 895, 895, 890,
-    880, 890, 895, 895, 895, 895, 890, 880,
-    870, 880, 890, 890, 890, 890, 880, 870 
-];
-
-#[rustfmt::skip]
-const ROOK_MG: Psqt = [
-   500, 500, 500, 500, 500, 500, 500, 500,
-   515, 515, 515, 520, 520, 515, 515, 515,
-   500, 500, 500, 500, 500, 500, 500, 500,
-   500, 500, 500, 500, 500, 500, 500, 500,
-   500, 500, 500, 500, 500, 500, 500, 500,
-   500, 500, 500, 500, 500, 500, 500, 500,
-   500, 500, 500, 500, 500, 500, 500, 500,
-   500, 500, 500, 510, 510, 510, 500, 500
-];
-
-#[rustfmt::skip]
-const BISHOP_MG: Psqt = [
-    300, 320, 320, 320, 320, 320, 320, 300,
-    305, 320, 320, 320, 320, 320, 320, 305,
-    310, 320, 320, 325, 325, 320, 320, 310,
-    310, 330, 330, 350, 350, 330, 330, 310,
-    325, 325, 330, 345, 345, 330, 325, 325,
-    325, 325, 325, 330, 330, 325, 325, 325,
-    310, 325, 325, 330, 330, 325, 325, 310,
-    300, 310, 310, 310, 310, 310, 310, 300
-];
-
-#[rustfmt::skip]
-const KNIGHT_MG: Psqt = [
-    290, 300, 300, 300, 300, 300, 300, 290,
-    300, 305, 305, 305, 305, 305, 305, 300,
-    300, 305, 325, 325, 325, 325, 305, 300,
-    300, 305, 325, 325, 325, 325, 305, 300,
-    300, 305, 325, 325, 325, 325, 305, 300,
-    300, 305, 325, 325, 325, 325, 305, 300,
-    300, 305, 305, 305, 305, 305, 305, 300,
-    290, 310, 300, 300, 300, 300, 310, 290
-];
-
-#[rustfmt::skip]
-const PAWN_MG: Psqt = [
-    100, 100, 100, 100, 100, 100, 100, 100,
-    160, 160, 160, 160, 170, 160, 160, 160,
-    140, 140, 140, 150, 160, 140, 140, 140,
-    120, 120, 120, 140, 150, 120, 120, 120,
-    105, 105, 115, 130, 140, 110, 105, 105,
-    105, 105, 110, 120, 130, 105, 105, 105,
-    105, 105, 105,  70,  70, 105, 105, 105,
-    100, 100, 100, 100, 100, 100, 100, 100
-];
-
-pub const PSQT_MG: [Psqt; NrOf::PIECE_TYPES] =
-    [KING_MG, QUEEN_MG, ROOK_MG, BISHOP_MG, KNIGHT_MG, PAWN_MG];
+// The positional value of a piece of the given side on a square.
+pub fn psqt(side: u8, piece: Pieces, square: u8) -> Score {
+    let index = index(side, square);
+    let piece = piece as usize;
+    Score::new(PSQT_MG[piece][index] as i32, PSQT_EG[piece][index] as i32)
+}
+
+// Where the square is in the tables for the given side.
+pub fn index(side: u8, square: u8) -> usize {
+    if side == WHITE {
+        FLIP[square as usize]
+    } else {
+        square as usize
+    }
+}
 
 // When one side has a bare king, this PSQT is used to drive that king to
 // the edge of the board and mate it there.
@@ -173,9 +116,8 @@ pub const KING_EDGE: Psqt = [
 // This results in the following converstion table, from aquare number
 // to PSQT element, needed for WHITE only::
 
-#[allow(dead_code)]
 #[rustfmt::skip]
-pub const FLIP: [usize; 64] = [
+const FLIP: [usize; 64] = [
     56, 57, 58, 59, 60, 61, 62, 63,
     48, 49, 50, 51, 52, 53, 54, 55,
     40, 41, 42, 43, 44, 45, 46, 47,
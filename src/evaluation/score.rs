@@ -0,0 +1,81 @@
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+// The game phase of a position with all pieces on the board. It drops
+// towards 0 as pieces other than pawns are traded off.
+pub const MAX_PHASE: i32 = 24;
+
+// An evaluation term as a pair of a middlegame and an endgame value, which
+// are blended by the game phase once all terms are summed up.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Score {
+    pub mg: i32,
+    pub eg: i32,
+}
+
+impl Score {
+    pub const fn new(mg: i32, eg: i32) -> Self {
+        Self { mg, eg }
+    }
+
+    // Blend the two values: the middlegame value at `MAX_PHASE`, the
+    // endgame value at 0.
+    pub fn taper(self, phase: i32) -> i32 {
+        let phase = phase.clamp(0, MAX_PHASE);
+        (self.mg * phase + self.eg * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+}
+
+impl Add for Score {
+    type Output = Score;
+    fn add(self, other: Score) -> Score {
+        Score::new(self.mg + other.mg, self.eg + other.eg)
+    }
+}
+
+impl AddAssign for Score {
+    fn add_assign(&mut self, other: Score) {
+        *self = *self + other;
+    }
+}
+
+impl Sub for Score {
+    type Output = Score;
+    fn sub(self, other: Score) -> Score {
+        Score::new(self.mg - other.mg, self.eg - other.eg)
+    }
+}
+
+impl SubAssign for Score {
+    fn sub_assign(&mut self, other: Score) {
+        *self = *self - other;
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+    fn neg(self) -> Score {
+        Score::new(-self.mg, -self.eg)
+    }
+}
+
+impl Mul<i32> for Score {
+    type Output = Score;
+    fn mul(self, n: i32) -> Score {
+        Score::new(self.mg * n, self.eg * n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_taper() {
+        let score = Score::new(100, -50);
+        assert_eq!(score.taper(MAX_PHASE), 100);
+        assert_eq!(score.taper(0), -50);
+        assert_eq!(score.taper(MAX_PHASE / 2), 25);
+        assert_eq!(score.taper(MAX_PHASE + 10), 100);
+        assert_eq!(score * 2 - score, score);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::defs::*;
+
+// The tunable terms of the evaluation, in the order of `weights.rs`. Each
+// has a number of weights, like one for each rank or each piece.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Term {
+    Material,
+    Psqt,
+    Mobility,
+    KnightOutpost,
+    BishopOutpost,
+    BishopPair,
+    RookOpenFile,
+    RookSemiOpenFile,
+    RookOnSeventh,
+    TrappedRook,
+    TrappedBishop,
+    PawnThreat,
+    MinorThreat,
+    Passed,
+    Candidate,
+    Supported,
+    Phalanx,
+    Doubled,
+    Isolated,
+    Backward,
+    PassedFreePath,
+    PassedOwnKingDistance,
+    PassedTheirKingDistance,
+    Shield,
+    Storm,
+    BlockedStorm,
+    KingSemiOpenFile,
+    KingOpenFile,
+    KingAttacker,
+}
+
+impl Term {
+    pub const ALL: [Term; 29] = [
+        Term::Material,
+        Term::Psqt,
+        Term::Mobility,
+        Term::KnightOutpost,
+        Term::BishopOutpost,
+        Term::BishopPair,
+        Term::RookOpenFile,
+        Term::RookSemiOpenFile,
+        Term::RookOnSeventh,
+        Term::TrappedRook,
+        Term::TrappedBishop,
+        Term::PawnThreat,
+        Term::MinorThreat,
+        Term::Passed,
+        Term::Candidate,
+        Term::Supported,
+        Term::Phalanx,
+        Term::Doubled,
+        Term::Isolated,
+        Term::Backward,
+        Term::PassedFreePath,
+        Term::PassedOwnKingDistance,
+        Term::PassedTheirKingDistance,
+        Term::Shield,
+        Term::Storm,
+        Term::BlockedStorm,
+        Term::KingSemiOpenFile,
+        Term::KingOpenFile,
+        Term::KingAttacker,
+    ];
+
+    // The number of weights of the term.
+    pub fn size(self) -> usize {
+        match self {
+            Term::Material | Term::Mobility | Term::KingAttacker => NrOf::PIECE_TYPES,
+            Term::Psqt => NrOf::PIECE_TYPES * NrOf::SQUARES,
+            Term::Passed
+            | Term::Candidate
+            | Term::Supported
+            | Term::Phalanx
+            | Term::PassedFreePath => 8,
+            Term::Shield => 4,
+            Term::Storm | Term::BlockedStorm => 5,
+            _ => 1,
+        }
+    }
+
+    // Where the weights of the term start in the parameter vector.
+    pub fn offset(self) -> usize {
+        Term::ALL
+            .iter()
+            .take_while(|&&term| term != self)
+            .map(|term| term.size())
+            .sum()
+    }
+}
+
+// The number of weights of all terms together.
+pub fn parameter_count() -> usize {
+    Term::ALL.iter().map(|term| term.size()).sum()
+}
+
+// Records how often each weight counts in an evaluation. The normal
+// evaluation passes `NoTrace`, which compiles to nothing.
+pub trait Trace {
+    fn add(&mut self, term: Term, index: usize, side: u8, count: i32);
+}
+
+pub struct NoTrace;
+
+impl Trace for NoTrace {
+    #[inline(always)]
+    fn add(&mut self, _: Term, _: usize, _: u8, _: i32) {}
+}
+
+// How often each weight counts for White, less how often for Black. Most
+// terms are linear, so the evaluation is these times the weights.
+pub struct Coefficients(pub Vec<i32>);
+
+impl Default for Coefficients {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Coefficients {
+    pub fn new() -> Self {
+        Self(vec![0; parameter_count()])
+    }
+}
+
+impl Trace for Coefficients {
+    fn add(&mut self, term: Term, index: usize, side: u8, count: i32) {
+        debug_assert!(index < term.size());
+        let sign = if side == WHITE { 1 } else { -1 };
+        self.0[term.offset() + index] += sign * count;
+    }
+}
@@ -0,0 +1,311 @@
+// The weights of the hand-crafted evaluation, for the middlegame and the
+// endgame, in centipawns. This file is written by the Texel tuner
+// (`rust_chess texel`), which is why they are all in one place. Only the
+// king danger, which isn't linear, has its own constants.
+
+use super::score::Score;
+
+// Piece values, in the order of `Pieces`.
+pub const MATERIAL: [Score; 6] = [
+    Score::new(100, 120),
+    Score::new(320, 330),
+    Score::new(300, 290),
+    Score::new(500, 530),
+    Score::new(900, 940),
+    Score::new(0, 0),
+];
+
+// The positional part of the piece values, in the order of `Pieces`, laid
+// out as a diagram with White at the bottom.
+#[rustfmt::skip]
+pub const PSQT_MG: [[i16; 64]; 6] = [
+    // Pawn
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+         60,  60,  60,  60,  70,  60,  60,  60,
+         40,  40,  40,  50,  60,  40,  40,  40,
+         20,  20,  20,  40,  50,  20,  20,  20,
+          5,   5,  15,  30,  40,  10,   5,   5,
+          5,   5,  10,  20,  30,   5,   5,   5,
+          5,   5,   5, -30, -30,   5,   5,   5,
+          0,   0,   0,   0,   0,   0,   0,   0,
+    ],
+    // Bishop
+    [
+        -20,   0,   0,   0,   0,   0,   0, -20,
+        -15,   0,   0,   0,   0,   0,   0, -15,
+        -10,   0,   0,   5,   5,   0,   0, -10,
+        -10,  10,  10,  30,  30,  10,  10, -10,
+          5,   5,  10,  25,  25,  10,   5,   5,
+          5,   5,   5,  10,  10,   5,   5,   5,
+        -10,   5,   5,  10,  10,   5,   5, -10,
+        -20, -10, -10, -10, -10, -10, -10, -20,
+    ],
+    // Knight
+    [
+        -10,   0,   0,   0,   0,   0,   0, -10,
+          0,   5,   5,   5,   5,   5,   5,   0,
+          0,   5,  25,  25,  25,  25,   5,   0,
+          0,   5,  25,  25,  25,  25,   5,   0,
+          0,   5,  25,  25,  25,  25,   5,   0,
+          0,   5,  25,  25,  25,  25,   5,   0,
+          0,   5,   5,   5,   5,   5,   5,   0,
+        -10,  10,   0,   0,   0,   0,  10, -10,
+    ],
+    // Rook
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+         15,  15,  15,  20,  20,  15,  15,  15,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,  10,  10,  10,   0,   0,
+    ],
+    // Queen
+    [
+        -30, -20, -10, -10, -10, -10, -20, -30,
+        -20, -10,  -5,  -5,  -5,  -5, -10, -20,
+        -10,  -5,  10,  10,  10,  10,  -5, -10,
+        -10,  -5,  10,  20,  20,  10,  -5, -10,
+        -10,  -5,  10,  20,  20,  10,  -5, -10,
+        -10,  -5,  -5,  -5,  -5,  -5,  -5, -10,
+        -20, -10,  -5,  -5,  -5,  -5, -10, -20,
+        -30, -20, -10, -10, -10, -10, -20, -30,
+    ],
+    // King
+    [
+        -60, -60, -60, -60, -60, -60, -60, -60,
+        -50, -50, -50, -50, -50, -50, -50, -50,
+        -40, -40, -40, -40, -40, -40, -40, -40,
+        -30, -30, -30, -40, -40, -30, -30, -30,
+        -20, -20, -20, -30, -30, -20, -20, -20,
+        -10, -10, -15, -20, -20, -15, -10, -10,
+          5,   5,   0, -10, -10,   0,   5,   5,
+         10,  20,  20, -10,   0, -10,  30,  15,
+    ],
+];
+
+#[rustfmt::skip]
+pub const PSQT_EG: [[i16; 64]; 6] = [
+    // Pawn
+    [
+          0,   0,   0,   0,   0,   0,   0,   0,
+         90,  90,  90,  90,  90,  90,  90,  90,
+         55,  55,  55,  55,  55,  55,  55,  55,
+         30,  30,  30,  30,  30,  30,  30,  30,
+         15,  15,  15,  15,  15,  15,  15,  15,
+          5,   5,   5,   5,   5,   5,   5,   5,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+    ],
+    // Bishop
+    [
+        -15, -10, -10, -10, -10, -10, -10, -15,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -10,   0,   5,   5,   5,   5,   0, -10,
+        -10,   0,   5,  10,  10,   5,   0, -10,
+        -10,   0,   5,  10,  10,   5,   0, -10,
+        -10,   0,   5,   5,   5,   5,   0, -10,
+        -10,   0,   0,   0,   0,   0,   0, -10,
+        -15, -10, -10, -10, -10, -10, -10, -15,
+    ],
+    // Knight
+    [
+        -40, -30, -20, -20, -20, -20, -30, -40,
+        -30, -15,   0,   0,   0,   0, -15, -30,
+        -20,   0,  10,  15,  15,  10,   0, -20,
+        -20,   5,  15,  20,  20,  15,   5, -20,
+        -20,   5,  15,  20,  20,  15,   5, -20,
+        -20,   0,  10,  15,  15,  10,   0, -20,
+        -30, -15,   0,   0,   0,   0, -15, -30,
+        -40, -30, -20, -20, -20, -20, -30, -40,
+    ],
+    // Rook
+    [
+          5,   5,   5,   5,   5,   5,   5,   5,
+         15,  15,  15,  15,  15,  15,  15,  15,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+          0,   0,   0,   0,   0,   0,   0,   0,
+    ],
+    // Queen
+    [
+        -20, -10, -10,  -5,  -5, -10, -10, -20,
+        -10,   0,   5,   5,   5,   5,   0, -10,
+        -10,   5,  10,  10,  10,  10,   5, -10,
+         -5,   5,  10,  15,  15,  10,   5,  -5,
+         -5,   5,  10,  15,  15,  10,   5,  -5,
+        -10,   5,  10,  10,  10,  10,   5, -10,
+        -10,   0,   5,   5,   5,   5,   0, -10,
+        -20, -10, -10,  -5,  -5, -10, -10, -20,
+    ],
+    // King
+    [
+        -50, -30, -30, -30, -30, -30, -30, -50,
+        -30, -10,   0,   0,   0,   0, -10, -30,
+        -30,   0,  20,  25,  25,  20,   0, -30,
+        -30,   0,  25,  35,  35,  25,   0, -30,
+        -30,   0,  25,  35,  35,  25,   0, -30,
+        -30,   0,  20,  25,  25,  20,   0, -30,
+        -30, -20,   0,   0,   0,   0, -20, -30,
+        -50, -40, -30, -30, -30, -30, -40, -50,
+    ],
+];
+
+// For every safe square a piece can go to, compared to how many it has on
+// average, in the order of `Pieces`.
+pub const MOBILITY: [Score; 6] = [
+    Score::new(0, 0),
+    Score::new(5, 5),
+    Score::new(4, 4),
+    Score::new(2, 4),
+    Score::new(1, 2),
+    Score::new(0, 0),
+];
+
+// A minor piece in the enemy half, defended by a pawn, where no enemy pawn
+// can ever chase it away.
+pub const KNIGHT_OUTPOST: Score = Score::new(30, 20);
+
+pub const BISHOP_OUTPOST: Score = Score::new(20, 10);
+
+pub const BISHOP_PAIR: Score = Score::new(30, 50);
+
+pub const ROOK_OPEN_FILE: Score = Score::new(25, 10);
+
+pub const ROOK_SEMI_OPEN_FILE: Score = Score::new(12, 8);
+
+// only if there are pawns to eat there, or the king is cut off on the 8th
+pub const ROOK_ON_SEVENTH: Score = Score::new(10, 25);
+
+// a rook stuck in the corner by its own king, which can't castle any more
+pub const TRAPPED_ROOK: Score = Score::new(-40, -10);
+
+// a bishop which took the pawn on a7 or h7 and is cut off by b6 or g6
+pub const TRAPPED_BISHOP: Score = Score::new(-80, -80);
+
+// for each piece attacked by a pawn, and each rook or queen attacked by a
+// minor piece
+pub const PAWN_THREAT: Score = Score::new(40, 30);
+
+pub const MINOR_THREAT: Score = Score::new(20, 15);
+
+// Pawns by their rank, as seen from their own side.
+pub const PASSED: [Score; 8] = [
+    Score::new(0, 0),
+    Score::new(0, 5),
+    Score::new(5, 10),
+    Score::new(10, 20),
+    Score::new(20, 40),
+    Score::new(40, 70),
+    Score::new(60, 110),
+    Score::new(0, 0),
+];
+
+pub const CANDIDATE: [Score; 8] = [
+    Score::new(0, 0),
+    Score::new(0, 5),
+    Score::new(5, 5),
+    Score::new(5, 10),
+    Score::new(10, 20),
+    Score::new(20, 35),
+    Score::new(0, 0),
+    Score::new(0, 0),
+];
+
+// defended by another pawn
+pub const SUPPORTED: [Score; 8] = [
+    Score::new(0, 0),
+    Score::new(0, 0),
+    Score::new(5, 5),
+    Score::new(8, 6),
+    Score::new(12, 10),
+    Score::new(20, 25),
+    Score::new(35, 45),
+    Score::new(0, 0),
+];
+
+// side by side with another pawn
+pub const PHALANX: [Score; 8] = [
+    Score::new(0, 0),
+    Score::new(2, 0),
+    Score::new(4, 2),
+    Score::new(6, 4),
+    Score::new(12, 10),
+    Score::new(25, 30),
+    Score::new(40, 55),
+    Score::new(0, 0),
+];
+
+pub const DOUBLED: Score = Score::new(-10, -25);
+
+pub const ISOLATED: Score = Score::new(-10, -15);
+
+pub const BACKWARD: Score = Score::new(-8, -10);
+
+// A passed pawn with nothing in the way to the promotion square, by rank.
+pub const PASSED_FREE_PATH: [Score; 8] = [
+    Score::new(0, 0),
+    Score::new(0, 0),
+    Score::new(0, 0),
+    Score::new(0, 5),
+    Score::new(0, 10),
+    Score::new(0, 20),
+    Score::new(0, 35),
+    Score::new(0, 0),
+];
+
+// For each square of distance of the kings to the square in front of a
+// passed pawn, times its rank less 2: ours is subtracted, theirs added.
+pub const PASSED_OWN_KING_DISTANCE: Score = Score::new(0, 2);
+
+pub const PASSED_THEIR_KING_DISTANCE: Score = Score::new(0, 5);
+
+// Own pawn on a file around the king, by how many ranks it is in front of
+// it. 0 means there is none.
+pub const SHIELD: [Score; 4] = [
+    Score::new(-25, 0),
+    Score::new(12, 0),
+    Score::new(6, 0),
+    Score::new(-5, 0),
+];
+
+// Enemy pawn coming up a file around the king, by how many ranks it is in
+// front of it, and the same if one of our pawns blocks it.
+pub const STORM: [Score; 5] = [
+    Score::new(0, 0),
+    Score::new(-5, 0),
+    Score::new(-30, 0),
+    Score::new(-15, 0),
+    Score::new(-5, 0),
+];
+
+pub const BLOCKED_STORM: [Score; 5] = [
+    Score::new(0, 0),
+    Score::new(-2, 0),
+    Score::new(-15, 0),
+    Score::new(-7, 0),
+    Score::new(-2, 0),
+];
+
+// a file around the king without our pawns, or without any
+pub const KING_SEMI_OPEN_FILE: Score = Score::new(-15, 0);
+
+pub const KING_OPEN_FILE: Score = Score::new(-25, 0);
+
+// For each enemy piece attacking the squares around the king, in the order
+// of `Pieces`, on top of the king danger in `king_safety.rs`. That grows
+// with the square of the attacks, so it isn't tuned.
+pub const KING_ATTACKER: [Score; 6] = [
+    Score::new(0, 0),
+    Score::new(0, 0),
+    Score::new(0, 0),
+    Score::new(0, 0),
+    Score::new(0, 0),
+    Score::new(0, 0),
+];
@@ -0,0 +1,23 @@
+pub mod bitboard;
+pub mod board;
+pub mod book;
+pub mod datagen;
+pub mod defs;
+pub mod dtm;
+pub mod engine;
+pub mod evaluation;
+pub mod helper;
+pub mod move_generator;
+pub mod search;
+pub mod syzygy;
+pub mod tuner;
+pub mod uci;
+
+// What it takes to embed the search: set up a `Board`, give a `Search` the
+// `SearchLimits` and read the best move from its result.
+pub use board::Board;
+pub use search::limits::SearchLimits;
+pub use search::Search;
+
+use board::*;
+use defs::*;
@@ -1,39 +1,41 @@
-mod bitboard;
-mod board;
 mod cli;
-mod defs;
-mod helper;
-mod move_generator;
 
-use board::*;
-use defs::*;
-use move_generator::MoveGenerator;
-use moves::{
-    Castle::{Kingside, Queenside},
-    Move, MoveType,
-};
+use movelist::MoveList;
+use moves::{Castle::Queenside, Move, MoveType};
 use pieces::Pieces;
+use rust_chess::board::*;
+use rust_chess::move_generator::MoveGenerator;
+use rust_chess::{book, datagen, dtm, tuner, uci};
 
 fn main() {
-    move_gen();
-    // user();
-    // testing();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("dtm") => dtm::run(&args[1..]),
+        Some("book") => book::pgn::run(&args[1..]),
+        Some("datagen") => datagen::run(&args[1..]),
+        Some("texel") => tuner::run(&args[1..]),
+        Some("movegen") => move_gen(),
+        Some("user") => user(),
+        Some("testing") => testing(),
+        _ => uci::Uci::new().run(),
+    }
 }
 
 fn move_gen() {
-    let mut mg = MoveGenerator::new();
-    let mut board = Board::fen(Some(String::from(
+    let mg = MoveGenerator::new();
+    let mut moves = MoveList::new();
+    let board = Board::fen(Some(String::from(
         "1k6/8/8/8/4p3/1p1P4/1P4P1/3K4 w - - 0 1",
     )));
     board.display();
-    let moves = board.get_moves(&mg);
-    for m in moves {
+    board.get_moves(&mg, &mut moves);
+    for m in &moves {
         println!("{:?}", m);
     }
     let mut board = Board::fen(Some(String::from("1k6/8/8/8/8/8/8/R2K3R w - - 0 1")));
     board.display();
-    let moves = board.get_moves(&mg);
-    for m in moves {
+    board.get_legal_moves(&mg, &mut moves);
+    for m in &moves {
         println!("{:?}", m);
     }
 }
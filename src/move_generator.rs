@@ -1,10 +1,10 @@
-use crate::board::moves::Move;
 use crate::board::pieces::Pieces;
 use crate::defs::*;
 use crate::{helper::get_bitmask, NrOf};
 
 pub struct MoveGenerator {
     pawn: [[u64; NrOf::SQUARES]; NrOf::SIDES],
+    pawn_attacks: [[u64; NrOf::SQUARES]; NrOf::SIDES],
     knight: [u64; NrOf::SQUARES],
     bishop: [u64; NrOf::SQUARES],
     rook: [u64; NrOf::SQUARES],
@@ -12,6 +12,12 @@ pub struct MoveGenerator {
     // queen is rook || bishop
 }
 
+impl Default for MoveGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MoveGenerator {
     pub const KNIGHT_DIRS: [(i8, i8); 8] = [
         (-1, 2),
@@ -39,6 +45,7 @@ impl MoveGenerator {
     pub fn new() -> Self {
         let mut mg = MoveGenerator {
             pawn: [[0; NrOf::SQUARES]; NrOf::SIDES],
+            pawn_attacks: [[0; NrOf::SQUARES]; NrOf::SIDES],
             knight: [0; NrOf::SQUARES],
             bishop: [0; NrOf::SQUARES],
             rook: [0; NrOf::SQUARES],
@@ -65,7 +72,48 @@ impl MoveGenerator {
         }
     }
 
+    // Attack lookups used by the board for move generation and check
+    // detection. Sliders are computed on the fly from the occupancy.
+    pub fn pawn_attacks(&self, side: u8, square: Square) -> u64 {
+        self.pawn_attacks[side as usize][square as usize]
+    }
+
+    pub fn knight_attacks(&self, square: Square) -> u64 {
+        self.knight[square as usize]
+    }
+
+    pub fn king_attacks(&self, square: Square) -> u64 {
+        self.king[square as usize]
+    }
+
+    pub fn bishop_attacks(square: Square, occupancy: u64) -> u64 {
+        MoveGenerator::sliding_attacks(square, &MoveGenerator::BISHOP_DIRS, occupancy, 0)
+    }
+
+    pub fn rook_attacks(square: Square, occupancy: u64) -> u64 {
+        MoveGenerator::sliding_attacks(square, &MoveGenerator::ROOK_DIRS, occupancy, 0)
+    }
+
+    pub fn queen_attacks(square: Square, occupancy: u64) -> u64 {
+        MoveGenerator::bishop_attacks(square, occupancy)
+            | MoveGenerator::rook_attacks(square, occupancy)
+    }
+
+    // attacks of any piece type except pawns
+    pub fn attacks(&self, piece: Pieces, square: Square, occupancy: u64) -> u64 {
+        match piece {
+            Pieces::Knight => self.knight_attacks(square),
+            Pieces::Bishop => MoveGenerator::bishop_attacks(square, occupancy),
+            Pieces::Rook => MoveGenerator::rook_attacks(square, occupancy),
+            Pieces::Queen => MoveGenerator::queen_attacks(square, occupancy),
+            Pieces::King => self.king_attacks(square),
+            Pieces::Pawn | Pieces::Empty => panic!("No side-independent attacks for {:?}.", piece),
+        }
+    }
+
     fn init_pawn(&mut self) {
+        self.pawn_attacks[WHITE as usize] = get_attacks(vec![(1, -1), (1, 1)]);
+        self.pawn_attacks[BLACK as usize] = get_attacks(vec![(-1, -1), (-1, 1)]);
         self.pawn[WHITE as usize] = get_attacks(vec![(1, 0), (1, -1), (1, 1)]);
         self.pawn[BLACK as usize] = get_attacks(vec![(-1, 0), (-1, -1), (-1, 1)]);
         for (square, bb) in self.pawn[WHITE as usize].iter_mut().enumerate() {
@@ -119,9 +167,8 @@ impl MoveGenerator {
     }
 
     fn init_king(&mut self) {
+        // castling is generated by the board, as it depends on the position
         self.king = get_attacks(Vec::from(MoveGenerator::KING_DIRS));
-        self.king[Squares::E1 as usize] |= get_bitmask(Squares::C1) | get_bitmask(Squares::G1);
-        self.king[Squares::E8 as usize] |= get_bitmask(Squares::C8) | get_bitmask(Squares::G8);
     }
 
     fn init_knight(&mut self) {
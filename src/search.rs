@@ -0,0 +1,607 @@
+mod alpha_beta;
+pub mod limits;
+pub mod movepicker;
+pub mod ordering;
+pub mod params;
+mod pv;
+mod quiescence;
+pub mod time;
+pub mod transposition;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use self::limits::SearchLimits;
+use self::ordering::MoveOrdering;
+use self::params::{LmrTable, SearchParams};
+use self::pv::PvTable;
+use self::time::TimeManager;
+use self::transposition::{Bound, TT};
+use crate::board::movelist::MoveList;
+use crate::board::moves::Move;
+use crate::board::Board;
+use crate::evaluation::Evaluator;
+use crate::move_generator::MoveGenerator;
+use crate::syzygy::Tablebases;
+
+// Deepest ply the search can reach, including extensions and quiescence.
+pub const MAX_PLY: usize = 128;
+
+pub const INF: i32 = 32_000;
+pub const CHECKMATE: i32 = 31_000;
+// scores beyond this are mate scores
+pub const MATE_BOUND: i32 = CHECKMATE - MAX_PLY as i32;
+pub const DRAW: i32 = 0;
+// a win known from the tablebases, less than any mate
+pub const TB_WIN: i32 = MATE_BOUND - 1;
+
+pub const DEFAULT_HASH_MB: usize = 16;
+
+// how often the clock and the stop flag are looked at, node limits are
+// checked every node
+const CHECK_INTERVAL: u64 = 1024;
+
+// Each ply keeps a few move lists on the stack, more than the default
+// stack of a spawned thread can hold at full depth.
+pub const THREAD_STACK_SIZE: usize = 32 * 1024 * 1024;
+
+#[derive(Clone, Copy, Debug)]
+pub struct SearchResult {
+    pub best_move: Move,
+    // the expected reply, to ponder on; null if the PV is too short
+    pub ponder_move: Move,
+    pub score: i32,
+    pub depth: i8,
+    pub nodes: u64,
+}
+
+// Progress report of the search, sent after every iteration and whenever
+// an aspiration window fails. `bound` tells whether the score is exact or
+// only a lower or upper bound. With MultiPV, each line is reported on its
+// own, numbered from 1 for the best one.
+#[derive(Clone, Debug)]
+pub struct SearchInfo {
+    pub multipv: usize,
+    pub depth: i8,
+    pub seldepth: usize,
+    pub score: i32,
+    pub bound: Bound,
+    pub nodes: u64,
+    pub tbhits: u64,
+    pub hashfull: usize,
+    pub time: Duration,
+    pub pv: Vec<Move>,
+}
+
+// The search keeps its transposition table and move ordering tables
+// between calls, so it should live as long as the game.
+pub struct Search {
+    board: Board,
+    mg: Arc<MoveGenerator>,
+    tt: Arc<TT>,
+    // 0 for the main thread, helper threads of a parallel search count up
+    id: usize,
+    ordering: MoveOrdering,
+    eval: Evaluator,
+    tb: Option<Arc<Tablebases>>,
+    // probe the WDL tables during the search, unless the root moves were
+    // already chosen with the DTZ tables
+    tb_probing: bool,
+    tb_hits: u64,
+    // the value of the root position according to the tablebases
+    tb_score: Option<i32>,
+    params: SearchParams,
+    lmr: LmrTable,
+    pv: PvTable,
+    nodes: u64,
+    seldepth: usize,
+    root_best: Move,
+    // MultiPV: the root moves of the lines already found at this depth
+    // are left out when searching for the next one
+    excluded: Vec<Move>,
+    pv_index: usize,
+    limits: SearchLimits,
+    time: Option<TimeManager>,
+    // set from outside to end the search, e.g. on UCI `stop`
+    stop: Arc<AtomicBool>,
+    // cleared by `ponderhit`, which starts the clock
+    pondering: Arc<AtomicBool>,
+    stopped: bool,
+}
+
+impl Search {
+    pub fn new(mg: Arc<MoveGenerator>, hash_mb: usize) -> Self {
+        let tt = Arc::new(TT::new(hash_mb));
+        Self::with_shared(mg, tt, 0, Arc::new(AtomicBool::new(false)))
+    }
+
+    // Lazy SMP: helper searches run the same iterative deepening on their
+    // own board and history tables. They share the transposition table
+    // and the stop flag with the main search, and only help by filling the
+    // table with results the main search can use.
+    pub fn helper(&self, id: usize) -> Self {
+        let mut helper = Self::with_shared(
+            Arc::clone(&self.mg),
+            Arc::clone(&self.tt),
+            id,
+            self.stop_flag(),
+        );
+        helper.set_params(self.params);
+        helper.set_tablebases(self.tb.clone());
+        helper
+    }
+
+    fn with_shared(mg: Arc<MoveGenerator>, tt: Arc<TT>, id: usize, stop: Arc<AtomicBool>) -> Self {
+        let params = SearchParams::default();
+        Self {
+            board: Board::new(),
+            eval: Evaluator::new(Arc::clone(&mg)),
+            mg,
+            tb: None,
+            tb_probing: false,
+            tb_hits: 0,
+            tb_score: None,
+            tt,
+            id,
+            ordering: MoveOrdering::new(),
+            lmr: LmrTable::new(&params),
+            params,
+            pv: PvTable::new(),
+            nodes: 0,
+            seldepth: 0,
+            root_best: Move::default(),
+            excluded: Vec::new(),
+            pv_index: 0,
+            limits: SearchLimits::default(),
+            time: None,
+            stop,
+            pondering: Arc::new(AtomicBool::new(false)),
+            stopped: false,
+        }
+    }
+
+    pub fn params(&self) -> SearchParams {
+        self.params
+    }
+
+    pub fn set_params(&mut self, params: SearchParams) {
+        self.lmr = LmrTable::new(&params);
+        self.params = params;
+    }
+
+    pub fn set_tablebases(&mut self, tb: Option<Arc<Tablebases>>) {
+        self.tb = tb;
+    }
+
+    // Searches with another transposition table, e.g. one of a new size
+    // shared by all threads.
+    pub fn set_tt(&mut self, tt: Arc<TT>) {
+        self.tt = tt;
+    }
+
+    // Forget everything learned, e.g. when a new game starts.
+    pub fn clear(&mut self) {
+        self.tt.clear();
+        self.ordering.clear();
+        self.eval.clear();
+    }
+
+    // Setting this flag from another thread stops the search. It is not
+    // reset by the search, so a `stop` sent right after `go` isn't lost:
+    // clear it before starting.
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop)
+    }
+
+    // Set together with `SearchLimits::ponder` before starting, for the
+    // same reason. Clearing it is `ponderhit`: the opponent played the
+    // expected move and the ponder search goes on as a normal search, with
+    // the clock starting then.
+    pub fn ponder_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.pondering)
+    }
+
+    // Iterative deepening: search depth 1, 2, ... until one of the limits
+    // is reached. Each iteration fills the TT and history tables, so the
+    // next one is ordered well enough to more than make up for the
+    // repeated work.
+    pub fn iterative_deepening(
+        &mut self,
+        board: &Board,
+        limits: &SearchLimits,
+        mut report: impl FnMut(&SearchInfo),
+    ) -> SearchResult {
+        let start = Instant::now();
+        self.board = board.clone();
+        self.nodes = 0;
+        self.tb_hits = 0;
+        self.limits = limits.clone();
+        self.time = (limits.time.is_set() && !limits.infinite)
+            .then(|| TimeManager::new(&limits.time, board.we(), limits.move_overhead));
+        self.stopped = false;
+        self.ordering.clear_killers();
+        let mut result = SearchResult {
+            best_move: Move::default(),
+            ponder_move: Move::default(),
+            score: 0,
+            depth: 0,
+            nodes: 0,
+        };
+
+        let mut root_moves = MoveList::new();
+        self.board.get_legal_moves(&self.mg, &mut root_moves);
+        self.rank_root_moves(root_moves.as_slice());
+        let root_moves = root_moves
+            .iter()
+            .filter(|&&m| self.limits.allows_root_move(m))
+            .count();
+        let multi_pv = limits.multi_pv.clamp(1, root_moves.max(1));
+        let mut previous_scores = Vec::new();
+
+        let max_depth = limits.depth.unwrap_or((MAX_PLY - 1) as i8);
+        for depth in 1..=max_depth.clamp(1, (MAX_PLY - 1) as i8) {
+            // Helpers skip every other depth, half of them the odd and half
+            // the even ones, so that the threads spread over more depths
+            // instead of all searching the same tree.
+            if self.id > 0
+                && depth > 1
+                && depth < max_depth
+                && (depth as usize + self.id).is_multiple_of(2)
+            {
+                continue;
+            }
+            self.seldepth = 0;
+            self.excluded.clear();
+            let mut lines = Vec::with_capacity(multi_pv);
+            for pv_index in 0..multi_pv {
+                self.pv_index = pv_index;
+                let previous = previous_scores
+                    .get(pv_index)
+                    .copied()
+                    .unwrap_or(result.score);
+                let score = self.aspiration(depth, previous, start, &mut report);
+                if pv_index == 0 && !self.root_best.is_null() {
+                    if self.root_best != result.best_move {
+                        result.ponder_move = Move::default();
+                    }
+                    result.best_move = self.root_best;
+                }
+                if self.stopped {
+                    break;
+                }
+                lines.push((self.root_best, self.info(depth, score, Bound::Exact, start)));
+                self.excluded.push(self.root_best);
+            }
+            if self.stopped {
+                // the root move which raised alpha is still good to play,
+                // the rest of the aborted iteration is not to be trusted
+                break;
+            }
+
+            // a later line can come out better than an earlier one, as the
+            // searches are not exact
+            lines.sort_by_key(|(_, line)| std::cmp::Reverse(line.score));
+            for (i, (_, line)) in lines.iter_mut().enumerate() {
+                line.multipv = i + 1;
+                report(line);
+            }
+            previous_scores = lines.iter().map(|(_, line)| line.score).collect();
+            let score = lines[0].1.score;
+            if !lines[0].0.is_null() {
+                result.best_move = lines[0].0;
+                result.ponder_move = lines[0].1.pv.get(1).copied().unwrap_or_default();
+            }
+            result.score = score;
+            result.depth = depth;
+            result.nodes = self.nodes;
+
+            // no need to look deeper when the mate is found
+            let proven = score.abs() > MATE_BOUND && CHECKMATE - score.abs() <= depth as i32;
+            if proven || self.limits.mate_found(score) {
+                break;
+            }
+            if let Some(time) = self.clock() {
+                time.update(result.best_move, score);
+                if time.soft_limit_reached() {
+                    break;
+                }
+            }
+        }
+
+        // stopped before even the first iteration was done
+        if result.best_move.is_null() {
+            let mut moves = MoveList::new();
+            self.board.get_legal_moves(&self.mg, &mut moves);
+            if let Some(&m) = moves.iter().find(|&&m| self.limits.allows_root_move(m)) {
+                result.best_move = m;
+            }
+        }
+        result.nodes = self.nodes;
+        result
+    }
+
+    // With the root position in the tablebases, only the root moves which
+    // keep the best result are searched. The DTZ tables already choose
+    // moves which make progress, so the search needn't probe after that.
+    // With only WDL tables, it still probes to find the way to the win.
+    fn rank_root_moves(&mut self, moves: &[Move]) {
+        self.tb_probing = self.tb.is_some();
+        self.tb_score = None;
+        let Some(tb) = &self.tb else {
+            return;
+        };
+        let pieces = self.board.occupancy().count_ones() as usize;
+        if pieces > tb.max_pieces() || self.board.game_state.castling != 0 {
+            return;
+        }
+        let moves: Vec<Move> = moves
+            .iter()
+            .copied()
+            .filter(|&m| self.limits.allows_root_move(m))
+            .collect();
+        if let Some((best, score, dtz)) = tb.rank_root_moves(&mut self.board, &self.mg, &moves) {
+            self.limits.search_moves = best;
+            self.tb_score = Some(score);
+            self.tb_probing = !dtz && score > DRAW;
+        }
+    }
+
+    // Polled during the search. Once a limit is reached, every node
+    // returns at once and the iteration is abandoned.
+    fn should_stop(&mut self) -> bool {
+        if self.stopped {
+            return true;
+        }
+        if self.limits.nodes.is_some_and(|nodes| self.nodes >= nodes) {
+            self.stopped = true;
+        } else if self.nodes.is_multiple_of(CHECK_INTERVAL) {
+            self.stopped = self.stop.load(Ordering::Relaxed)
+                || self.clock().is_some_and(|time| time.hard_limit_reached());
+        }
+        self.stopped
+    }
+
+    // The time manager, unless the clock isn't running: no time control,
+    // or still pondering.
+    fn clock(&mut self) -> Option<&mut TimeManager> {
+        if self.limits.ponder {
+            if self.pondering.load(Ordering::Relaxed) {
+                return None;
+            }
+            self.limits.ponder = false;
+            if let Some(time) = &mut self.time {
+                time.restart();
+            }
+        }
+        self.time.as_mut()
+    }
+
+    // Aspiration windows: the score rarely moves far from one iteration to
+    // the next, so a narrow window around the last one gives more cutoffs.
+    // When the score falls outside, the window is widened on that side and
+    // the depth searched again.
+    fn aspiration(
+        &mut self,
+        depth: i8,
+        previous: i32,
+        start: Instant,
+        report: &mut impl FnMut(&SearchInfo),
+    ) -> i32 {
+        let mut window = self.params.asp_window;
+        let (mut alpha, mut beta) = if depth >= self.params.asp_min_depth
+            && previous.abs() < MATE_BOUND
+            && self.tb_score.is_none()
+        {
+            (previous - window, previous + window)
+        } else {
+            (-INF, INF)
+        };
+
+        loop {
+            self.root_best = Move::default();
+            let score = self.alpha_beta(depth, alpha, beta, 0, true);
+            if self.stopped {
+                return score;
+            }
+            if score <= alpha && alpha > -INF {
+                report(&self.info(depth, score, Bound::Upper, start));
+                beta = (alpha + beta) / 2;
+                alpha = (score - window).max(-INF);
+            } else if score >= beta && beta < INF {
+                report(&self.info(depth, score, Bound::Lower, start));
+                beta = (score + window).min(INF);
+            } else {
+                return score;
+            }
+            window += window / 2;
+        }
+    }
+
+    fn info(&self, depth: i8, score: i32, bound: Bound, start: Instant) -> SearchInfo {
+        // the tablebases know better, unless there is a mate
+        let score = match self.tb_score {
+            Some(tb_score) if score.abs() < MATE_BOUND => tb_score,
+            _ => score,
+        };
+        SearchInfo {
+            multipv: self.pv_index + 1,
+            depth,
+            seldepth: self.seldepth,
+            score,
+            bound,
+            nodes: self.nodes,
+            tbhits: self.tb_hits,
+            hashfull: self.tt.hashfull(),
+            time: start.elapsed(),
+            pv: self.pv.line().to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::moves::MoveType;
+    use crate::board::pieces::Pieces;
+
+    fn search_with(fen: &str, limits: &SearchLimits) -> SearchResult {
+        let mg = Arc::new(MoveGenerator::new());
+        let mut search = Search::new(mg, 1);
+        search.iterative_deepening(&Board::fen(Some(fen.to_string())), limits, |_| ())
+    }
+
+    fn search(fen: &str, depth: i8) -> SearchResult {
+        search_with(fen, &SearchLimits::depth(depth))
+    }
+
+    #[test]
+    fn test_finds_mate_in_one() {
+        // back rank mate: Ra8#
+        let result = search("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", 3);
+        assert_eq!((result.best_move.from(), result.best_move.to()), (0, 56));
+        assert_eq!(result.score, CHECKMATE - 1);
+    }
+
+    #[test]
+    fn test_wins_hanging_queen() {
+        let result = search("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", 4);
+        assert_eq!((result.best_move.from(), result.best_move.to()), (11, 35));
+        assert!(result.score >= 400);
+    }
+
+    #[test]
+    fn test_limits() {
+        let start = crate::defs::FEN_START_POSITION;
+        let result = search_with(start, &SearchLimits::depth(3));
+        assert_eq!(result.depth, 3);
+        // the reply to ponder on is legal after the best move
+        let mut board = Board::fen(None);
+        board.make(result.best_move);
+        let mut replies = MoveList::new();
+        board.get_legal_moves(&MoveGenerator::new(), &mut replies);
+        assert!(replies.iter().any(|&m| m == result.ponder_move));
+
+        let result = search_with(start, &SearchLimits::nodes(5000));
+        assert!(result.nodes <= 5000);
+        assert!(!result.best_move.is_null());
+
+        let result = search_with(start, &SearchLimits::movetime(50));
+        assert!(!result.best_move.is_null());
+        assert!(result.depth < (MAX_PLY - 1) as i8);
+
+        // Ra8 would be mate in 1 at depth 1, but only e2e4 may be searched
+        let fen = "6k1/5ppp/8/8/8/8/4P3/R5K1 w - - 0 1";
+        let e2e4 = Move::new(Pieces::Pawn, 12, 28, MoveType::Regular);
+        let limits = SearchLimits {
+            depth: Some(2),
+            search_moves: vec![e2e4],
+            ..Default::default()
+        };
+        assert_eq!(search_with(fen, &limits).best_move, e2e4);
+    }
+
+    #[test]
+    fn test_mate_limit_stops_early() {
+        let limits = SearchLimits {
+            mate: Some(1),
+            ..Default::default()
+        };
+        let result = search_with("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", &limits);
+        assert_eq!(result.score, CHECKMATE - 1);
+        assert_eq!(result.depth, 1);
+    }
+
+    #[test]
+    fn test_multi_pv() {
+        let mg = Arc::new(MoveGenerator::new());
+        let mut search = Search::new(mg, 1);
+        // Ra8 mates, Rxd1 wins a knight, everything else is about equal
+        let board = Board::fen(Some("6k1/5ppp/8/8/8/8/8/R2n2K1 w - - 0 1".to_string()));
+        let limits = SearchLimits {
+            depth: Some(4),
+            multi_pv: 3,
+            ..Default::default()
+        };
+        let mut lines = Vec::new();
+        let result = search.iterative_deepening(&board, &limits, |info| {
+            if info.bound == Bound::Exact {
+                lines.push(info.clone());
+            }
+        });
+        // the last iteration, which ends the search as the mate is found
+        lines.retain(|line| line.depth == result.depth);
+        assert_eq!(lines.len(), 3);
+        let numbers: Vec<usize> = lines.iter().map(|line| line.multipv).collect();
+        assert_eq!(numbers, [1, 2, 3]);
+        assert!(lines.windows(2).all(|w| w[0].score >= w[1].score));
+        assert_eq!(lines[0].pv[0].to_uci(), "a1a8");
+        assert_eq!(lines[0].score, CHECKMATE - 1);
+        assert_eq!(lines[1].pv[0].to_uci(), "a1d1");
+        assert_ne!(lines[2].pv[0], lines[1].pv[0]);
+        assert_eq!(result.best_move, lines[0].pv[0]);
+
+        // more lines than moves
+        let board = Board::fen(Some("6k1/5ppp/8/8/8/8/8/R5K1 b - - 0 1".to_string()));
+        let limits = SearchLimits {
+            depth: Some(2),
+            multi_pv: 50,
+            ..Default::default()
+        };
+        let mut count = 0;
+        search.iterative_deepening(&board, &limits, |info| count += (info.depth == 2) as usize);
+        assert_eq!(count, 8);
+    }
+
+    #[test]
+    fn test_stalemate_is_a_draw() {
+        let result = search("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", 3);
+        assert!(result.best_move.is_null());
+        assert_eq!(result.score, DRAW);
+    }
+
+    // The deepest iteration must not overflow the depth by extending a
+    // check at the root.
+    #[test]
+    fn test_check_extension_at_max_depth() {
+        let deep = std::thread::Builder::new()
+            .stack_size(THREAD_STACK_SIZE)
+            .spawn(|| {
+                let mut search = Search::new(Arc::new(MoveGenerator::new()), 1);
+                search.board = Board::fen(Some("4k3/8/8/8/8/8/4r3/4K3 w - - 0 1".to_string()));
+                search.limits = SearchLimits::nodes(1000);
+                search.alpha_beta((MAX_PLY - 1) as i8, -INF, INF, 0, true);
+                search.stopped
+            })
+            .unwrap();
+        assert!(deep.join().unwrap());
+    }
+
+    // Every tunable at the ends of its range, one at a time and all
+    // together, must still give a sane search.
+    #[test]
+    fn test_tunables_at_extremes() {
+        use self::params::TUNABLES;
+
+        let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 3 3";
+        let board = Board::fen(Some(fen.to_string()));
+        let mut search = Search::new(Arc::new(MoveGenerator::new()), 1);
+        let mut run = |params: SearchParams| {
+            search.clear();
+            search.set_params(params);
+            let result = search.iterative_deepening(&board, &SearchLimits::depth(5), |_| ());
+            assert!(!result.best_move.is_null());
+            assert_eq!(result.depth, 5);
+        };
+        for extreme in [|t: &params::Tunable| t.min, |t: &params::Tunable| t.max] {
+            let mut all = SearchParams::default();
+            for tunable in TUNABLES {
+                let mut params = SearchParams::default();
+                tunable.set(&mut params, extreme(tunable));
+                tunable.set(&mut all, extreme(tunable));
+                run(params);
+            }
+            run(all);
+        }
+    }
+}
@@ -0,0 +1,281 @@
+use super::movepicker::MovePicker;
+use super::transposition::Bound;
+use super::{Search, CHECKMATE, DRAW, INF, MATE_BOUND, MAX_PLY, TB_WIN};
+use crate::board::movelist::MoveList;
+use crate::board::moves::Move;
+
+impl Search {
+    // Principal variation search: the first move is searched with the full
+    // window, the others with a null window around alpha, which is enough
+    // to prove they are worse. Only a move that beats alpha after all is
+    // searched again with the full window.
+    //
+    // On top of that the usual selective search: null move pruning,
+    // reverse futility pruning and razoring before any move is tried,
+    // futility and late move pruning of quiet moves, late move reductions,
+    // and check extensions.
+    pub(super) fn alpha_beta(
+        &mut self,
+        mut depth: i8,
+        mut alpha: i32,
+        mut beta: i32,
+        ply: usize,
+        null_allowed: bool,
+    ) -> i32 {
+        self.pv.clear(ply);
+        if self.should_stop() {
+            return 0;
+        }
+        let pv_node = beta - alpha > 1;
+        let root = ply == 0;
+        if ply >= MAX_PLY - 1 {
+            return self.eval.evaluate(&self.board);
+        }
+
+        if !root {
+            if self.board.is_repetition() || self.board.draw_by_fifty_moves() {
+                return DRAW;
+            }
+            // a shorter mate was already found elsewhere
+            alpha = alpha.max(-CHECKMATE + ply as i32);
+            beta = beta.min(CHECKMATE - ply as i32 - 1);
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+
+        let in_check = self.board.in_check(&self.mg);
+        if in_check {
+            depth = depth
+                .saturating_add(self.params.check_extension)
+                .min((MAX_PLY - 1) as i8);
+        }
+        if depth <= 0 {
+            return self.quiescence(alpha, beta, ply, 0);
+        }
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(ply);
+
+        let key = self.board.game_state.zobrist_key;
+        let mut tt_move = Move::default();
+        if let Some(entry) = self.tt.probe(key) {
+            tt_move = entry.best_move;
+            let score = entry.score(ply);
+            if !pv_node && entry.depth >= depth {
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _ => (),
+                }
+            }
+        }
+
+        // Right after a capture or pawn move, the WDL tables know the
+        // result. Later on, the fifty move counter could change it.
+        let game_state = &self.board.game_state;
+        if !root && self.tb_probing && game_state.halfmove_clock == 0 && game_state.castling == 0 {
+            if let Some(wdl) = self.probe_wdl() {
+                self.tb_hits += 1;
+                let (score, bound) = match wdl {
+                    w if w < -1 => (-TB_WIN + ply as i32, Bound::Upper),
+                    w if w > 1 => (TB_WIN - ply as i32, Bound::Lower),
+                    // cursed wins and blessed losses are almost draws
+                    w => (DRAW + 2 * w, Bound::Exact),
+                };
+                let cutoff = match bound {
+                    Bound::Exact => true,
+                    Bound::Lower => score >= beta,
+                    Bound::Upper => score <= alpha,
+                };
+                if cutoff {
+                    let tb_depth = depth.saturating_add(6).min((MAX_PLY - 1) as i8);
+                    self.tt
+                        .store(key, tb_depth, score, bound, Move::default(), ply);
+                    return score;
+                }
+            }
+        }
+
+        let static_eval = if in_check {
+            -INF
+        } else {
+            self.eval.evaluate(&self.board)
+        };
+
+        if !pv_node && !in_check {
+            // Reverse futility pruning: so far above beta that no move is
+            // going to bring the score back below it.
+            if depth <= self.params.rfp_max_depth
+                && beta.abs() < MATE_BOUND
+                && static_eval - self.params.rfp_margin * depth as i32 >= beta
+            {
+                return static_eval;
+            }
+
+            // Razoring: so far below alpha that only tactics can help, and
+            // quiescence finds those.
+            if depth <= self.params.razor_max_depth
+                && static_eval + self.params.razor_margin * (depth as i32) < alpha
+            {
+                let score = self.quiescence(alpha, beta, ply, 0);
+                if self.stopped {
+                    return 0;
+                }
+                if score < alpha {
+                    return score;
+                }
+            }
+
+            // Null move pruning: if passing still fails high, a real move
+            // will too. Not in pawn endings, where zugzwang is common.
+            if null_allowed
+                && depth >= self.params.nmp_min_depth
+                && static_eval >= beta
+                && self.board.has_non_pawn_material(self.board.we())
+            {
+                let r = self.params.nmp_base_reduction + depth / self.params.nmp_depth_divisor;
+                self.board.make_null();
+                let score = -self.alpha_beta(depth - 1 - r, -beta, -beta + 1, ply + 1, false);
+                self.board.unmake_null();
+                if self.stopped {
+                    return 0;
+                }
+                if score >= beta {
+                    // unproven mates from a null move search are not trusted
+                    return if score > MATE_BOUND { beta } else { score };
+                }
+            }
+        }
+
+        let mut picker = if in_check {
+            MovePicker::evasions(tt_move)
+        } else {
+            MovePicker::new(&self.board, &self.ordering, tt_move, ply)
+        };
+        let futile = !pv_node
+            && !in_check
+            && depth <= self.params.fp_max_depth
+            && static_eval + self.params.futility_margin(depth) <= alpha;
+        let late_move_pruning = !pv_node && !in_check && depth <= self.params.lmp_max_depth;
+
+        let original_alpha = alpha;
+        let mut best_score = -INF;
+        let mut best_move = Move::default();
+        let mut legal_moves = 0;
+        let mut quiets_tried = MoveList::new();
+
+        while let Some(m) = picker.next(&self.board, &self.mg, &self.ordering, ply) {
+            let quiet = m.is_quiet();
+
+            // Futility and late move pruning skip quiet moves which are
+            // unlikely to matter, once there is a move to fall back on.
+            if !root && quiet && legal_moves > 0 && best_score > -MATE_BOUND {
+                if futile {
+                    continue;
+                }
+                if late_move_pruning && quiets_tried.len() >= self.params.lmp_threshold(depth) {
+                    continue;
+                }
+            }
+
+            if root && (!self.limits.allows_root_move(m) || self.excluded.contains(&m)) {
+                continue;
+            }
+            if !self.board.make_legal(m, &self.mg) {
+                continue;
+            }
+            legal_moves += 1;
+            let gives_check = self.board.in_check(&self.mg);
+            let new_depth = depth - 1;
+
+            let mut score;
+            if legal_moves == 1 {
+                score = -self.alpha_beta(new_depth, -beta, -alpha, ply + 1, true);
+            } else {
+                // Late move reductions: quiet moves ordered late are
+                // searched shallower, and only if they surprise by beating
+                // alpha they get the full depth.
+                let mut r = 0;
+                if legal_moves > self.params.lmr_min_moves
+                    && depth >= self.params.lmr_min_depth
+                    && quiet
+                    && !in_check
+                    && !gives_check
+                {
+                    r = self.lmr.reduction(depth, legal_moves);
+                    if pv_node {
+                        r -= 1;
+                    }
+                    r = r.min(new_depth - 1).max(0);
+                }
+                score = -self.alpha_beta(new_depth - r, -alpha - 1, -alpha, ply + 1, true);
+                if score > alpha && r > 0 {
+                    score = -self.alpha_beta(new_depth, -alpha - 1, -alpha, ply + 1, true);
+                }
+                if score > alpha && score < beta {
+                    score = -self.alpha_beta(new_depth, -beta, -alpha, ply + 1, true);
+                }
+            }
+            self.board.unmake();
+            if self.stopped {
+                return 0;
+            }
+            if quiet {
+                quiets_tried.push(m);
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = m;
+                if score > alpha {
+                    alpha = score;
+                    if pv_node {
+                        self.pv.update(ply, m);
+                    }
+                    if root {
+                        self.root_best = m;
+                    }
+                    if score >= beta {
+                        if quiet {
+                            let tried = quiets_tried.as_slice();
+                            self.ordering
+                                .update_quiet(&self.board, m, tried, depth, ply);
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+
+        if legal_moves == 0 {
+            return if in_check {
+                -CHECKMATE + ply as i32
+            } else {
+                DRAW
+            };
+        }
+
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if alpha > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        // the result of a root search without some of the moves isn't the
+        // value of the position
+        if !root || self.excluded.is_empty() {
+            self.tt.store(key, depth, best_score, bound, best_move, ply);
+        }
+        best_score
+    }
+
+    fn probe_wdl(&mut self) -> Option<i32> {
+        let tb = self.tb.as_ref()?;
+        if self.board.occupancy().count_ones() as usize > tb.max_pieces() {
+            return None;
+        }
+        tb.probe_wdl(&mut self.board, &self.mg)
+    }
+}
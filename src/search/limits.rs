@@ -0,0 +1,95 @@
+use super::time::{TimeControl, DEFAULT_MOVE_OVERHEAD};
+use super::{CHECKMATE, MATE_BOUND};
+use crate::board::moves::Move;
+
+// Everything that tells the search when to stop, as given with UCI `go`.
+// Without any limit the search runs until it is stopped from outside.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchLimits {
+    pub depth: Option<i8>,
+    pub nodes: Option<u64>,
+    // find a mate in this many moves
+    pub mate: Option<i32>,
+    pub time: TimeControl,
+    pub move_overhead: u64,
+    // the clock is ignored, until `ponderhit` for a ponder search
+    pub infinite: bool,
+    pub ponder: bool,
+    // only these root moves are searched, all if empty
+    pub search_moves: Vec<Move>,
+    // number of best lines to find
+    pub multi_pv: usize,
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        Self {
+            depth: None,
+            nodes: None,
+            mate: None,
+            time: TimeControl::default(),
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            infinite: false,
+            ponder: false,
+            search_moves: Vec::new(),
+            multi_pv: 1,
+        }
+    }
+}
+
+impl SearchLimits {
+    // Shorthands for embedding the search, where there is no `go` to parse.
+    pub fn depth(depth: i8) -> Self {
+        Self {
+            depth: Some(depth),
+            ..Default::default()
+        }
+    }
+
+    pub fn nodes(nodes: u64) -> Self {
+        Self {
+            nodes: Some(nodes),
+            ..Default::default()
+        }
+    }
+
+    pub fn movetime(ms: u64) -> Self {
+        Self {
+            time: TimeControl {
+                movetime: Some(ms),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    // Is the score a mate for the side to move as short as asked for?
+    pub fn mate_found(&self, score: i32) -> bool {
+        match self.mate {
+            Some(moves) => score > MATE_BOUND && (CHECKMATE - score + 1) / 2 <= moves,
+            None => false,
+        }
+    }
+
+    pub fn allows_root_move(&self, m: Move) -> bool {
+        self.search_moves.is_empty() || self.search_moves.contains(&m)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mate_found() {
+        let limits = SearchLimits {
+            mate: Some(2),
+            ..Default::default()
+        };
+        assert!(limits.mate_found(CHECKMATE - 1));
+        assert!(limits.mate_found(CHECKMATE - 3));
+        assert!(!limits.mate_found(CHECKMATE - 5));
+        assert!(!limits.mate_found(-CHECKMATE + 2));
+        assert!(!SearchLimits::default().mate_found(CHECKMATE - 1));
+    }
+}
@@ -0,0 +1,282 @@
+use super::ordering::{MoveOrdering, CAPTURE_SCORE};
+use crate::board::movelist::MoveList;
+use crate::board::moves::Move;
+use crate::board::Board;
+use crate::move_generator::MoveGenerator;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Stage {
+    TTMove,
+    GenerateCaptures,
+    GoodCaptures,
+    Killer1,
+    Killer2,
+    CounterMove,
+    GenerateQuiets,
+    Quiets,
+    BadCaptures,
+    GenerateEvasions,
+    Evasions,
+    // quiescence: all captures, best first, then the quiet checks on its
+    // first ply
+    GenerateQsearch,
+    Qsearch,
+    GenerateQuietChecks,
+    QuietChecks,
+    Done,
+}
+
+// Hands out the moves of a node one by one, generating them in stages:
+// the TT move first, then winning captures, killers and the counter move,
+// and only then the quiet moves and losing captures. If one of the early
+// moves causes a cutoff, the quiet moves are never generated.
+pub struct MovePicker {
+    stage: Stage,
+    tt_move: Move,
+    killers: [Move; 2],
+    counter: Move,
+    captures: MoveList,
+    quiets: MoveList,
+    capture_index: usize,
+    quiet_index: usize,
+    // moves handed out before their stage was generated
+    early: [Move; 4],
+    early_count: usize,
+    // quiescence only
+    quiet_checks: bool,
+}
+
+impl MovePicker {
+    pub fn new(board: &Board, ordering: &MoveOrdering, tt_move: Move, ply: usize) -> Self {
+        Self::with_stage(
+            Stage::TTMove,
+            tt_move,
+            ordering.killers(ply),
+            ordering.counter_move(board),
+        )
+    }
+
+    // When in check all evasions are generated and ordered at once.
+    pub fn evasions(tt_move: Move) -> Self {
+        Self::with_stage(
+            Stage::GenerateEvasions,
+            tt_move,
+            [Move::default(); 2],
+            Move::default(),
+        )
+    }
+
+    pub fn qsearch(quiet_checks: bool) -> Self {
+        let mut picker = Self::with_stage(
+            Stage::GenerateQsearch,
+            Move::default(),
+            [Move::default(); 2],
+            Move::default(),
+        );
+        picker.quiet_checks = quiet_checks;
+        picker
+    }
+
+    fn with_stage(stage: Stage, tt_move: Move, killers: [Move; 2], counter: Move) -> Self {
+        Self {
+            stage,
+            tt_move,
+            killers,
+            counter,
+            captures: MoveList::new(),
+            quiets: MoveList::new(),
+            capture_index: 0,
+            quiet_index: 0,
+            early: [Move::default(); 4],
+            early_count: 0,
+            quiet_checks: false,
+        }
+    }
+
+    fn already_tried(&self, m: Move) -> bool {
+        self.early[..self.early_count].contains(&m)
+    }
+
+    fn hand_out_early(&mut self, m: Move) -> Option<Move> {
+        self.early[self.early_count] = m;
+        self.early_count += 1;
+        Some(m)
+    }
+
+    pub fn next(
+        &mut self,
+        board: &Board,
+        mg: &MoveGenerator,
+        ordering: &MoveOrdering,
+        ply: usize,
+    ) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::TTMove => {
+                    self.stage = Stage::GenerateCaptures;
+                    if board.is_pseudo_legal(mg, self.tt_move) {
+                        return self.hand_out_early(self.tt_move);
+                    }
+                }
+                Stage::GenerateCaptures => {
+                    board.generate_captures(mg, &mut self.captures);
+                    ordering.score_moves(board, mg, &mut self.captures, Move::default(), ply);
+                    self.stage = Stage::GoodCaptures;
+                }
+                Stage::GoodCaptures => {
+                    if self.capture_index < self.captures.len() {
+                        let m = self.captures.pick(self.capture_index);
+                        if self.captures.score(self.capture_index) >= CAPTURE_SCORE {
+                            self.capture_index += 1;
+                            if !self.already_tried(m) {
+                                return Some(m);
+                            }
+                            continue;
+                        }
+                    }
+                    self.stage = Stage::Killer1;
+                }
+                Stage::Killer1 | Stage::Killer2 | Stage::CounterMove => {
+                    let (m, next) = match self.stage {
+                        Stage::Killer1 => (self.killers[0], Stage::Killer2),
+                        Stage::Killer2 => (self.killers[1], Stage::CounterMove),
+                        _ => (self.counter, Stage::GenerateQuiets),
+                    };
+                    self.stage = next;
+                    if m.is_quiet() && !self.already_tried(m) && board.is_pseudo_legal(mg, m) {
+                        return self.hand_out_early(m);
+                    }
+                }
+                Stage::GenerateQuiets => {
+                    board.generate_quiets(mg, &mut self.quiets);
+                    ordering.score_moves(board, mg, &mut self.quiets, Move::default(), ply);
+                    self.stage = Stage::Quiets;
+                }
+                Stage::Quiets => {
+                    if self.quiet_index < self.quiets.len() {
+                        let m = self.quiets.pick(self.quiet_index);
+                        self.quiet_index += 1;
+                        if !self.already_tried(m) {
+                            return Some(m);
+                        }
+                        continue;
+                    }
+                    self.stage = Stage::BadCaptures;
+                }
+                Stage::BadCaptures => {
+                    if self.capture_index < self.captures.len() {
+                        let m = self.captures.pick(self.capture_index);
+                        self.capture_index += 1;
+                        if !self.already_tried(m) {
+                            return Some(m);
+                        }
+                        continue;
+                    }
+                    self.stage = Stage::Done;
+                }
+                Stage::GenerateEvasions => {
+                    board.generate_evasions(mg, &mut self.captures);
+                    ordering.score_moves(board, mg, &mut self.captures, self.tt_move, ply);
+                    self.stage = Stage::Evasions;
+                }
+                Stage::GenerateQsearch => {
+                    board.generate_captures(mg, &mut self.captures);
+                    ordering.score_moves(board, mg, &mut self.captures, Move::default(), ply);
+                    self.stage = Stage::Qsearch;
+                }
+                Stage::Evasions | Stage::Qsearch => {
+                    if self.capture_index < self.captures.len() {
+                        let m = self.captures.pick(self.capture_index);
+                        self.capture_index += 1;
+                        return Some(m);
+                    }
+                    self.stage = if self.stage == Stage::Qsearch && self.quiet_checks {
+                        Stage::GenerateQuietChecks
+                    } else {
+                        Stage::Done
+                    };
+                }
+                Stage::GenerateQuietChecks => {
+                    board.generate_quiet_checks(mg, &mut self.quiets);
+                    ordering.score_moves(board, mg, &mut self.quiets, Move::default(), ply);
+                    self.stage = Stage::QuietChecks;
+                }
+                Stage::QuietChecks => {
+                    if self.quiet_index < self.quiets.len() {
+                        let m = self.quiets.pick(self.quiet_index);
+                        self.quiet_index += 1;
+                        return Some(m);
+                    }
+                    self.stage = Stage::Done;
+                }
+                Stage::Done => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::moves::MoveType;
+    use crate::board::pieces::Pieces;
+
+    // The staged picker must hand out every pseudo-legal move exactly once.
+    fn assert_picks_all(fen: &str, tt_move: Move) {
+        let mg = MoveGenerator::new();
+        let board = Board::fen(Some(fen.to_string()));
+        let mut ordering = MoveOrdering::new();
+        let killer = Move::new(Pieces::Rook, 0, 1, MoveType::Regular);
+        ordering.update_quiet(&board, killer, &[killer], 3, 0);
+
+        let mut all = MoveList::new();
+        board.get_moves(&mg, &mut all);
+        let mut picker = MovePicker::new(&board, &ordering, tt_move, 0);
+        let mut picked = MoveList::new();
+        while let Some(m) = picker.next(&board, &mg, &ordering, 0) {
+            assert!(!picked.contains(m), "{:?} picked twice", m);
+            picked.push(m);
+        }
+        assert_eq!(picked.len(), all.len());
+        if all.contains(tt_move) {
+            assert_eq!(picked[0], tt_move);
+        }
+    }
+
+    #[test]
+    fn test_picks_every_move_once() {
+        let kiwipete = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+        let quiet = Move::new(Pieces::Knight, 18, 1, MoveType::Regular);
+        let capture = Move::new(Pieces::Knight, 36, 53, MoveType::Capture(Pieces::Pawn));
+        assert_picks_all(kiwipete, Move::default());
+        assert_picks_all(kiwipete, quiet);
+        assert_picks_all(kiwipete, capture);
+        // a killer from another position which is not legal here
+        assert_picks_all(crate::defs::FEN_START_POSITION, quiet);
+    }
+
+    #[test]
+    fn test_qsearch_quiet_checks() {
+        let mg = MoveGenerator::new();
+        let ordering = MoveOrdering::new();
+        let board = Board::fen(Some("6k1/5ppp/3p4/8/4N3/8/5PPP/R5K1 w - - 0 1".to_string()));
+        let picked = |quiet_checks| {
+            let mut picker = MovePicker::qsearch(quiet_checks);
+            let mut picked = MoveList::new();
+            while let Some(m) = picker.next(&board, &mg, &ordering, 0) {
+                picked.push(m);
+            }
+            picked
+        };
+        let mate = Move::new(Pieces::Rook, 0, 56, MoveType::Regular);
+        let capture = Move::new(Pieces::Knight, 28, 43, MoveType::Capture(Pieces::Pawn));
+        let check = Move::new(Pieces::Knight, 28, 45, MoveType::Regular);
+        let captures = picked(false);
+        assert_eq!(captures.len(), 1);
+        assert_eq!(captures[0], capture);
+        let with_checks = picked(true);
+        assert_eq!(with_checks.len(), 3);
+        assert_eq!(with_checks[0], capture);
+        assert!(with_checks.contains(mate) && with_checks.contains(check));
+    }
+}
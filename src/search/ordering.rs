@@ -0,0 +1,306 @@
+use super::MAX_PLY;
+use crate::board::movelist::MoveList;
+use crate::board::moves::Move;
+use crate::board::pieces::Pieces;
+use crate::board::Board;
+use crate::defs::*;
+use crate::move_generator::MoveGenerator;
+
+// Score bands. Every move of a higher band is tried before every move of
+// a lower band; inside a band the tables below decide.
+pub const TT_MOVE_SCORE: i32 = 10_000_000;
+pub const CAPTURE_SCORE: i32 = 1_000_000;
+pub const KILLER_SCORE: [i32; 2] = [900_000, 800_000];
+pub const COUNTER_SCORE: i32 = 700_000;
+// captures losing material are tried after all quiet moves
+pub const BAD_CAPTURE_SCORE: i32 = -1_000_000;
+
+// History values are kept in [-MAX_HISTORY, MAX_HISTORY] by the gravity
+// formula in `apply_gravity`, so quiets never reach the counter band.
+pub const MAX_HISTORY: i32 = 16_384;
+
+// Most valuable victim, least valuable attacker. Indexed by [victim][attacker]
+// in the order of `Pieces`: Pawn, Bishop, Knight, Rook, Queen, King.
+const PIECE_ORDER: [i32; NrOf::PIECE_TYPES] = [1, 3, 2, 4, 5, 6];
+const fn mvv_lva_table() -> [[i32; NrOf::PIECE_TYPES]; NrOf::PIECE_TYPES] {
+    let mut table = [[0; NrOf::PIECE_TYPES]; NrOf::PIECE_TYPES];
+    let mut victim = 0;
+    while victim < NrOf::PIECE_TYPES {
+        let mut attacker = 0;
+        while attacker < NrOf::PIECE_TYPES {
+            table[victim][attacker] = PIECE_ORDER[victim] * 10 - PIECE_ORDER[attacker];
+            attacker += 1;
+        }
+        victim += 1;
+    }
+    table
+}
+pub const MVV_LVA: [[i32; NrOf::PIECE_TYPES]; NrOf::PIECE_TYPES] = mvv_lva_table();
+
+// Continuation history looks back this many plies (1 = the opponent's last
+// move, 2 = our own move before that).
+pub const CONTINUATION_PLIES: usize = 2;
+const CONTINUATION_SIZE: usize =
+    CONTINUATION_PLIES * NrOf::PIECE_TYPES * NrOf::SQUARES * NrOf::PIECE_TYPES * NrOf::SQUARES;
+
+// All heuristics the search learns while it runs. One instance per search
+// thread; it is kept between iterations (and moves), only killers are
+// per-ply and cleared on a new search.
+#[derive(Clone)]
+pub struct MoveOrdering {
+    killers: [[Move; 2]; MAX_PLY],
+    // butterfly history: [side][from][to]
+    history: [[[i32; NrOf::SQUARES]; NrOf::SQUARES]; NrOf::SIDES],
+    // refutation of the previous move: [side][previous piece][previous to]
+    counter_moves: [[[Move; NrOf::SQUARES]; NrOf::PIECE_TYPES]; NrOf::SIDES],
+    // [plies back][previous piece][previous to][piece][to], flattened
+    continuation: Vec<i32>,
+}
+
+impl Default for MoveOrdering {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MoveOrdering {
+    pub fn new() -> Self {
+        Self {
+            killers: [[Move::default(); 2]; MAX_PLY],
+            history: [[[0; NrOf::SQUARES]; NrOf::SQUARES]; NrOf::SIDES],
+            counter_moves: [[[Move::default(); NrOf::SQUARES]; NrOf::PIECE_TYPES]; NrOf::SIDES],
+            continuation: vec![0; CONTINUATION_SIZE],
+        }
+    }
+
+    // Forget everything, e.g. on `ucinewgame`.
+    pub fn clear(&mut self) {
+        *self = MoveOrdering::new();
+    }
+
+    // Killers belong to the position searched before; the long-term tables
+    // are kept.
+    pub fn clear_killers(&mut self) {
+        self.killers = [[Move::default(); 2]; MAX_PLY];
+    }
+
+    // Scores all moves in the list, TT move first. The list itself is not
+    // sorted; the search picks moves in order with `MoveList::pick`.
+    pub fn score_moves(
+        &self,
+        board: &Board,
+        mg: &MoveGenerator,
+        moves: &mut MoveList,
+        tt_move: Move,
+        ply: usize,
+    ) {
+        let counter = self.counter_move(board);
+        for i in 0..moves.len() {
+            let m = moves[i];
+            let score = if !tt_move.is_null() && m == tt_move {
+                TT_MOVE_SCORE
+            } else if !m.is_quiet() {
+                if board.see_ge(mg, m, 0) {
+                    CAPTURE_SCORE + self.capture_score(m)
+                } else {
+                    BAD_CAPTURE_SCORE + self.capture_score(m)
+                }
+            } else if m == self.killers[ply][0] {
+                KILLER_SCORE[0]
+            } else if m == self.killers[ply][1] {
+                KILLER_SCORE[1]
+            } else if m == counter {
+                COUNTER_SCORE
+            } else {
+                self.quiet_score(board, m)
+            };
+            moves.set_score(i, score);
+        }
+    }
+
+    // MVV-LVA, with promotions ranked by the promoted piece.
+    pub fn capture_score(&self, m: Move) -> i32 {
+        let attacker = m.piece() as usize;
+        let capture = match m.captured() {
+            Some(victim) => MVV_LVA[victim as usize][attacker],
+            None => 0,
+        };
+        let promotion = match m.promoted() {
+            Some(Pieces::Queen) => MVV_LVA[Pieces::Queen as usize][attacker],
+            Some(_) => -MVV_LVA[Pieces::Queen as usize][attacker],
+            None => 0,
+        };
+        capture + promotion
+    }
+
+    pub fn quiet_score(&self, board: &Board, m: Move) -> i32 {
+        let side = board.we() as usize;
+        let mut score = self.history[side][m.from() as usize][m.to() as usize];
+        for (plies_back, previous) in previous_moves(board).iter().enumerate() {
+            if let Some(previous) = previous {
+                score += self.continuation[continuation_index(plies_back, *previous, m)];
+            }
+        }
+        score
+    }
+
+    pub fn killers(&self, ply: usize) -> [Move; 2] {
+        self.killers[ply]
+    }
+
+    pub fn counter_move(&self, board: &Board) -> Move {
+        match previous_moves(board)[0] {
+            Some(previous) => {
+                self.counter_moves[board.we() as usize][previous.piece() as usize]
+                    [previous.to() as usize]
+            }
+            None => Move::default(),
+        }
+    }
+
+    // A quiet move caused a beta cutoff. It becomes a killer and the
+    // counter move of the previous move, gets a history bonus, and all
+    // quiets tried before it get a malus.
+    pub fn update_quiet(
+        &mut self,
+        board: &Board,
+        best: Move,
+        tried: &[Move],
+        depth: i8,
+        ply: usize,
+    ) {
+        if self.killers[ply][0] != best {
+            self.killers[ply][1] = self.killers[ply][0];
+            self.killers[ply][0] = best;
+        }
+
+        let previous = previous_moves(board);
+        if let Some(last) = previous[0] {
+            self.counter_moves[board.we() as usize][last.piece() as usize][last.to() as usize] =
+                best;
+        }
+
+        let bonus = history_bonus(depth);
+        self.update_history(board, &previous, best, bonus);
+        for m in tried.iter().filter(|m| **m != best) {
+            self.update_history(board, &previous, *m, -bonus);
+        }
+    }
+
+    fn update_history(
+        &mut self,
+        board: &Board,
+        previous: &[Option<Move>; CONTINUATION_PLIES],
+        m: Move,
+        bonus: i32,
+    ) {
+        let side = board.we() as usize;
+        apply_gravity(
+            &mut self.history[side][m.from() as usize][m.to() as usize],
+            bonus,
+        );
+        for (plies_back, previous) in previous.iter().enumerate() {
+            if let Some(previous) = previous {
+                let index = continuation_index(plies_back, *previous, m);
+                apply_gravity(&mut self.continuation[index], bonus);
+            }
+        }
+    }
+}
+
+// Moves leading to the current position, most recent first.
+fn previous_moves(board: &Board) -> [Option<Move>; CONTINUATION_PLIES] {
+    let mut previous = [None; CONTINUATION_PLIES];
+    let count = board.history.len();
+    for (plies_back, slot) in previous.iter_mut().enumerate() {
+        if let Some(state) = count
+            .checked_sub(plies_back + 1)
+            .and_then(|i| board.history.get_ref(i))
+        {
+            if !state.next_move.is_null() {
+                *slot = Some(state.next_move);
+            }
+        }
+    }
+    previous
+}
+
+fn continuation_index(plies_back: usize, previous: Move, m: Move) -> usize {
+    (((plies_back * NrOf::PIECE_TYPES + previous.piece() as usize) * NrOf::SQUARES
+        + previous.to() as usize)
+        * NrOf::PIECE_TYPES
+        + m.piece() as usize)
+        * NrOf::SQUARES
+        + m.to() as usize
+}
+
+pub fn history_bonus(depth: i8) -> i32 {
+    let depth = depth.max(0) as i32;
+    (depth * depth).min(MAX_HISTORY / 8)
+}
+
+// History gravity: the closer a value is to MAX_HISTORY, the less a bonus
+// pushes it further, so old information fades instead of saturating.
+pub fn apply_gravity(entry: &mut i32, bonus: i32) {
+    let bonus = bonus.clamp(-MAX_HISTORY, MAX_HISTORY);
+    *entry += bonus - *entry * bonus.abs() / MAX_HISTORY;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::moves::MoveType;
+
+    #[test]
+    fn test_mvv_lva() {
+        let pxq = MVV_LVA[Pieces::Queen as usize][Pieces::Pawn as usize];
+        let qxq = MVV_LVA[Pieces::Queen as usize][Pieces::Queen as usize];
+        let pxr = MVV_LVA[Pieces::Rook as usize][Pieces::Pawn as usize];
+        let kxp = MVV_LVA[Pieces::Pawn as usize][Pieces::King as usize];
+        assert!(pxq > qxq);
+        assert!(qxq > pxr);
+        assert!(pxr > kxp);
+        assert!(kxp > 0);
+    }
+
+    #[test]
+    fn test_gravity_stays_bounded() {
+        let mut entry = 0;
+        for _ in 0..10_000 {
+            apply_gravity(&mut entry, history_bonus(30));
+        }
+        assert!(entry <= MAX_HISTORY);
+        for _ in 0..10_000 {
+            apply_gravity(&mut entry, -history_bonus(30));
+        }
+        assert!(entry >= -MAX_HISTORY);
+    }
+
+    #[test]
+    fn test_ordering_bands() {
+        let mg = MoveGenerator::new();
+        let board = Board::fen(Some(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1".to_string(),
+        ));
+        let mut ordering = MoveOrdering::new();
+        let mut moves = MoveList::new();
+        board.get_moves(&mg, &mut moves);
+
+        let tt_move = Move::new(Pieces::Knight, 36, 53, MoveType::Capture(Pieces::Pawn));
+        let killer = Move::new(Pieces::Rook, 0, 1, MoveType::Regular);
+        let quiet = Move::new(Pieces::Rook, 0, 3, MoveType::Regular);
+        assert!(moves.contains(tt_move) && moves.contains(killer));
+        ordering.update_quiet(&board, killer, &[quiet, killer], 4, 3);
+        ordering.score_moves(&board, &mg, &mut moves, tt_move, 3);
+
+        // the TT move comes first, then captures, then the killer
+        assert_eq!(moves.pick(0), tt_move);
+        let mut i = 1;
+        while !moves.pick(i).is_quiet() {
+            i += 1;
+        }
+        assert_eq!(moves[i], killer);
+        // the malus pushed the other quiet below the untouched ones
+        assert!(ordering.quiet_score(&board, quiet) < 0);
+    }
+}
@@ -0,0 +1,227 @@
+use super::MAX_PLY;
+use crate::defs::MAX_LEGAL_MOVES;
+
+// Declares `SearchParams`, its defaults and, for tuning, `TUNABLES` from
+// one list. Each parameter has its default, its UCI option name, its range
+// and the step size SPSA should end with (`c_end`). Fractions are exposed
+// in hundredths, marked by `/ 100`, as UCI only knows integer options.
+macro_rules! search_params {
+    ($($field:ident: $type:ty = $default:expr,
+        $name:literal $(/ $scale:tt)?, $min:literal..=$max:literal, $step:literal;)*) => {
+        #[derive(Clone, Copy, Debug)]
+        pub struct SearchParams {
+            $(pub $field: $type,)*
+        }
+
+        impl Default for SearchParams {
+            fn default() -> Self {
+                Self {
+                    $($field: $default,)*
+                }
+            }
+        }
+
+        #[cfg(any(test, feature = "tune"))]
+        pub const TUNABLES: &[Tunable] = &[
+            $(tunable!($name, $field $(/ $scale)?, $min..=$max, $step),)*
+        ];
+    };
+}
+
+#[cfg(any(test, feature = "tune"))]
+macro_rules! tunable {
+    ($name:literal, $field:ident, $min:literal..=$max:literal, $step:literal) => {
+        Tunable {
+            name: $name,
+            min: $min,
+            max: $max,
+            step: $step,
+            get: |params| params.$field as i32,
+            set: |params, value| params.$field = value as _,
+        }
+    };
+    ($name:literal, $field:ident / 100, $min:literal..=$max:literal, $step:literal) => {
+        Tunable {
+            name: $name,
+            min: $min,
+            max: $max,
+            step: $step,
+            get: |params| (params.$field * 100.0).round() as i32,
+            set: |params, value| params.$field = value as f64 / 100.0,
+        }
+    };
+}
+
+// Every selective search technique has its own knobs. They live in one
+// struct so they can be tuned without touching the search itself. With the
+// `tune` feature they are UCI options.
+search_params! {
+    // Null move pruning: R = base + depth / divisor
+    nmp_min_depth: i8 = 3, "NmpMinDepth", 1..=8, 0.5;
+    nmp_base_reduction: i8 = 3, "NmpBaseReduction", 1..=6, 0.5;
+    nmp_depth_divisor: i8 = 4, "NmpDepthDivisor", 1..=8, 0.5;
+
+    // Late move reductions: R = base + ln(depth) * ln(moves) / divisor
+    lmr_base: f64 = 0.75, "LmrBase" / 100, 0..=200, 10.0;
+    lmr_divisor: f64 = 2.25, "LmrDivisor" / 100, 100..=400, 15.0;
+    lmr_min_depth: i8 = 3, "LmrMinDepth", 1..=6, 0.5;
+    lmr_min_moves: usize = 3, "LmrMinMoves", 1..=10, 0.5;
+
+    // Reverse futility pruning: eval - margin * depth >= beta
+    rfp_max_depth: i8 = 6, "RfpMaxDepth", 1..=12, 0.5;
+    rfp_margin: i32 = 80, "RfpMargin", 20..=200, 8.0;
+
+    // Futility pruning of quiet moves: eval + base + margin * depth <= alpha
+    fp_max_depth: i8 = 6, "FpMaxDepth", 1..=12, 0.5;
+    fp_base: i32 = 100, "FpBase", 0..=300, 10.0;
+    fp_margin: i32 = 80, "FpMargin", 20..=200, 8.0;
+
+    // Razoring: eval + margin * depth < alpha drops into quiescence
+    razor_max_depth: i8 = 2, "RazorMaxDepth", 0..=6, 0.5;
+    razor_margin: i32 = 250, "RazorMargin", 50..=600, 25.0;
+
+    // Late move pruning: skip quiets after base + depth * depth of them
+    lmp_max_depth: i8 = 6, "LmpMaxDepth", 1..=12, 0.5;
+    lmp_base: usize = 3, "LmpBase", 0..=12, 0.5;
+
+    // Extend the search when the side to move is in check
+    check_extension: i8 = 1, "CheckExtension", 0..=1, 0.5;
+
+    // Aspiration windows: search +-window around the last score, and
+    // widen by half the window again on every fail
+    asp_min_depth: i8 = 4, "AspMinDepth", 1..=10, 0.5;
+    asp_window: i32 = 25, "AspWindow", 5..=100, 5.0;
+}
+
+impl SearchParams {
+    pub fn lmp_threshold(&self, depth: i8) -> usize {
+        self.lmp_base + (depth as usize) * (depth as usize)
+    }
+
+    pub fn futility_margin(&self, depth: i8) -> i32 {
+        self.fp_base + self.fp_margin * depth as i32
+    }
+}
+
+// A search parameter for tuning with SPSA: its UCI option name, its range
+// and the step size SPSA should end with.
+#[cfg(any(test, feature = "tune"))]
+pub struct Tunable {
+    pub name: &'static str,
+    pub min: i32,
+    pub max: i32,
+    pub step: f64,
+    get: fn(&SearchParams) -> i32,
+    set: fn(&mut SearchParams, i32),
+}
+
+#[cfg(any(test, feature = "tune"))]
+impl Tunable {
+    pub fn get(&self, params: &SearchParams) -> i32 {
+        (self.get)(params)
+    }
+
+    // Values out of range are clamped.
+    pub fn set(&self, params: &mut SearchParams, value: i32) {
+        (self.set)(params, value.clamp(self.min, self.max))
+    }
+
+    pub fn find(name: &str) -> Option<&'static Tunable> {
+        TUNABLES
+            .iter()
+            .find(|tunable| tunable.name.eq_ignore_ascii_case(name))
+    }
+}
+
+// The learning rate SPSA should end with, the same for all.
+#[cfg(any(test, feature = "tune"))]
+const SPSA_R_END: f64 = 0.002;
+
+// The parameters in the input format of the usual SPSA tools, one per
+// line: `name, int, default, min, max, c_end, r_end`.
+#[cfg(any(test, feature = "tune"))]
+pub fn spsa_input(params: &SearchParams) -> String {
+    TUNABLES
+        .iter()
+        .map(|tunable| {
+            format!(
+                "{}, int, {}, {}, {}, {}, {}\n",
+                tunable.name,
+                tunable.get(params),
+                tunable.min,
+                tunable.max,
+                tunable.step,
+                SPSA_R_END
+            )
+        })
+        .collect()
+}
+
+// Reductions indexed by [depth][number of moves searched], precomputed
+// from the parameters as the logarithms are expensive.
+pub struct LmrTable {
+    table: Vec<[i8; MAX_LEGAL_MOVES]>,
+}
+
+impl LmrTable {
+    pub fn new(params: &SearchParams) -> Self {
+        let mut table = vec![[0i8; MAX_LEGAL_MOVES]; MAX_PLY];
+        for (depth, row) in table.iter_mut().enumerate().skip(1) {
+            for (moves, reduction) in row.iter_mut().enumerate().skip(1) {
+                let r = params.lmr_base
+                    + (depth as f64).ln() * (moves as f64).ln() / params.lmr_divisor;
+                *reduction = r.max(0.0) as i8;
+            }
+        }
+        Self { table }
+    }
+
+    pub fn reduction(&self, depth: i8, moves: usize) -> i8 {
+        let depth = (depth.max(0) as usize).min(MAX_PLY - 1);
+        self.table[depth][moves.min(MAX_LEGAL_MOVES - 1)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lmr_table_grows() {
+        let lmr = LmrTable::new(&SearchParams::default());
+        assert_eq!(lmr.reduction(1, 1), 0);
+        assert!(lmr.reduction(3, 4) <= lmr.reduction(10, 4));
+        assert!(lmr.reduction(10, 4) <= lmr.reduction(10, 30));
+        assert!(lmr.reduction(20, 40) > 1);
+    }
+
+    #[test]
+    fn test_tunables() {
+        let defaults = SearchParams::default();
+        for tunable in TUNABLES {
+            let value = tunable.get(&defaults);
+            assert!(
+                (tunable.min..=tunable.max).contains(&value),
+                "{}",
+                tunable.name
+            );
+            let mut params = defaults;
+            tunable.set(&mut params, tunable.max);
+            assert_eq!(tunable.get(&params), tunable.max);
+            tunable.set(&mut params, value);
+            assert_eq!(tunable.get(&params), value);
+        }
+        let mut params = defaults;
+        Tunable::find("lmrbase").unwrap().set(&mut params, 90);
+        assert_eq!(params.lmr_base, 0.9);
+        Tunable::find("NmpDepthDivisor")
+            .unwrap()
+            .set(&mut params, 0);
+        assert_eq!(params.nmp_depth_divisor, 1);
+        assert!(Tunable::find("Hash").is_none());
+
+        let input = spsa_input(&defaults);
+        assert_eq!(input.lines().count(), TUNABLES.len());
+        assert!(input.contains("LmrBase, int, 75, 0, 200, 10, 0.002\n"));
+    }
+}
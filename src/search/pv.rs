@@ -0,0 +1,65 @@
+use super::MAX_PLY;
+use crate::board::moves::Move;
+
+// Triangular PV table: row `ply` holds the best line found from that ply
+// on. When a move raises alpha, it is put in front of the child's line,
+// so the root row always ends up with the full principal variation.
+pub struct PvTable {
+    moves: Vec<[Move; MAX_PLY]>,
+    length: [usize; MAX_PLY],
+}
+
+impl PvTable {
+    pub fn new() -> Self {
+        Self {
+            moves: vec![[Move::default(); MAX_PLY]; MAX_PLY],
+            length: [0; MAX_PLY],
+        }
+    }
+
+    // Called on entering a node: no line known yet.
+    pub fn clear(&mut self, ply: usize) {
+        self.length[ply] = ply;
+    }
+
+    pub fn update(&mut self, ply: usize, m: Move) {
+        let child_length = self.length[ply + 1].max(ply + 1);
+        let (parent, child) = self.moves.split_at_mut(ply + 1);
+        let row = &mut parent[ply];
+        row[ply] = m;
+        row[ply + 1..child_length].copy_from_slice(&child[0][ply + 1..child_length]);
+        self.length[ply] = child_length;
+    }
+
+    pub fn line(&self) -> &[Move] {
+        &self.moves[0][..self.length[0]]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::moves::MoveType;
+    use crate::board::pieces::Pieces;
+
+    #[test]
+    fn test_lines_are_collected_towards_the_root() {
+        let a = Move::new(Pieces::Pawn, 12, 28, MoveType::Regular);
+        let b = Move::new(Pieces::Pawn, 52, 36, MoveType::Regular);
+        let c = Move::new(Pieces::Knight, 6, 21, MoveType::Regular);
+        let mut pv = PvTable::new();
+        pv.clear(0);
+        pv.clear(1);
+        pv.clear(2);
+        pv.clear(3);
+        pv.update(2, c);
+        pv.update(1, b);
+        pv.update(0, a);
+        assert_eq!(pv.line(), &[a, b, c]);
+
+        // a new best move at the root whose child had no line
+        pv.clear(1);
+        pv.update(0, c);
+        assert_eq!(pv.line(), &[c]);
+    }
+}
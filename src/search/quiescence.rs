@@ -0,0 +1,63 @@
+use super::movepicker::MovePicker;
+use super::{Search, CHECKMATE, MAX_PLY};
+use crate::board::moves::Move;
+
+impl Search {
+    // Quiescence search: only captures (and promotions) are searched until
+    // the position is quiet, so the evaluation is never taken in the middle
+    // of an exchange. The side to move may always "stand pat" on the static
+    // evaluation, unless it is in check. On its first ply (`depth` 0) the
+    // quiet moves which give check are searched after the captures.
+    pub(super) fn quiescence(&mut self, mut alpha: i32, beta: i32, ply: usize, depth: i32) -> i32 {
+        self.pv.clear(ply);
+        if self.should_stop() {
+            return 0;
+        }
+        self.nodes += 1;
+        self.seldepth = self.seldepth.max(ply);
+        if ply >= MAX_PLY - 1 {
+            return self.eval.evaluate(&self.board);
+        }
+
+        let in_check = self.board.in_check(&self.mg);
+        let mut best_score = -CHECKMATE + ply as i32;
+        let mut picker = if in_check {
+            MovePicker::evasions(Move::default())
+        } else {
+            let stand_pat = self.eval.evaluate(&self.board);
+            if stand_pat >= beta {
+                return stand_pat;
+            }
+            alpha = alpha.max(stand_pat);
+            best_score = stand_pat;
+            MovePicker::qsearch(depth == 0)
+        };
+
+        while let Some(m) = picker.next(&self.board, &self.mg, &self.ordering, ply) {
+            // captures which lose material can't raise the score, nor can
+            // checks which leave the piece hanging
+            if !in_check && !self.board.see_ge(&self.mg, m, 0) {
+                continue;
+            }
+            if !self.board.make_legal(m, &self.mg) {
+                continue;
+            }
+            let score = -self.quiescence(-beta, -alpha, ply + 1, depth - 1);
+            self.board.unmake();
+            if self.stopped {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                if score > alpha {
+                    alpha = score;
+                    if score >= beta {
+                        break;
+                    }
+                }
+            }
+        }
+        best_score
+    }
+}
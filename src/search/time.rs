@@ -0,0 +1,183 @@
+use std::time::{Duration, Instant};
+
+use crate::board::moves::Move;
+use crate::defs::WHITE;
+
+pub const DEFAULT_MOVE_OVERHEAD: u64 = 10;
+// assumed number of moves left in the game without `movestogo`
+const MOVES_TO_GO: u64 = 30;
+const MAX_MOVES_TO_GO: u64 = 50;
+
+// How much longer than the soft limit to think with an unstable best move,
+// indexed by the number of iterations it has stayed the same.
+const STABILITY_SCALE: [f64; 5] = [2.0, 1.4, 1.1, 0.9, 0.8];
+// A score drop of this many centipawns doubles the soft limit.
+const SCORE_DROP_SCALE: f64 = 100.0;
+
+// The clock as sent with UCI `go`, all times in milliseconds.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TimeControl {
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    pub movestogo: Option<u64>,
+    pub movetime: Option<u64>,
+}
+
+impl TimeControl {
+    pub fn is_set(&self) -> bool {
+        self.movetime.is_some() || self.wtime.is_some() || self.btime.is_some()
+    }
+}
+
+// Decides how long to think about a move. The soft limit is checked after
+// every iteration: once it is reached, starting another one is not worth
+// it. It grows while the best move keeps changing or the score drops. The
+// hard limit aborts the search in the middle of an iteration.
+#[derive(Clone, Copy, Debug)]
+pub struct TimeManager {
+    start: Instant,
+    soft: Duration,
+    hard: Duration,
+    best_move: Move,
+    stability: usize,
+    score: Option<i32>,
+    scale: f64,
+    // `movetime`: use exactly the given time
+    fixed: bool,
+}
+
+impl TimeManager {
+    pub fn new(tc: &TimeControl, side: u8, move_overhead: u64) -> Self {
+        let (soft, hard) = Self::allocate(tc, side, move_overhead);
+        Self {
+            start: Instant::now(),
+            soft: Duration::from_millis(soft),
+            hard: Duration::from_millis(hard),
+            best_move: Move::default(),
+            stability: 0,
+            score: None,
+            scale: 1.0,
+            fixed: tc.movetime.is_some(),
+        }
+    }
+
+    // Soft and hard limit in milliseconds.
+    fn allocate(tc: &TimeControl, side: u8, move_overhead: u64) -> (u64, u64) {
+        if let Some(movetime) = tc.movetime {
+            let time = movetime.saturating_sub(move_overhead).max(1);
+            return (time, time);
+        }
+        let (time, inc) = if side == WHITE {
+            (tc.wtime, tc.winc)
+        } else {
+            (tc.btime, tc.binc)
+        };
+        let left = time.unwrap_or(0).saturating_sub(move_overhead).max(1);
+        let inc = inc.unwrap_or(0);
+        let moves_to_go = tc
+            .movestogo
+            .unwrap_or(MOVES_TO_GO)
+            .clamp(1, MAX_MOVES_TO_GO);
+
+        // never risk more than a fraction of what is left on the clock
+        let hard = (left / moves_to_go * 5 + inc).min(left * 3 / 4).max(1);
+        let soft = (left / moves_to_go + inc * 3 / 4).min(hard);
+        (soft, hard)
+    }
+
+    // Start the clock again, e.g. on `ponderhit`.
+    pub fn restart(&mut self) {
+        self.start = Instant::now();
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    // Called after every finished iteration with its result.
+    pub fn update(&mut self, best_move: Move, score: i32) {
+        if best_move == self.best_move {
+            self.stability = (self.stability + 1).min(STABILITY_SCALE.len() - 1);
+        } else {
+            self.best_move = best_move;
+            self.stability = 0;
+        }
+        let drop = self.score.map_or(0, |previous| previous - score);
+        self.score = Some(score);
+        let drop_scale = 1.0 + (drop.clamp(0, SCORE_DROP_SCALE as i32) as f64) / SCORE_DROP_SCALE;
+        self.scale = STABILITY_SCALE[self.stability] * drop_scale;
+    }
+
+    pub fn soft_limit_reached(&self) -> bool {
+        if self.fixed {
+            return self.hard_limit_reached();
+        }
+        self.elapsed() >= self.soft.mul_f64(self.scale).min(self.hard)
+    }
+
+    pub fn hard_limit_reached(&self) -> bool {
+        self.elapsed() >= self.hard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::moves::MoveType;
+    use crate::board::pieces::Pieces;
+    use crate::defs::BLACK;
+
+    #[test]
+    fn test_allocation() {
+        let movetime = TimeControl {
+            movetime: Some(1000),
+            ..Default::default()
+        };
+        assert_eq!(TimeManager::allocate(&movetime, WHITE, 10), (990, 990));
+
+        let sudden_death = TimeControl {
+            wtime: Some(60_000),
+            btime: Some(3_000),
+            winc: Some(1_000),
+            binc: Some(1_000),
+            ..Default::default()
+        };
+        let (soft, hard) = TimeManager::allocate(&sudden_death, WHITE, 0);
+        assert_eq!(soft, 60_000 / 30 + 750);
+        assert!(soft < hard && hard <= 45_000);
+        // little time left: the hard limit keeps a reserve
+        let (soft, hard) = TimeManager::allocate(&sudden_death, BLACK, 0);
+        assert!(soft <= hard && hard <= 2_250);
+
+        let last_move = TimeControl {
+            wtime: Some(10_000),
+            movestogo: Some(1),
+            ..Default::default()
+        };
+        let (soft, hard) = TimeManager::allocate(&last_move, WHITE, 100);
+        assert!(soft <= hard && hard < 9_900);
+    }
+
+    #[test]
+    fn test_unstable_best_move_gets_more_time() {
+        let tc = TimeControl {
+            wtime: Some(60_000),
+            ..Default::default()
+        };
+        let mut time = TimeManager::new(&tc, WHITE, 0);
+        let a = Move::new(Pieces::Pawn, 12, 28, MoveType::Regular);
+        let b = Move::new(Pieces::Pawn, 11, 27, MoveType::Regular);
+        time.update(a, 20);
+        let changed = time.scale;
+        time.update(a, 20);
+        time.update(a, 20);
+        assert!(time.scale < changed);
+        let stable = time.scale;
+        time.update(a, -40);
+        assert!(time.scale > stable);
+        time.update(b, -40);
+        assert_eq!(time.scale, STABILITY_SCALE[0]);
+    }
+}
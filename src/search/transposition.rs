@@ -0,0 +1,206 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use super::MATE_BOUND;
+use crate::board::moves::Move;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bound {
+    Exact,
+    // score is at least this (fail high)
+    Lower,
+    // score is at most this (fail low)
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TTEntry {
+    pub best_move: Move,
+    pub score: i16,
+    pub depth: i8,
+    pub bound: Bound,
+}
+
+impl TTEntry {
+    // Mate scores are stored relative to the node, not the root, so they
+    // stay correct when the position is reached at another ply.
+    pub fn score(&self, ply: usize) -> i32 {
+        let score = self.score as i32;
+        if score > MATE_BOUND {
+            score - ply as i32
+        } else if score < -MATE_BOUND {
+            score + ply as i32
+        } else {
+            score
+        }
+    }
+
+    // move (24 bits), score (16), depth (8), bound (2). The bound is never
+    // 0, so an empty slot is all zeros.
+    fn pack(&self) -> u64 {
+        let bound = match self.bound {
+            Bound::Exact => 1,
+            Bound::Lower => 2,
+            Bound::Upper => 3,
+        };
+        self.best_move.pack() as u64
+            | (self.score as u16 as u64) << 24
+            | (self.depth as u8 as u64) << 40
+            | bound << 48
+    }
+
+    fn unpack(data: u64) -> Self {
+        let bound = match data >> 48 {
+            1 => Bound::Exact,
+            2 => Bound::Lower,
+            _ => Bound::Upper,
+        };
+        Self {
+            best_move: Move::unpack((data & 0xff_ffff) as u32),
+            score: (data >> 24) as u16 as i16,
+            depth: (data >> 40) as u8 as i8,
+            bound,
+        }
+    }
+}
+
+// The key is stored xor-ed with the data. If two threads write the same
+// slot at once and the halves of their entries get mixed, the key check
+// fails and the torn entry is just a miss, so no locking is needed.
+#[derive(Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+// Shared by all search threads.
+pub struct TT {
+    slots: Vec<Slot>,
+}
+
+impl TT {
+    pub fn new(megabytes: usize) -> Self {
+        let count = (megabytes * 1024 * 1024 / std::mem::size_of::<Slot>()).max(1);
+        Self {
+            slots: (0..count).map(|_| Slot::default()).collect(),
+        }
+    }
+
+    pub fn clear(&self) {
+        for slot in &self.slots {
+            slot.key.store(0, Ordering::Relaxed);
+            slot.data.store(0, Ordering::Relaxed);
+        }
+    }
+
+    fn slot(&self, key: u64) -> &Slot {
+        // multiply-shift maps the key onto any table size without modulo
+        &self.slots[((key as u128 * self.slots.len() as u128) >> 64) as usize]
+    }
+
+    pub fn probe(&self, key: u64) -> Option<TTEntry> {
+        let slot = self.slot(key);
+        let data = slot.data.load(Ordering::Relaxed);
+        if data != 0 && slot.key.load(Ordering::Relaxed) ^ data == key {
+            Some(TTEntry::unpack(data))
+        } else {
+            None
+        }
+    }
+
+    pub fn store(
+        &self,
+        key: u64,
+        depth: i8,
+        score: i32,
+        bound: Bound,
+        best_move: Move,
+        ply: usize,
+    ) {
+        let slot = self.slot(key);
+        let old = self.probe(key);
+        // prefer deeper results of the same position, but always make room
+        // for new positions
+        if let Some(old) = old {
+            if depth < old.depth - 2 && bound != Bound::Exact {
+                return;
+            }
+        }
+        let score = if score > MATE_BOUND {
+            score + ply as i32
+        } else if score < -MATE_BOUND {
+            score - ply as i32
+        } else {
+            score
+        };
+        // keep the old move if this search didn't find one
+        let best_move = match old {
+            Some(old) if best_move.is_null() => old.best_move,
+            _ => best_move,
+        };
+        let data = TTEntry {
+            best_move,
+            score: score as i16,
+            depth,
+            bound,
+        }
+        .pack();
+        slot.key.store(key ^ data, Ordering::Relaxed);
+        slot.data.store(data, Ordering::Relaxed);
+    }
+
+    // Permille of the table in use, as reported by UCI `hashfull`.
+    pub fn hashfull(&self) -> usize {
+        let sample = self.slots.len().min(1000);
+        let used = self.slots[..sample]
+            .iter()
+            .filter(|slot| slot.data.load(Ordering::Relaxed) != 0)
+            .count();
+        used * 1000 / sample
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::CHECKMATE;
+
+    #[test]
+    fn test_store_and_probe() {
+        let tt = TT::new(1);
+        assert!(tt.probe(42).is_none());
+        tt.store(42, 5, 123, Bound::Lower, Move::default(), 0);
+        let entry = tt.probe(42).unwrap();
+        assert_eq!(entry.score(0), 123);
+        assert_eq!(entry.depth, 5);
+        assert_eq!(entry.bound, Bound::Lower);
+        assert!(tt.probe(43).is_none());
+        tt.clear();
+        assert!(tt.probe(42).is_none());
+    }
+
+    #[test]
+    fn test_mate_scores_are_ply_independent() {
+        let tt = TT::new(1);
+        // mate in 3 plies from the node, found at ply 4 (so 7 from the root)
+        tt.store(7, 3, CHECKMATE - 7, Bound::Exact, Move::default(), 4);
+        // reached again at ply 10: still mate 3 plies from the node
+        assert_eq!(tt.probe(7).unwrap().score(10), CHECKMATE - 13);
+    }
+
+    #[test]
+    fn test_entry_round_trip() {
+        use crate::board::moves::MoveType;
+        use crate::board::pieces::Pieces;
+        let tt = TT::new(1);
+        let m = Move::new(
+            Pieces::Pawn,
+            52,
+            61,
+            MoveType::PromotionCapture(Pieces::Queen, Pieces::Rook),
+        );
+        tt.store(99, -3, -1234, Bound::Upper, m, 0);
+        let entry = tt.probe(99).unwrap();
+        assert_eq!((entry.best_move, entry.score, entry.depth), (m, -1234, -3));
+        assert_eq!(entry.bound, Bound::Upper);
+    }
+}
@@ -0,0 +1,518 @@
+pub mod table;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use self::table::{Kind, Table, BLACK_PIECE, KING, PAWN, TB_PIECES};
+use crate::bitboard::Bitboard;
+use crate::board::movelist::MoveList;
+use crate::board::moves::Move;
+use crate::board::pieces::Pieces;
+use crate::board::Board;
+use crate::defs::*;
+use crate::move_generator::MoveGenerator;
+
+// Win/draw/loss of the side to move. A cursed win is a win which the
+// fifty move rule turns into a draw, a blessed loss a loss it saves.
+pub const LOSS: i32 = -2;
+pub const BLESSED_LOSS: i32 = -1;
+pub const DRAW: i32 = 0;
+pub const CURSED_WIN: i32 = 1;
+pub const WIN: i32 = 2;
+
+// Root moves are ranked by how soon they win or how long they hold out.
+const MAX_DTZ: i32 = 1 << 18;
+// a win as long as it is reached within this many plies
+const RULE_50: i32 = 100;
+
+// The WDL and DTZ file of one material, loaded when first probed.
+struct Entry {
+    wdl: PathBuf,
+    dtz: Option<PathBuf>,
+    wdl_table: OnceLock<Option<Table>>,
+    dtz_table: OnceLock<Option<Table>>,
+}
+
+// Syzygy tablebases: perfect information about all positions with few
+// pieces. The WDL tables tell whether a position is won, drawn or lost and
+// are probed during the search. The DTZ tables tell how many plies it is
+// until the next capture or pawn move when playing best, and are used at
+// the root to keep only the moves which make progress.
+//
+// The files are read into memory when a table is first needed, which
+// makes the first probe of a big table slow. Shared by all search
+// threads.
+pub struct Tablebases {
+    entries: HashMap<String, Entry>,
+    max_pieces: usize,
+}
+
+impl Tablebases {
+    // Finds the tables in the directories of `path`, separated as in the
+    // PATH variable of the platform. Directories which don't exist are
+    // skipped.
+    pub fn new(path: &str) -> Self {
+        let mut tb = Self {
+            entries: HashMap::new(),
+            max_pieces: 0,
+        };
+        let files = std::env::split_paths(path)
+            .filter_map(|dir| fs::read_dir(dir).ok())
+            .flatten()
+            .filter_map(|file| file.ok().map(|file| file.path()));
+        let mut dtz = HashMap::new();
+        for file in files {
+            let Some(name) = material_name(&file) else {
+                continue;
+            };
+            match file.extension().and_then(|ext| ext.to_str()) {
+                Some("rtbw") => {
+                    tb.max_pieces = tb.max_pieces.max(name.len() - 1);
+                    tb.entries.entry(name).or_insert(Entry {
+                        wdl: file,
+                        dtz: None,
+                        wdl_table: OnceLock::new(),
+                        dtz_table: OnceLock::new(),
+                    });
+                }
+                Some("rtbz") => {
+                    dtz.entry(name).or_insert(file);
+                }
+                _ => (),
+            }
+        }
+        for (name, file) in dtz {
+            if let Some(entry) = tb.entries.get_mut(&name) {
+                entry.dtz = Some(file);
+            }
+        }
+        tb
+    }
+
+    // The most pieces, kings included, of any table found.
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    // The table of the material on the board, and whether it has Black as
+    // the first part of the name.
+    fn entry(&self, board: &Board) -> Option<(&Entry, bool)> {
+        let white = side_name(board, WHITE);
+        let black = side_name(board, BLACK);
+        if let Some(entry) = self.entries.get(&format!("{}v{}", white, black)) {
+            return Some((entry, false));
+        }
+        self.entries
+            .get(&format!("{}v{}", black, white))
+            .map(|entry| (entry, true))
+    }
+
+    fn probe_wdl_table(&self, board: &Board) -> Option<i32> {
+        if board.occupancy().count_ones() == 2 {
+            return Some(DRAW);
+        }
+        let (entry, flip) = self.entry(board)?;
+        let table = entry
+            .wdl_table
+            .get_or_init(|| Table::load(&entry.wdl, &name_of(&entry.wdl), Kind::Wdl))
+            .as_ref()?;
+        Some(table.probe_wdl(&pieces(board), board.we(), flip))
+    }
+
+    // None if a table is missing, Some(None) if the table only has the
+    // other side to move.
+    fn probe_dtz_table(&self, board: &Board, wdl: i32) -> Option<Option<i32>> {
+        let (entry, flip) = self.entry(board)?;
+        let path = entry.dtz.as_ref()?;
+        let table = entry
+            .dtz_table
+            .get_or_init(|| Table::load(path, &name_of(path), Kind::Dtz))
+            .as_ref()?;
+        Some(table.probe_dtz(&pieces(board), board.we(), flip, wdl))
+    }
+
+    // The tables leave out positions where a capture is at least as good
+    // as the value stored, which helps the compression, and they know
+    // nothing of en passant. So the captures have to be tried first: the
+    // best of them and the stored value is the real value. With `zeroing`
+    // pawn moves are tried as well, as the DTZ tables leave out positions
+    // where one of those wins. The second value is set when the best move
+    // is one of the moves tried, and the stored value is not to be used.
+    fn search(&self, board: &mut Board, mg: &MoveGenerator, zeroing: bool) -> Option<(i32, bool)> {
+        let mut moves = MoveList::new();
+        board.get_legal_moves(mg, &mut moves);
+        let mut best = LOSS;
+        let mut tried = 0;
+        for &m in moves.iter() {
+            if !m.is_capture() && (!zeroing || m.piece() != Pieces::Pawn) {
+                continue;
+            }
+            tried += 1;
+            board.make(m);
+            let result = self.search(board, mg, false);
+            board.unmake();
+            let value = -result?.0;
+            if value > best {
+                best = value;
+                if value >= WIN {
+                    return Some((value, true));
+                }
+            }
+        }
+
+        // With all moves tried, the stored value could be wrong, e.g. when
+        // the only moves are en passant captures.
+        let all_tried = tried > 0 && tried == moves.len();
+        let value = if all_tried {
+            best
+        } else {
+            self.probe_wdl_table(board)?
+        };
+        if best >= value {
+            Some((best, best > DRAW || all_tried))
+        } else {
+            Some((value, false))
+        }
+    }
+
+    // The WDL value of the position for the side to move. Not to be used
+    // with castling rights. None if a table is missing.
+    pub fn probe_wdl(&self, board: &mut Board, mg: &MoveGenerator) -> Option<i32> {
+        self.search(board, mg, false).map(|(wdl, _)| wdl)
+    }
+
+    // Plies to the next capture or pawn move, positive when winning and
+    // negative when losing, 0 for a draw. Wins and losses which depend on
+    // the fifty move rule are 100 further away.
+    pub fn probe_dtz(&self, board: &mut Board, mg: &MoveGenerator) -> Option<i32> {
+        let (wdl, zeroing_best) = self.search(board, mg, true)?;
+        if wdl == DRAW {
+            return Some(0);
+        }
+        if zeroing_best {
+            return Some(dtz_before_zeroing(wdl));
+        }
+        if let Some(dtz) = self.probe_dtz_table(board, wdl)? {
+            let cursed = (wdl == CURSED_WIN || wdl == BLESSED_LOSS) as i32;
+            return Some((dtz + 100 * cursed) * wdl.signum());
+        }
+
+        // The table is for the other side to move: the best move is the
+        // one with the best DTZ after it.
+        let mut moves = MoveList::new();
+        board.get_legal_moves(mg, &mut moves);
+        let mut min_dtz = i32::MAX;
+        for &m in moves.iter() {
+            let zeroing = m.is_capture() || m.piece() == Pieces::Pawn;
+            board.make(m);
+            // for a zeroing move, the DTZ before making it
+            let dtz = if zeroing {
+                self.probe_wdl(board, mg)
+                    .map(|wdl| -dtz_before_zeroing(wdl))
+            } else {
+                self.probe_dtz(board, mg).map(|dtz| -dtz)
+            };
+            let mates = dtz == Some(1) && is_mate(board, mg);
+            board.unmake();
+            let mut dtz = dtz?;
+            if mates {
+                min_dtz = 1;
+            }
+            if !zeroing {
+                dtz += dtz.signum();
+            }
+            if dtz < min_dtz && dtz.signum() == wdl.signum() {
+                min_dtz = dtz;
+            }
+        }
+        // no legal moves: mated
+        Some(if min_dtz == i32::MAX { -1 } else { min_dtz })
+    }
+
+    // Ranks the root moves with the DTZ tables, or only the WDL tables if
+    // there are no DTZ tables. Wins which the fifty move rule would spoil
+    // rank below the others, and so do losses which it might save. Returns
+    // the moves of the best rank and the score of that rank, and whether
+    // DTZ tables were used, or None if a table is missing.
+    pub fn rank_root_moves(
+        &self,
+        board: &mut Board,
+        mg: &MoveGenerator,
+        moves: &[Move],
+    ) -> Option<(Vec<Move>, i32, bool)> {
+        let (ranks, dtz) = match self.rank_by_dtz(board, mg, moves) {
+            Some(ranks) => (ranks, true),
+            None => (self.rank_by_wdl(board, mg, moves)?, false),
+        };
+        let best = *ranks.iter().max()?;
+        let best_moves = moves
+            .iter()
+            .zip(&ranks)
+            .filter(|&(_, &rank)| rank == best)
+            .map(|(&m, _)| m)
+            .collect();
+        Some((best_moves, rank_score(best), dtz))
+    }
+
+    fn rank_by_dtz(
+        &self,
+        board: &mut Board,
+        mg: &MoveGenerator,
+        moves: &[Move],
+    ) -> Option<Vec<i32>> {
+        let clock = board.game_state.halfmove_clock as i32;
+        let repeated = board.is_repetition();
+        let mut ranks = Vec::with_capacity(moves.len());
+        for &m in moves {
+            board.make(m);
+            let dtz = if board.game_state.halfmove_clock == 0 {
+                self.probe_wdl(board, mg)
+                    .map(|wdl| dtz_before_zeroing(-wdl))
+            } else if board.is_repetition() || board.draw_by_fifty_moves() {
+                Some(0)
+            } else {
+                self.probe_dtz(board, mg).map(|dtz| -dtz - dtz.signum())
+            };
+            let mates = dtz == Some(2) && is_mate(board, mg);
+            board.unmake();
+            let dtz = if mates { 1 } else { dtz? };
+
+            let rank = if dtz > 0 {
+                if dtz + clock < RULE_50 && !repeated {
+                    MAX_DTZ
+                } else {
+                    MAX_DTZ - (dtz + clock)
+                }
+            } else if dtz < 0 {
+                if -dtz * 2 + clock < RULE_50 {
+                    -MAX_DTZ
+                } else {
+                    -MAX_DTZ + (-dtz + clock)
+                }
+            } else {
+                0
+            };
+            ranks.push(rank);
+        }
+        Some(ranks)
+    }
+
+    fn rank_by_wdl(
+        &self,
+        board: &mut Board,
+        mg: &MoveGenerator,
+        moves: &[Move],
+    ) -> Option<Vec<i32>> {
+        const WDL_RANK: [i32; 5] = [-MAX_DTZ, -MAX_DTZ + 101, 0, MAX_DTZ - 101, MAX_DTZ];
+        let mut ranks = Vec::with_capacity(moves.len());
+        for &m in moves {
+            board.make(m);
+            let wdl = if board.is_repetition() || board.draw_by_fifty_moves() {
+                Some(DRAW)
+            } else {
+                self.probe_wdl(board, mg).map(|wdl| -wdl)
+            };
+            board.unmake();
+            ranks.push(WDL_RANK[(wdl? + 2) as usize]);
+        }
+        Some(ranks)
+    }
+}
+
+// A sure win is a bit less than a mate, a win or loss the fifty move rule
+// may spoil gets some centipawns, more the closer it is to a real one.
+fn rank_score(rank: i32) -> i32 {
+    const TB_WIN: i32 = crate::search::TB_WIN;
+    const PAWN: i32 = 100;
+    const SURE: i32 = MAX_DTZ - RULE_50;
+    if rank >= SURE {
+        TB_WIN
+    } else if rank > 0 {
+        (rank - (MAX_DTZ - 200)).max(3) * PAWN / 200
+    } else if rank == 0 {
+        0
+    } else if rank > -SURE {
+        (rank + (MAX_DTZ - 200)).min(-3) * PAWN / 200
+    } else {
+        -TB_WIN
+    }
+}
+
+// After a capture or pawn move the count starts again: the DTZ of the
+// move itself.
+fn dtz_before_zeroing(wdl: i32) -> i32 {
+    match wdl {
+        WIN => 1,
+        CURSED_WIN => 101,
+        BLESSED_LOSS => -101,
+        LOSS => -1,
+        _ => 0,
+    }
+}
+
+fn is_mate(board: &mut Board, mg: &MoveGenerator) -> bool {
+    let mut moves = MoveList::new();
+    board.get_legal_moves(mg, &mut moves);
+    moves.is_empty() && board.in_check(mg)
+}
+
+// The pieces of a side as in the names of the files, e.g. `KRP`.
+fn side_name(board: &Board, side: u8) -> String {
+    let mut name = String::from("K");
+    for (piece, letter) in [
+        (Pieces::Queen, 'Q'),
+        (Pieces::Rook, 'R'),
+        (Pieces::Bishop, 'B'),
+        (Pieces::Knight, 'N'),
+        (Pieces::Pawn, 'P'),
+    ] {
+        let count = board.get_pieces(side, piece).count_ones();
+        (0..count).for_each(|_| name.push(letter));
+    }
+    name
+}
+
+// `KRPvKR` from `/tb/KRPvKR.rtbw`, if it is the name of a table.
+fn material_name(file: &Path) -> Option<String> {
+    let name = name_of(file);
+    let (white, black) = name.split_once('v')?;
+    let valid =
+        |side: &str| side.starts_with('K') && side[1..].chars().all(|c| "QRBNP".contains(c));
+    let pieces = white.len() + black.len();
+    (valid(white) && valid(black) && pieces <= TB_PIECES).then_some(name)
+}
+
+fn name_of(file: &Path) -> String {
+    file.file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+// All pieces on the board as (piece, square) with the pieces coded as in
+// the files.
+fn pieces(board: &Board) -> Vec<(u8, Square)> {
+    let code = |piece: Pieces| match piece {
+        Pieces::Pawn => PAWN,
+        Pieces::Knight => 2,
+        Pieces::Bishop => 3,
+        Pieces::Rook => 4,
+        Pieces::Queen => 5,
+        _ => KING,
+    };
+    let mut pieces = Vec::with_capacity(TB_PIECES);
+    for side in [WHITE, BLACK] {
+        let color = if side == WHITE { 0 } else { BLACK_PIECE };
+        for square in Bitboard::new(board.get_side(side)) {
+            pieces.push((code(board.piece_list[square as usize]) | color, square));
+        }
+    }
+    pieces.sort_unstable_by_key(|&(_, square)| square);
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `test-data/syzygy` has KQvK, KRvK and KPvK, written from our distance
+    // to mate tables by `table::write`. For the 4-man tests, copy the
+    // official 4-man files there and run `cargo test -- --ignored`.
+    fn tablebases(pieces: usize) -> Tablebases {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test-data/syzygy");
+        let tb = Tablebases::new(dir.to_str().unwrap());
+        assert!(
+            tb.max_pieces() >= pieces,
+            "no tablebases in {}",
+            dir.display()
+        );
+        tb
+    }
+
+    fn board(fen: &str) -> Board {
+        Board::fen(Some(fen.to_string()))
+    }
+
+    #[test]
+    fn test_names() {
+        let krp = board("8/8/4k3/8/2R5/8/1P6/4K3 w - - 0 1");
+        assert_eq!(side_name(&krp, WHITE), "KRP");
+        assert_eq!(side_name(&krp, BLACK), "K");
+        let name = |file: &str| material_name(Path::new(file));
+        assert_eq!(name("/tb/KRPvKR.rtbw"), Some(String::from("KRPvKR")));
+        assert_eq!(name("/tb/KRPvKR.rtbz"), Some(String::from("KRPvKR")));
+        assert_eq!(name("/tb/README.txt"), None);
+        assert_eq!(name("/tb/KQRBNPvKQ.rtbw"), None);
+
+        let tb = Tablebases::new("/no/such/directory");
+        assert_eq!(tb.max_pieces(), 0);
+        let mg = MoveGenerator::new();
+        let mut krk = board("8/8/4k3/8/8/8/8/R3K3 w - - 0 1");
+        assert_eq!(tb.probe_wdl(&mut krk, &mg), None);
+        // two bare kings need no table
+        let mut kings = board("8/8/4k3/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(tb.probe_wdl(&mut kings, &mg), Some(DRAW));
+    }
+
+    #[test]
+    fn test_wdl() {
+        let tb = tablebases(3);
+        let mg = MoveGenerator::new();
+        let wdl = |fen: &str| tb.probe_wdl(&mut board(fen), &mg);
+        assert_eq!(wdl("8/8/4k3/8/8/8/8/R3K3 w - - 0 1"), Some(WIN));
+        assert_eq!(wdl("8/8/4k3/8/8/8/8/R3K3 b - - 0 1"), Some(LOSS));
+        // the rook is lost at once
+        assert_eq!(wdl("8/8/8/8/8/8/1k6/R3K3 b - - 0 1"), Some(DRAW));
+        assert_eq!(wdl("8/4k3/8/4K3/4P3/8/8/8 w - - 0 1"), Some(DRAW));
+        assert_eq!(wdl("8/4k3/8/4K3/4P3/8/8/8 b - - 0 1"), Some(LOSS));
+        // the same with the colours reversed
+        assert_eq!(wdl("8/8/8/4p3/4k3/8/4K3/8 w - - 0 1"), Some(LOSS));
+        assert_eq!(wdl("8/8/4k3/8/8/8/8/Q3K3 b - - 0 1"), Some(LOSS));
+        // stalemate
+        assert_eq!(wdl("k7/2Q5/1K6/8/8/8/8/8 b - - 0 1"), Some(DRAW));
+    }
+
+    #[test]
+    #[ignore = "needs the 4-man tables in test-data/syzygy"]
+    fn test_wdl_four_pieces() {
+        let tb = tablebases(4);
+        let mg = MoveGenerator::new();
+        let wdl = |fen: &str| tb.probe_wdl(&mut board(fen), &mg);
+        // KRvKB is a draw, KQvKR a win
+        assert_eq!(wdl("8/8/4k3/4b3/8/8/8/R3K3 w - - 0 1"), Some(DRAW));
+        assert_eq!(wdl("8/8/4k3/4r3/8/8/8/Q3K3 w - - 0 1"), Some(WIN));
+    }
+
+    #[test]
+    fn test_dtz_and_root() {
+        let tb = tablebases(3);
+        let mg = MoveGenerator::new();
+        // mate in one with the rook
+        let mut mate = board("4k3/8/4K3/8/8/8/8/R7 w - - 0 1");
+        assert_eq!(tb.probe_dtz(&mut mate, &mg), Some(1));
+        let mut moves = MoveList::new();
+        mate.get_legal_moves(&mg, &mut moves);
+        let (best, score, dtz) = tb
+            .rank_root_moves(&mut mate, &mg, moves.as_slice())
+            .unwrap();
+        assert!(dtz);
+        assert_eq!(score, crate::search::TB_WIN);
+        assert!(best.iter().any(|m| m.to_uci() == "a1a8"));
+
+        // losing the rook is never among the best moves
+        let mut krk = board("8/8/8/8/8/8/1k6/R3K3 w - - 0 1");
+        assert!(tb.probe_dtz(&mut krk, &mg).unwrap() > 0);
+        krk.get_legal_moves(&mg, &mut moves);
+        let (best, _, _) = tb.rank_root_moves(&mut krk, &mg, moves.as_slice()).unwrap();
+        assert!(best.iter().all(|m| m.to_uci() != "a1b1"));
+
+        // mate in one with the queen, and with the other side to move the
+        // only move leads to it
+        let mut kqk = board("k7/8/1K6/8/8/8/8/6Q1 w - - 0 1");
+        assert_eq!(tb.probe_dtz(&mut kqk, &mg), Some(1));
+        let mut kqk = board("k7/8/1K6/8/8/8/8/6Q1 b - - 0 1");
+        assert_eq!(tb.probe_dtz(&mut kqk, &mg), Some(-2));
+    }
+}
@@ -0,0 +1,908 @@
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::defs::*;
+
+#[cfg(test)]
+mod write;
+
+// The file format of the Syzygy tablebases, as generated by Ronald de
+// Man's tools: how a position is turned into an index, and how the value
+// at that index is decompressed. What the values mean is up to the caller.
+
+pub const TB_PIECES: usize = 7;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+// flags of each part of a table
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+// flags of the whole file
+const FILE_SPLIT: u8 = 1;
+const FILE_HAS_PAWNS: u8 = 2;
+
+// Pieces are coded as in the files: 1 to 6 for the white pawn, knight,
+// bishop, rook, queen and king, the black ones 8 higher.
+pub const PAWN: u8 = 1;
+pub const KING: u8 = 6;
+pub const BLACK_PIECE: u8 = 8;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Kind {
+    Wdl,
+    Dtz,
+}
+
+// Tables of the index encoding, the same for all files.
+struct Encoding {
+    // a2-h7 to 0..47, higher for the squares nearer to the edge and to
+    // the 2nd rank: the leading pawn is the one with the highest
+    map_pawns: [usize; 64],
+    // the 28 squares below the a1-h8 diagonal
+    map_b1h1h7: [usize; 64],
+    // the 10 squares of the a1-d1-d4 triangle, the diagonal last
+    map_a1d1d4: [usize; 64],
+    // the 462 legal places of two kings with the first in the triangle
+    map_kk: [[usize; 64]; 10],
+    // binomial[k][n]: the ways to choose k of n
+    binomial: [[u64; 64]; 6],
+    lead_pawn_idx: [[u64; 64]; 6],
+    lead_pawns_size: [[u64; 4]; 6],
+}
+
+static ENCODING: OnceLock<Encoding> = OnceLock::new();
+
+// the corner of the a1-d1-d4 triangle
+const D4: usize = 27;
+
+fn off_a1h8(square: usize) -> i32 {
+    (square / 8) as i32 - (square % 8) as i32
+}
+
+fn king_attacks(square: usize) -> u64 {
+    let (rank, file) = ((square / 8) as i32, (square % 8) as i32);
+    let mut attacks = 0;
+    for (dr, df) in [
+        (-1, -1),
+        (-1, 0),
+        (-1, 1),
+        (0, -1),
+        (0, 1),
+        (1, -1),
+        (1, 0),
+        (1, 1),
+    ] {
+        let (r, f) = (rank + dr, file + df);
+        if (0..8).contains(&r) && (0..8).contains(&f) {
+            attacks |= 1u64 << (r * 8 + f);
+        }
+    }
+    attacks
+}
+
+impl Encoding {
+    fn new() -> Self {
+        let mut e = Self {
+            map_pawns: [0; 64],
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            binomial: [[0; 64]; 6],
+            lead_pawn_idx: [[0; 64]; 6],
+            lead_pawns_size: [[0; 4]; 6],
+        };
+
+        let mut code = 0;
+        for square in 0..64 {
+            if off_a1h8(square) < 0 {
+                e.map_b1h1h7[square] = code;
+                code += 1;
+            }
+        }
+
+        let mut diagonal = Vec::new();
+        code = 0;
+        for square in 0..=D4 {
+            if off_a1h8(square) < 0 && square % 8 <= 3 {
+                e.map_a1d1d4[square] = code;
+                code += 1;
+            } else if off_a1h8(square) == 0 && square % 8 <= 3 {
+                diagonal.push(square);
+            }
+        }
+        for square in diagonal {
+            e.map_a1d1d4[square] = code;
+            code += 1;
+        }
+
+        // With the first king on the diagonal, the second may not be above
+        // it. Both kings on the diagonal come last.
+        let mut both_on_diagonal = Vec::new();
+        code = 0;
+        for idx in 0..10 {
+            for s1 in 0..=D4 {
+                if e.map_a1d1d4[s1] != idx || (idx == 0 && s1 != Squares::B1 as usize) {
+                    continue;
+                }
+                for s2 in 0..64 {
+                    if (king_attacks(s1) | 1u64 << s1) & 1u64 << s2 != 0 {
+                        continue;
+                    }
+                    if off_a1h8(s1) == 0 && off_a1h8(s2) > 0 {
+                        continue;
+                    }
+                    if off_a1h8(s1) == 0 && off_a1h8(s2) == 0 {
+                        both_on_diagonal.push((idx, s2));
+                    } else {
+                        e.map_kk[idx][s2] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (idx, square) in both_on_diagonal {
+            e.map_kk[idx][square] = code;
+            code += 1;
+        }
+
+        e.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..6.min(n + 1) {
+                e.binomial[k][n] = if k > 0 { e.binomial[k - 1][n - 1] } else { 0 }
+                    + if k < n { e.binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        // The pawn tables are split by the file of the leading pawn, so
+        // the index starts again for every file.
+        let mut available = 48;
+        for lead_pawns in 1..=5 {
+            for file in 0..4 {
+                let mut idx = 0;
+                for rank in 1..7 {
+                    let square = rank * 8 + file;
+                    if lead_pawns == 1 {
+                        available -= 2;
+                        e.map_pawns[square] = available + 1;
+                        e.map_pawns[square ^ 7] = available;
+                    }
+                    e.lead_pawn_idx[lead_pawns][square] = idx;
+                    idx += e.binomial[lead_pawns - 1][e.map_pawns[square]];
+                }
+                e.lead_pawns_size[lead_pawns][file] = idx;
+            }
+        }
+        e
+    }
+}
+
+fn encoding() -> &'static Encoding {
+    ENCODING.get_or_init(Encoding::new)
+}
+
+// One of the up to 8 parts of a table, by side to move and the file of
+// the leading pawn. The values are compressed in blocks by recursive
+// pairing followed by a canonical Huffman code. The positions of the
+// tables within the file are kept as offsets.
+#[derive(Clone, Default)]
+struct PairsData {
+    flags: u8,
+    min_sym_len: u8,
+    num_blocks: usize,
+    block_size: usize,
+    // about every span values there is an entry in the sparse index
+    span: u64,
+    sparse_index: usize,
+    sparse_index_size: usize,
+    block_length: usize,
+    block_length_size: usize,
+    lowest_sym: usize,
+    btree: usize,
+    data: usize,
+    base64: Vec<u64>,
+    // how many values (-1) each symbol stands for
+    symlen: Vec<u8>,
+    // the order of the pieces, which defines their groups
+    pieces: [u8; TB_PIECES],
+    group_idx: [u64; TB_PIECES + 1],
+    group_len: [usize; TB_PIECES + 1],
+    // DTZ: where the value maps of a win, loss, cursed win and blessed
+    // loss start
+    map_idx: [usize; 4],
+}
+
+// A WDL or DTZ file, read into memory.
+pub struct Table {
+    kind: Kind,
+    data: Vec<u8>,
+    piece_count: usize,
+    has_pawns: bool,
+    has_unique_pieces: bool,
+    // both sides have the same pieces
+    symmetric: bool,
+    // of the leading side and the other one
+    pawn_count: [usize; 2],
+    // by side to move, then file
+    items: Vec<PairsData>,
+    // DTZ: the value maps
+    map: usize,
+}
+
+impl Table {
+    // `name` is the material as in the file name, e.g. `KRPvKR`, which is
+    // also how the pieces are arranged in the table: the first part is
+    // White's.
+    pub fn load(path: &Path, name: &str, kind: Kind) -> Option<Self> {
+        let data = fs::read(path).ok()?;
+        let mut table = Self::new(data, name, kind)?;
+        table.init().then_some(table)
+    }
+
+    // The table before its header is read.
+    fn new(data: Vec<u8>, name: &str, kind: Kind) -> Option<Self> {
+        let magic = if kind == Kind::Wdl {
+            WDL_MAGIC
+        } else {
+            DTZ_MAGIC
+        };
+        if data.len() < 5 || data[..4] != magic {
+            return None;
+        }
+
+        let (white, black) = name.split_once('v')?;
+        let count = |side: &str, piece: char| side.chars().filter(|&c| c == piece).count();
+        let has_pawns = name.contains('P');
+        let has_unique_pieces = [white, black]
+            .iter()
+            .any(|side| "QRBNP".chars().any(|piece| count(side, piece) == 1));
+        // The leading side is the one with less pawns, if both have them.
+        let (white_pawns, black_pawns) = (count(white, 'P'), count(black, 'P'));
+        let white_leads = black_pawns == 0 || (white_pawns > 0 && black_pawns >= white_pawns);
+        let pawn_count = if white_leads {
+            [white_pawns, black_pawns]
+        } else {
+            [black_pawns, white_pawns]
+        };
+
+        let sides = if kind == Kind::Wdl { 2 } else { 1 };
+        let files = if has_pawns { 4 } else { 1 };
+        Some(Self {
+            kind,
+            data,
+            piece_count: white.len() + black.len(),
+            has_pawns,
+            has_unique_pieces,
+            symmetric: white == black,
+            pawn_count,
+            items: vec![PairsData::default(); sides * files],
+            map: 0,
+        })
+    }
+
+    fn files(&self) -> usize {
+        if self.has_pawns {
+            4
+        } else {
+            1
+        }
+    }
+
+    // The sides to move stored in the file.
+    fn sides(&self) -> usize {
+        if self.kind == Kind::Wdl && !self.symmetric {
+            2
+        } else {
+            1
+        }
+    }
+
+    fn item(&self, stm: usize, file: usize) -> &PairsData {
+        let sides = self.items.len() / self.files();
+        &self.items[(stm % sides) * self.files() + if self.has_pawns { file } else { 0 }]
+    }
+
+    fn item_mut(&mut self, stm: usize, file: usize) -> &mut PairsData {
+        let files = self.files();
+        &mut self.items[stm * files + file]
+    }
+
+    fn u16_at(&self, pos: usize) -> u16 {
+        u16::from_le_bytes([self.data[pos], self.data[pos + 1]])
+    }
+
+    fn u32_at(&self, pos: usize) -> u32 {
+        u32::from_le_bytes(self.data[pos..pos + 4].try_into().unwrap())
+    }
+
+    // Big endian, as the compressed data is read as a bit stream. Past
+    // the end of the file reads zeros.
+    fn be_u32_at(&self, pos: usize) -> u32 {
+        let mut bytes = [0; 4];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = self.data.get(pos + i).copied().unwrap_or(0);
+        }
+        u32::from_be_bytes(bytes)
+    }
+
+    // Reads the header and finds where the parts of the table are. False
+    // if the file doesn't fit its name.
+    fn init(&mut self) -> bool {
+        let Some(mut pos) = self.init_pieces() else {
+            return false;
+        };
+        let files = self.files();
+        let sides = self.sides();
+
+        for file in 0..files {
+            for i in 0..sides {
+                match self.set_sizes(i, file, pos) {
+                    Some(next) => pos = next,
+                    None => return false,
+                }
+            }
+        }
+        if self.kind == Kind::Dtz {
+            match self.set_dtz_map(pos) {
+                Some(next) => pos = next,
+                None => return false,
+            }
+        }
+        for file in 0..files {
+            for i in 0..sides {
+                let d = self.item_mut(i, file);
+                d.sparse_index = pos;
+                pos += d.sparse_index_size * 6;
+            }
+        }
+        for file in 0..files {
+            for i in 0..sides {
+                let d = self.item_mut(i, file);
+                d.block_length = pos;
+                pos += d.block_length_size * 2;
+            }
+        }
+        for file in 0..files {
+            for i in 0..sides {
+                pos = (pos + 0x3f) & !0x3f;
+                let d = self.item_mut(i, file);
+                d.data = pos;
+                pos += d.num_blocks * d.block_size;
+            }
+        }
+        pos <= self.data.len()
+    }
+
+    // The pieces of the table and their groups, up to the sizes of the
+    // parts. Returns where those start.
+    fn init_pieces(&mut self) -> Option<usize> {
+        let mut pos = 4;
+        let flags = self.data[pos];
+        if (flags & FILE_HAS_PAWNS != 0) != self.has_pawns
+            || (flags & FILE_SPLIT != 0) == self.symmetric
+        {
+            return None;
+        }
+        pos += 1;
+
+        let sides = self.sides();
+        let files = self.files();
+        let pp = self.has_pawns && self.pawn_count[1] > 0;
+        let header = files * (1 + pp as usize + self.piece_count);
+        if self.data.len() < pos + header {
+            return None;
+        }
+
+        for file in 0..files {
+            let second = if pp { self.data[pos + 1] } else { 0xff };
+            let order = [
+                [self.data[pos] & 0xf, second & 0xf],
+                [self.data[pos] >> 4, second >> 4],
+            ];
+            pos += 1 + pp as usize;
+            for k in 0..self.piece_count {
+                let byte = self.data[pos];
+                for i in 0..sides {
+                    self.item_mut(i, file).pieces[k] = if i == 1 { byte >> 4 } else { byte & 0xf };
+                }
+                pos += 1;
+            }
+            for (i, order) in order.iter().enumerate().take(sides) {
+                self.set_groups(i, file, order);
+            }
+        }
+        Some(pos + (pos & 1))
+    }
+
+    // The pieces are encoded in groups: the leading pieces or pawns, then
+    // the other pawns, then each kind of piece. The order of the groups
+    // within the index is given by the file.
+    fn set_groups(&mut self, stm: usize, file: usize, order: &[u8; 2]) {
+        let e = encoding();
+        let (has_pawns, has_unique_pieces) = (self.has_pawns, self.has_unique_pieces);
+        let pp = has_pawns && self.pawn_count[1] > 0;
+        let piece_count = self.piece_count;
+        let d = self.item_mut(stm, file);
+
+        let mut first_len: i32 = if has_pawns {
+            0
+        } else if has_unique_pieces {
+            3
+        } else {
+            2
+        };
+        let mut n = 0;
+        d.group_len[n] = 1;
+        for i in 1..piece_count {
+            first_len -= 1;
+            if first_len > 0 || d.pieces[i] == d.pieces[i - 1] {
+                d.group_len[n] += 1;
+            } else {
+                n += 1;
+                d.group_len[n] = 1;
+            }
+        }
+        n += 1;
+        d.group_len[n] = 0;
+
+        let mut next = if pp { 2 } else { 1 };
+        let mut free_squares = 64 - d.group_len[0] - if pp { d.group_len[1] } else { 0 };
+        let mut idx = 1u64;
+        let mut k = 0;
+        while next < n || k == order[0] as usize || k == order[1] as usize {
+            if k == order[0] as usize {
+                d.group_idx[0] = idx;
+                idx *= if has_pawns {
+                    e.lead_pawns_size[d.group_len[0]][file]
+                } else if has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] as usize {
+                d.group_idx[1] = idx;
+                idx *= e.binomial[d.group_len[1]][48 - d.group_len[0]];
+            } else {
+                d.group_idx[next] = idx;
+                idx *= e.binomial[d.group_len[next]][free_squares];
+                free_squares -= d.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+        d.group_idx[n] = idx;
+    }
+
+    // Reads the sizes of the tables of one part and its Huffman code.
+    fn set_sizes(&mut self, stm: usize, file: usize, mut pos: usize) -> Option<usize> {
+        let flags = *self.data.get(pos)?;
+        pos += 1;
+        if flags & FLAG_SINGLE_VALUE != 0 {
+            let value = *self.data.get(pos)?;
+            let d = self.item_mut(stm, file);
+            d.flags = flags;
+            d.min_sym_len = value;
+            return Some(pos + 1);
+        }
+        if self.data.len() < pos + 10 {
+            return None;
+        }
+
+        let d = self.item(stm, file);
+        let tb_size = d.group_idx[d.group_len.iter().position(|&len| len == 0)?];
+        let block_size = 1usize << self.data[pos];
+        let span = 1u64 << self.data[pos + 1];
+        let padding = self.data[pos + 2] as usize;
+        let num_blocks = self.u32_at(pos + 3) as usize;
+        let max_sym_len = self.data[pos + 7];
+        let min_sym_len = self.data[pos + 8];
+        pos += 9;
+        if max_sym_len < min_sym_len || min_sym_len == 0 {
+            return None;
+        }
+        let lowest_sym = pos;
+
+        // The canonical code has the longer symbols at the lower values, so
+        // base64[i], the lowest symbol of length min + i padded to 64 bits,
+        // goes down with i. The length of the symbol at the start of a
+        // bit stream is then the first i with the stream >= base64[i].
+        let lengths = (max_sym_len - min_sym_len + 1) as usize;
+        if self.data.len() < pos + lengths * 2 + 2 {
+            return None;
+        }
+        let lowest = |i: usize| self.u16_at(lowest_sym + 2 * i) as u64;
+        let mut base64 = vec![0u64; lengths];
+        for i in (0..lengths - 1).rev() {
+            base64[i] = base64[i + 1]
+                .wrapping_add(lowest(i))
+                .wrapping_sub(lowest(i + 1))
+                / 2;
+        }
+        for (i, base) in base64.iter_mut().enumerate() {
+            *base = base
+                .checked_shl(64 - i as u32 - min_sym_len as u32)
+                .unwrap_or(0);
+        }
+        pos += lengths * 2;
+
+        let symbols = self.u16_at(pos) as usize;
+        pos += 2;
+        let btree = pos;
+        if self.data.len() < btree + symbols * 3 {
+            return None;
+        }
+        let mut symlen = vec![0u8; symbols];
+        let mut visited = vec![false; symbols];
+        for sym in 0..symbols {
+            if !visited[sym] {
+                symlen[sym] = self.set_symlen(btree, sym, &mut symlen, &mut visited)?;
+            }
+        }
+
+        let d = self.item_mut(stm, file);
+        d.flags = flags;
+        d.min_sym_len = min_sym_len;
+        d.block_size = block_size;
+        d.span = span;
+        d.sparse_index_size = tb_size.div_ceil(span) as usize;
+        d.num_blocks = num_blocks;
+        d.block_length_size = num_blocks + padding;
+        d.lowest_sym = lowest_sym;
+        d.base64 = base64;
+        d.btree = btree;
+        d.symlen = symlen;
+        Some(btree + symbols * 3 + (symbols & 1))
+    }
+
+    // How many values (-1) a symbol expands to, from the pairs it stands
+    // for. The tree has no cycles, but a broken file might.
+    fn set_symlen(
+        &self,
+        btree: usize,
+        sym: usize,
+        symlen: &mut [u8],
+        visited: &mut [bool],
+    ) -> Option<u8> {
+        visited[sym] = true;
+        let (left, right) = Self::pair(&self.data, btree, sym);
+        if right == 0xfff {
+            return Some(0);
+        }
+        for child in [left, right] {
+            if *visited.get(child)? {
+                continue;
+            }
+            symlen[child] = self.set_symlen(btree, child, symlen, visited)?;
+        }
+        Some(symlen[left].wrapping_add(symlen[right]).wrapping_add(1))
+    }
+
+    // The two symbols a symbol stands for, 12 bits each. A symbol which
+    // stands for a value has the value on the left and 0xfff on the right.
+    fn pair(data: &[u8], btree: usize, sym: usize) -> (usize, usize) {
+        let lr = &data[btree + sym * 3..btree + sym * 3 + 3];
+        let left = ((lr[1] as usize & 0xf) << 8) | lr[0] as usize;
+        let right = ((lr[2] as usize) << 4) | (lr[1] as usize >> 4);
+        (left, right)
+    }
+
+    // DTZ values are stored as indexes into small maps, one for each of
+    // win, loss, cursed win and blessed loss.
+    fn set_dtz_map(&mut self, mut pos: usize) -> Option<usize> {
+        self.map = pos;
+        for file in 0..self.files() {
+            let flags = self.item(0, file).flags;
+            if flags & FLAG_MAPPED == 0 {
+                continue;
+            }
+            let mut map_idx = [0; 4];
+            for idx in &mut map_idx {
+                if self.data.len() < pos + 3 {
+                    return None;
+                }
+                if flags & FLAG_WIDE != 0 {
+                    pos += pos & 1;
+                    *idx = (pos - self.map) / 2 + 1;
+                    pos += 2 * self.u16_at(pos) as usize + 2;
+                } else {
+                    *idx = pos - self.map + 1;
+                    pos += self.data[pos] as usize + 1;
+                }
+            }
+            self.item_mut(0, file).map_idx = map_idx;
+        }
+        Some(pos + (pos & 1))
+    }
+
+    // DTZ tables have only one side to move. False if it's the other one,
+    // and the position has to be looked up a move later.
+    fn dtz_stm_matches(&self, stm: usize, file: usize) -> bool {
+        (self.item(stm, file).flags & FLAG_STM) as usize == stm
+            || (self.symmetric && !self.has_pawns)
+    }
+
+    // The WDL value of a position: -2 to 2 for a loss, a blessed loss, a
+    // draw, a cursed win and a win of the side to move. The pieces are
+    // given as (piece, square). `flip` is set if Black has the first part
+    // of the material in the name of the table.
+    pub fn probe_wdl(&self, pieces: &[(u8, Square)], stm: u8, flip: bool) -> i32 {
+        match self.index(pieces, stm, flip) {
+            Some((item, file, idx)) => self.decompress(self.item(item, file), idx) - 2,
+            None => 0,
+        }
+    }
+
+    // The DTZ value of a position with a known WDL value, in plies, or
+    // None if the table is for the other side to move.
+    pub fn probe_dtz(&self, pieces: &[(u8, Square)], stm: u8, flip: bool, wdl: i32) -> Option<i32> {
+        let (item, file, idx) = self.index(pieces, stm, flip)?;
+        let d = self.item(item, file);
+        let mut value = self.decompress(d, idx);
+
+        const WDL_MAP: [usize; 5] = [1, 3, 0, 2, 0];
+        if d.flags & FLAG_MAPPED != 0 {
+            let i = d.map_idx[WDL_MAP[(wdl + 2) as usize]] + value as usize;
+            value = if d.flags & FLAG_WIDE != 0 {
+                self.u16_at(self.map + 2 * i) as i32
+            } else {
+                self.data[self.map + i] as i32
+            };
+        }
+        let in_moves = (wdl == 2 && d.flags & FLAG_WIN_PLIES == 0)
+            || (wdl == -2 && d.flags & FLAG_LOSS_PLIES == 0)
+            || wdl == 1
+            || wdl == -1;
+        if in_moves {
+            value *= 2;
+        }
+        Some(value + 1)
+    }
+
+    // The part of the table and the index within it. None for a DTZ table
+    // of the other side to move.
+    fn index(&self, position: &[(u8, Square)], stm: u8, flip: bool) -> Option<(usize, usize, u64)> {
+        let e = encoding();
+        // The tables only have the stronger side as White, and symmetric
+        // ones only White to move. Otherwise colours and ranks are flipped.
+        let flip = flip || (self.symmetric && stm == BLACK);
+        let color_flip = if flip { BLACK_PIECE } else { 0 };
+        let square_flip = if flip { 56 } else { 0 };
+        let stm = (flip as u8 ^ stm) as usize;
+
+        let mut squares = [0usize; TB_PIECES];
+        let mut pieces = [0u8; TB_PIECES];
+        let mut size = 0;
+        let mut lead_pawns = 0;
+        let mut file = 0;
+        let mut lead = 0;
+
+        // Tables with pawns are split by the file of the leading pawn,
+        // the one nearest to the a-file or h-file and the 2nd rank.
+        if self.has_pawns {
+            lead = self.items[0].pieces[0] ^ color_flip;
+            for &(piece, square) in position {
+                if piece == lead {
+                    squares[size] = (square ^ square_flip) as usize;
+                    size += 1;
+                }
+            }
+            lead_pawns = size;
+            let front = (0..size).max_by_key(|&i| e.map_pawns[squares[i]]).unwrap();
+            squares.swap(0, front);
+            file = (squares[0] % 8).min(7 - squares[0] % 8);
+        }
+
+        if self.kind == Kind::Dtz && !self.dtz_stm_matches(stm, file) {
+            return None;
+        }
+
+        for &(piece, square) in position {
+            if self.has_pawns && piece == lead {
+                continue;
+            }
+            squares[size] = (square ^ square_flip) as usize;
+            pieces[size] = piece ^ color_flip;
+            size += 1;
+        }
+
+        // bring the pieces into the order of the table
+        let d = self.item(stm, file);
+        for i in lead_pawns..size.saturating_sub(1) {
+            for j in i + 1..size {
+                if d.pieces[i] == pieces[j] {
+                    pieces.swap(i, j);
+                    squares.swap(i, j);
+                    break;
+                }
+            }
+        }
+
+        // the leading piece goes to the a- to d-files
+        if squares[0] % 8 > 3 {
+            squares[..size].iter_mut().for_each(|square| *square ^= 7);
+        }
+
+        let mut idx;
+        if self.has_pawns {
+            idx = e.lead_pawn_idx[lead_pawns][squares[0]];
+            squares[1..lead_pawns].sort_by_key(|&square| e.map_pawns[square]);
+            for (i, &square) in squares.iter().enumerate().take(lead_pawns).skip(1) {
+                idx += e.binomial[i][e.map_pawns[square]];
+            }
+        } else {
+            // without pawns, also to the 1st to 4th ranks, and the first
+            // of the leading group not on the a1-h8 diagonal below it
+            if squares[0] / 8 > 3 {
+                squares[..size].iter_mut().for_each(|square| *square ^= 56);
+            }
+            for i in 0..d.group_len[0] {
+                let off = off_a1h8(squares[i]);
+                if off == 0 {
+                    continue;
+                }
+                if off > 0 {
+                    for square in &mut squares[i..size] {
+                        *square = ((*square >> 3) | (*square << 3)) & 63;
+                    }
+                }
+                break;
+            }
+            idx = if self.has_unique_pieces {
+                unique_pieces_index(e, &squares)
+            } else {
+                e.map_kk[e.map_a1d1d4[squares[0]]][squares[1]] as u64
+            };
+        }
+
+        // then the other groups, each sorted, with the squares taken by the
+        // groups before left out
+        idx *= d.group_idx[0];
+        let mut start = d.group_len[0];
+        let mut remaining_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = 1;
+        while d.group_len[next] != 0 {
+            let len = d.group_len[next];
+            squares[start..start + len].sort_unstable();
+            let mut n = 0;
+            for i in 0..len {
+                let square = squares[start + i];
+                let adjust = squares[..start].iter().filter(|&&s| square > s).count();
+                n += e.binomial[i + 1][square - adjust - if remaining_pawns { 8 } else { 0 }];
+            }
+            remaining_pawns = false;
+            idx += n * d.group_idx[next];
+            start += len;
+            next += 1;
+        }
+        Some((stm, file, idx))
+    }
+
+    // Finds the value at an index: first the block it is in, from the
+    // nearest entry of the sparse index, then the symbol within the block
+    // which covers it, and then the value within the pairs the symbol
+    // stands for.
+    fn decompress(&self, d: &PairsData, idx: u64) -> i32 {
+        if d.flags & FLAG_SINGLE_VALUE != 0 {
+            return d.min_sym_len as i32;
+        }
+
+        // the entries of the sparse index point to the values at
+        // k * span + span / 2
+        let k = (idx / d.span) as usize;
+        let entry = d.sparse_index + 6 * k;
+        let mut block = self.u32_at(entry) as usize;
+        let mut offset = self.u16_at(entry + 4) as i64;
+        offset += (idx % d.span) as i64 - (d.span / 2) as i64;
+
+        // each block holds block_length + 1 values
+        let block_length = |block: usize| self.u16_at(d.block_length + 2 * block) as i64;
+        while offset < 0 {
+            block -= 1;
+            offset += block_length(block) + 1;
+        }
+        while offset > block_length(block) {
+            offset -= block_length(block) + 1;
+            block += 1;
+        }
+
+        let mut ptr = d.data + block * d.block_size;
+        let mut buf64 = (self.be_u32_at(ptr) as u64) << 32 | self.be_u32_at(ptr + 4) as u64;
+        ptr += 8;
+        let mut buf64_size = 64;
+        let min_len = d.min_sym_len as usize;
+        let mut sym;
+        loop {
+            let mut len = 0;
+            while len + 1 < d.base64.len() && buf64 < d.base64[len] {
+                len += 1;
+            }
+            sym = (buf64.wrapping_sub(d.base64[len]) >> (64 - len - min_len)) as u16;
+            sym = sym.wrapping_add(self.u16_at(d.lowest_sym + 2 * len));
+            let count = d.symlen[sym as usize] as i64 + 1;
+            if offset < count {
+                break;
+            }
+            offset -= count;
+            let bits = len + min_len;
+            buf64 <<= bits;
+            buf64_size -= bits as i32;
+            if buf64_size <= 32 {
+                buf64_size += 32;
+                buf64 |= (self.be_u32_at(ptr) as u64) << (64 - buf64_size);
+                ptr += 4;
+            }
+        }
+
+        let mut sym = sym as usize;
+        while d.symlen[sym] != 0 {
+            let (left, right) = Self::pair(&self.data, d.btree, sym);
+            let count = d.symlen[left] as i64 + 1;
+            if offset < count {
+                sym = left;
+            } else {
+                offset -= count;
+                sym = right;
+            }
+        }
+        Self::pair(&self.data, d.btree, sym).0 as i32
+    }
+}
+
+// Three unique pieces together, the first in the a1-d1-d4 triangle. The
+// cases of pieces on the a1-h8 diagonal come after those below it.
+fn unique_pieces_index(e: &Encoding, squares: &[usize]) -> u64 {
+    let (s0, s1, s2) = (squares[0], squares[1], squares[2]);
+    let adjust1 = (s1 > s0) as usize;
+    let adjust2 = (s2 > s0) as usize + (s2 > s1) as usize;
+    let rank = |square: usize| square / 8;
+    let idx = if off_a1h8(s0) != 0 {
+        (e.map_a1d1d4[s0] * 63 + (s1 - adjust1)) * 62 + s2 - adjust2
+    } else if off_a1h8(s1) != 0 {
+        (6 * 63 + rank(s0) * 28 + e.map_b1h1h7[s1]) * 62 + s2 - adjust2
+    } else if off_a1h8(s2) != 0 {
+        6 * 63 * 62 + 4 * 28 * 62 + rank(s0) * 7 * 28 + (rank(s1) - adjust1) * 28 + e.map_b1h1h7[s2]
+    } else {
+        6 * 63 * 62
+            + 4 * 28 * 62
+            + 4 * 7 * 28
+            + rank(s0) * 7 * 6
+            + (rank(s1) - adjust1) * 6
+            + (rank(s2) - adjust2)
+    };
+    idx as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encoding() {
+        let e = encoding();
+        // all legal places of the kings have their own code
+        let mut codes: Vec<usize> = (0..10)
+            .flat_map(|i| (0..64).map(move |s| (i, s)))
+            .filter(|&(i, s)| e.map_kk[i][s] != 0 || (i == 0 && s == Squares::D1 as usize))
+            .map(|(i, s)| e.map_kk[i][s])
+            .collect();
+        codes.sort_unstable();
+        codes.dedup();
+        assert_eq!(codes.len(), 462);
+        assert_eq!(*codes.last().unwrap(), 461);
+
+        assert_eq!(e.binomial[2][5], 10);
+        assert_eq!(e.binomial[5][63], 7_028_847);
+        assert_eq!(e.map_pawns[Squares::A2 as usize], 47);
+        assert_eq!(e.map_pawns[Squares::H2 as usize], 46);
+        assert_eq!(e.map_a1d1d4[Squares::B1 as usize], 0);
+        assert_eq!(e.map_a1d1d4[D4], 9);
+        // a single leading pawn on any of the 6 ranks of a file
+        assert_eq!(e.lead_pawns_size[1], [6; 4]);
+    }
+}
@@ -0,0 +1,433 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use super::*;
+use crate::board::pieces::Pieces;
+use crate::dtm::{Dtm, DtmTables};
+use crate::move_generator::MoveGenerator;
+
+// Writes tables in the Syzygy format for the tests, from our distance to
+// mate tables. The index is the one the decoder computes, the values are
+// compressed as in the official files: pairs of symbols are replaced by
+// new symbols, which are then given a canonical Huffman code, in blocks
+// found through a sparse index. DTZ files can only be written where DTZ
+// is DTM: without pawns, and with nothing for the weaker side to capture.
+
+const BLOCK_SIZE: usize = 1 << 6;
+const SPAN: usize = 1 << 10;
+// the values a symbol stands for fit into the u8 of `symlen`
+const MAX_EXPANSION: usize = 256;
+// symbols are 12 bits, and 0xfff marks a value
+const MAX_SYMBOLS: usize = 0xfff;
+
+fn piece_code(letter: char) -> u8 {
+    match letter {
+        'P' => PAWN,
+        'N' => 2,
+        'B' => 3,
+        'R' => 4,
+        'Q' => 5,
+        _ => KING,
+    }
+}
+
+fn piece(code: u8) -> Pieces {
+    match code & 7 {
+        PAWN => Pieces::Pawn,
+        2 => Pieces::Knight,
+        3 => Pieces::Bishop,
+        4 => Pieces::Rook,
+        5 => Pieces::Queen,
+        _ => Pieces::King,
+    }
+}
+
+// The pieces in the order of the table: the pawns first, as they lead,
+// then as in the name. Only one side may have pawns.
+fn table_pieces(name: &str) -> Vec<u8> {
+    let (white, black) = name.split_once('v').unwrap();
+    let mut pieces: Vec<u8> = white
+        .chars()
+        .map(piece_code)
+        .chain(black.chars().map(|c| piece_code(c) | BLACK_PIECE))
+        .collect();
+    pieces.sort_by_key(|&code| code & 7 != PAWN);
+    pieces
+}
+
+fn header(name: &str, kind: Kind) -> Vec<u8> {
+    let pieces = table_pieces(name);
+    let has_pawns = pieces[0] & 7 == PAWN;
+    let mut bytes = if kind == Kind::Wdl {
+        WDL_MAGIC.to_vec()
+    } else {
+        DTZ_MAGIC.to_vec()
+    };
+    bytes.push(FILE_SPLIT | if has_pawns { FILE_HAS_PAWNS } else { 0 });
+    for _ in 0..if has_pawns { 4 } else { 1 } {
+        // the leading group first in the index, for both sides to move
+        bytes.push(0);
+        bytes.extend(pieces.iter().map(|&code| code | code << 4));
+    }
+    if bytes.len() % 2 == 1 {
+        bytes.push(0);
+    }
+    bytes
+}
+
+// The values of a side to move and file, None where any value will do.
+fn values(
+    table: &Table,
+    mg: &MoveGenerator,
+    tables: &mut DtmTables,
+    name: &str,
+) -> Vec<Vec<Option<u16>>> {
+    let pieces = table_pieces(name);
+    let dtm = tables.generate(mg, name).unwrap();
+    let mut values: Vec<Vec<Option<u16>>> = table
+        .items
+        .iter()
+        .map(|d| {
+            let size = d.group_idx[d.group_len.iter().position(|&len| len == 0).unwrap()];
+            vec![None; size as usize]
+        })
+        .collect();
+    let positions = NrOf::SQUARES.pow(pieces.len() as u32);
+    for stm in [WHITE, BLACK] {
+        for mut i in 0..positions {
+            let mut coded = Vec::with_capacity(pieces.len());
+            let mut board_pieces = Vec::with_capacity(pieces.len());
+            for &code in &pieces {
+                let square = (i % NrOf::SQUARES) as Square;
+                i /= NrOf::SQUARES;
+                let side = if code & BLACK_PIECE != 0 {
+                    BLACK
+                } else {
+                    WHITE
+                };
+                coded.push((code, square));
+                board_pieces.push((side, piece(code), square));
+            }
+            let pawn_on_edge = coded
+                .iter()
+                .any(|&(code, square)| code & 7 == PAWN && matches!(square / 8, 0 | 7));
+            let mut squares: Vec<Square> = coded.iter().map(|&(_, square)| square).collect();
+            squares.sort_unstable();
+            squares.dedup();
+            if pawn_on_edge || squares.len() != pieces.len() {
+                continue;
+            }
+            let Some(dtm) = dtm.dtm(&board_pieces, stm) else {
+                continue;
+            };
+            let Some((item, file, idx)) = table.index(&coded, stm, false) else {
+                continue;
+            };
+            let value = match (table.kind, dtm) {
+                (Kind::Wdl, Dtm::Win(_)) => Some(4),
+                (Kind::Wdl, Dtm::Draw) => Some(2),
+                (Kind::Wdl, Dtm::Loss(_)) => Some(0),
+                (Kind::Dtz, Dtm::Win(plies) | Dtm::Loss(plies)) if plies > 0 => {
+                    Some(plies as u16 - 1)
+                }
+                // mates and draws are found without the table
+                (Kind::Dtz, _) => None,
+            };
+            let slot = &mut values[item * table.files() + file][idx as usize];
+            assert!(
+                slot.is_none() || *slot == value,
+                "{name}: two values at {idx}"
+            );
+            *slot = value;
+        }
+    }
+    values
+}
+
+// One side to move and file of a table, compressed.
+#[derive(Default)]
+struct Part {
+    sizes: Vec<u8>,
+    sparse_index: Vec<u8>,
+    block_lengths: Vec<u8>,
+    data: Vec<u8>,
+}
+
+fn compress(values: &[Option<u16>], flags: u8) -> Part {
+    // where any value will do, the one before it makes the longest runs
+    let first = values.iter().flatten().next().copied().unwrap_or(0);
+    let values: Vec<u16> = values
+        .iter()
+        .scan(first, |last, &value| {
+            *last = value.unwrap_or(*last);
+            Some(*last)
+        })
+        .collect();
+    if values.iter().all(|&value| value == values[0]) {
+        return Part {
+            sizes: vec![flags | FLAG_SINGLE_VALUE, values[0] as u8],
+            ..Part::default()
+        };
+    }
+
+    // a symbol for each value, then the pairs
+    let mut pairs: Vec<(usize, usize)> = Vec::new();
+    let mut lengths: Vec<usize> = Vec::new();
+    let mut leaves = HashMap::new();
+    let mut symbols: Vec<usize> = values
+        .iter()
+        .map(|&value| {
+            *leaves.entry(value).or_insert_with(|| {
+                pairs.push((value as usize, MAX_SYMBOLS));
+                lengths.push(1);
+                pairs.len() - 1
+            })
+        })
+        .collect();
+    while pairs.len() < MAX_SYMBOLS {
+        let mut counts: HashMap<(usize, usize), usize> = HashMap::new();
+        for pair in symbols.windows(2) {
+            if lengths[pair[0]] + lengths[pair[1]] <= MAX_EXPANSION {
+                *counts.entry((pair[0], pair[1])).or_default() += 1;
+            }
+        }
+        let Some((&(left, right), &count)) = counts
+            .iter()
+            .max_by_key(|&(&pair, &count)| (count, Reverse(pair)))
+        else {
+            break;
+        };
+        if count < 4 {
+            break;
+        }
+        pairs.push((left, right));
+        lengths.push(lengths[left] + lengths[right]);
+        let symbol = pairs.len() - 1;
+        let mut paired = Vec::with_capacity(symbols.len());
+        let mut i = 0;
+        while i < symbols.len() {
+            if i + 1 < symbols.len() && (symbols[i], symbols[i + 1]) == (left, right) {
+                paired.push(symbol);
+                i += 2;
+            } else {
+                paired.push(symbols[i]);
+                i += 1;
+            }
+        }
+        symbols = paired;
+    }
+
+    // Huffman code lengths of the symbols used
+    let mut frequencies = vec![0usize; pairs.len()];
+    for &symbol in &symbols {
+        frequencies[symbol] += 1;
+    }
+    let mut bits = vec![0usize; pairs.len()];
+    let mut heap: BinaryHeap<Reverse<(usize, Vec<usize>)>> = frequencies
+        .iter()
+        .enumerate()
+        .filter(|&(_, &frequency)| frequency > 0)
+        .map(|(symbol, &frequency)| Reverse((frequency, vec![symbol])))
+        .collect();
+    if heap.len() == 1 {
+        let Reverse((_, symbol)) = heap.pop().unwrap();
+        bits[symbol[0]] = 1;
+    }
+    while heap.len() > 1 {
+        let Reverse((a, mut left)) = heap.pop().unwrap();
+        let Reverse((b, right)) = heap.pop().unwrap();
+        left.extend(right);
+        for &symbol in &left {
+            bits[symbol] += 1;
+        }
+        heap.push(Reverse((a + b, left)));
+    }
+    let min_len = *bits.iter().filter(|&&len| len > 0).min().unwrap();
+    let max_len = *bits.iter().max().unwrap();
+    assert!(max_len <= 32, "code too long");
+
+    // The symbols are renumbered: the longest codes first, then those
+    // only used in pairs.
+    let mut order: Vec<usize> = (0..pairs.len()).collect();
+    order.sort_by_key(|&symbol| (bits[symbol] == 0, Reverse(bits[symbol])));
+    let mut number = vec![0; pairs.len()];
+    for (new, &old) in order.iter().enumerate() {
+        number[old] = new;
+    }
+    let count = |len: usize| bits.iter().filter(|&&b| b == len).count();
+    // the first symbol and the first code of each length
+    let mut lowest = vec![0; max_len + 2];
+    let mut base = vec![0u64; max_len + 2];
+    for len in (min_len..=max_len).rev() {
+        lowest[len] = lowest[len + 1] + count(len + 1);
+        base[len] = (base[len + 1] + count(len + 1) as u64) / 2;
+    }
+    let code = |symbol: usize| {
+        let len = bits[symbol];
+        (base[len] + (number[symbol] - lowest[len]) as u64, len)
+    };
+
+    // the blocks, each with whole symbols
+    let mut blocks: Vec<(Vec<u8>, usize)> = Vec::new();
+    let (mut block, mut used, mut length) = (vec![0u8; BLOCK_SIZE], 0, 0);
+    for &symbol in &symbols {
+        let (code, len) = code(symbol);
+        if used + len > BLOCK_SIZE * 8 || length + lengths[symbol] > u16::MAX as usize + 1 {
+            blocks.push((block, length));
+            (block, used, length) = (vec![0u8; BLOCK_SIZE], 0, 0);
+        }
+        for bit in (0..len).rev() {
+            if code >> bit & 1 != 0 {
+                block[used / 8] |= 0x80 >> (used % 8);
+            }
+            used += 1;
+        }
+        length += lengths[symbol];
+    }
+    blocks.push((block, length));
+
+    let mut sizes = vec![
+        flags,
+        BLOCK_SIZE.trailing_zeros() as u8,
+        SPAN.trailing_zeros() as u8,
+        0,
+    ];
+    sizes.extend((blocks.len() as u32).to_le_bytes());
+    sizes.extend([max_len as u8, min_len as u8]);
+    for &lowest in &lowest[min_len..=max_len] {
+        sizes.extend((lowest as u16).to_le_bytes());
+    }
+    sizes.extend((pairs.len() as u16).to_le_bytes());
+    for &old in &order {
+        let (left, right) = pairs[old];
+        let (left, right) = if right == MAX_SYMBOLS {
+            (left, right)
+        } else {
+            (number[left], number[right])
+        };
+        sizes.extend([
+            left as u8,
+            (left >> 8) as u8 | (right << 4) as u8,
+            (right >> 4) as u8,
+        ]);
+    }
+    if pairs.len() % 2 == 1 {
+        sizes.push(0);
+    }
+
+    // every SPAN values, the block and the offset in it of the value in
+    // the middle of the span
+    let mut sparse_index = Vec::new();
+    let (mut block, mut start) = (0, 0);
+    for k in 0..values.len().div_ceil(SPAN) {
+        let middle = k * SPAN + SPAN / 2;
+        while block + 1 < blocks.len() && start + blocks[block].1 <= middle {
+            start += blocks[block].1;
+            block += 1;
+        }
+        sparse_index.extend((block as u32).to_le_bytes());
+        sparse_index.extend(u16::try_from(middle - start).unwrap().to_le_bytes());
+    }
+    Part {
+        sizes,
+        sparse_index,
+        block_lengths: blocks
+            .iter()
+            .flat_map(|&(_, length)| (length as u16 - 1).to_le_bytes())
+            .collect(),
+        data: blocks.into_iter().flat_map(|(block, _)| block).collect(),
+    }
+}
+
+// The file of a table in the Syzygy format.
+pub fn write(mg: &MoveGenerator, tables: &mut DtmTables, name: &str, kind: Kind) -> Vec<u8> {
+    let mut bytes = header(name, kind);
+    let mut table = Table::new(bytes.clone(), name, kind).unwrap();
+    table.init_pieces().unwrap();
+    let flags = if kind == Kind::Dtz {
+        FLAG_WIN_PLIES | FLAG_LOSS_PLIES
+    } else {
+        0
+    };
+    let values = values(&table, mg, tables, name);
+    // the parts are stored by file, then side to move
+    let files = table.files();
+    let parts: Vec<Part> = (0..values.len())
+        .map(|i| {
+            compress(
+                &values[i % table.sides() * files + i / table.sides()],
+                flags,
+            )
+        })
+        .collect();
+
+    for part in &parts {
+        bytes.extend(&part.sizes);
+    }
+    if bytes.len() % 2 == 1 {
+        bytes.push(0);
+    }
+    for part in &parts {
+        bytes.extend(&part.sparse_index);
+    }
+    for part in &parts {
+        bytes.extend(&part.block_lengths);
+    }
+    for part in &parts {
+        bytes.resize(bytes.len().next_multiple_of(64), 0);
+        bytes.extend(&part.data);
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every value written is read back.
+    #[test]
+    fn test_round_trip() {
+        let mg = MoveGenerator::new();
+        let mut tables = DtmTables::new();
+        for (name, kind) in [
+            ("KRvK", Kind::Wdl),
+            ("KRvK", Kind::Dtz),
+            ("KPvK", Kind::Wdl),
+        ] {
+            let bytes = write(&mg, &mut tables, name, kind);
+            let mut table = Table::new(bytes, name, kind).unwrap();
+            assert!(table.init());
+            let values = values(&table, &mg, &mut tables, name);
+            for (item, values) in values.iter().enumerate() {
+                for (idx, &value) in values.iter().enumerate() {
+                    if let Some(value) = value {
+                        let decoded = table.decompress(&table.items[item], idx as u64);
+                        assert_eq!(decoded, value as i32);
+                    }
+                }
+            }
+        }
+    }
+
+    // `cargo test write_test_tables -- --ignored` writes the tables the
+    // tests of the probing use.
+    #[test]
+    #[ignore = "writes the files in test-data/syzygy"]
+    fn write_test_tables() {
+        let mg = MoveGenerator::new();
+        let mut tables = DtmTables::new();
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("test-data/syzygy");
+        fs::create_dir_all(&dir).unwrap();
+        for (name, kinds) in [
+            ("KQvK", &[Kind::Wdl, Kind::Dtz][..]),
+            ("KRvK", &[Kind::Wdl, Kind::Dtz]),
+            ("KPvK", &[Kind::Wdl]),
+        ] {
+            for &kind in kinds {
+                let extension = if kind == Kind::Wdl { "rtbw" } else { "rtbz" };
+                let bytes = write(&mg, &mut tables, name, kind);
+                fs::write(dir.join(format!("{name}.{extension}")), bytes).unwrap();
+            }
+        }
+    }
+}
@@ -0,0 +1,554 @@
+use std::fs;
+use std::path::Path;
+
+use crate::board::Board;
+use crate::evaluation;
+use crate::evaluation::endgame::SCALE_NORMAL;
+use crate::evaluation::score::{Score, MAX_PHASE};
+use crate::evaluation::trace::{parameter_count, Term};
+use crate::evaluation::weights::*;
+use crate::move_generator::MoveGenerator;
+
+// Texel tuning: the evaluation should predict the results of games. A
+// score is turned into an expected result by a sigmoid, and the weights
+// are changed to make the mean squared error against the actual results
+// of many positions as small as possible.
+//
+// Almost all of the evaluation is linear in its weights, so each position
+// is traced once: how often each weight counts, plus what is left of the
+// evaluation that isn't tuned (the part of king danger which grows with
+// the square of the attacks). Evaluating with other weights is then a
+// short sum, and so is the gradient.
+
+// How the weights are optimised: Adam, with the learning rate in
+// centipawns.
+pub struct TunerSettings {
+    pub epochs: usize,
+    pub rate: f64,
+}
+
+impl Default for TunerSettings {
+    fn default() -> Self {
+        Self {
+            epochs: 1000,
+            rate: 1.0,
+        }
+    }
+}
+
+const BETA1: f64 = 0.9;
+const BETA2: f64 = 0.999;
+const EPSILON: f64 = 1e-8;
+
+// A labelled position as the tuner sees it.
+struct Entry {
+    // the index of each weight which counts, and how often
+    coefficients: Vec<(u16, i16)>,
+    // the part of the evaluation which isn't tuned
+    fixed: Score,
+    phase: i32,
+    scale: i32,
+    // from White's point of view, 1 for a win and 0.5 for a draw
+    result: f64,
+}
+
+impl Entry {
+    fn new(board: &Board, mg: &MoveGenerator, result: f64, weights: &[Score]) -> Option<Self> {
+        let traced = evaluation::trace(board, mg)?;
+        let coefficients: Vec<(u16, i16)> = traced
+            .coefficients
+            .0
+            .iter()
+            .enumerate()
+            .filter(|&(_, &c)| c != 0)
+            .map(|(i, &c)| (i as u16, c as i16))
+            .collect();
+        let linear = coefficients.iter().fold(Score::default(), |sum, &(i, c)| {
+            sum + weights[i as usize] * c as i32
+        });
+        Some(Self {
+            coefficients,
+            fixed: traced.score - linear,
+            phase: traced.phase,
+            scale: traced.scale,
+            result,
+        })
+    }
+
+    // The middlegame and endgame weight of a term, times the scale.
+    fn factors(&self) -> (f64, f64) {
+        let scale = self.scale as f64 / SCALE_NORMAL as f64;
+        let mg = self.phase as f64 / MAX_PHASE as f64;
+        (mg * scale, (1.0 - mg) * scale)
+    }
+
+    fn evaluate(&self, weights: &[[f64; 2]]) -> f64 {
+        let (mut mg, mut eg) = (self.fixed.mg as f64, self.fixed.eg as f64);
+        for &(i, c) in &self.coefficients {
+            mg += weights[i as usize][0] * c as f64;
+            eg += weights[i as usize][1] * c as f64;
+        }
+        let (mg_factor, eg_factor) = self.factors();
+        mg * mg_factor + eg * eg_factor
+    }
+}
+
+// The expected result for a score in centipawns.
+fn sigmoid(k: f64, score: f64) -> f64 {
+    1.0 / (1.0 + 10f64.powf(-k * score / 400.0))
+}
+
+fn error(entries: &[Entry], weights: &[[f64; 2]], k: f64) -> f64 {
+    let sum: f64 = entries
+        .iter()
+        .map(|entry| (entry.result - sigmoid(k, entry.evaluate(weights))).powi(2))
+        .sum();
+    sum / entries.len().max(1) as f64
+}
+
+// The K which fits the current evaluation best: scanned in steps that
+// become ten times finer around the best one so far.
+fn optimal_k(entries: &[Entry], weights: &[[f64; 2]]) -> f64 {
+    let (mut best, mut step) = (1.0, 0.5);
+    for _ in 0..4 {
+        best = (-10..=10)
+            .map(|i| best + i as f64 * step)
+            .filter(|&k| k > 0.0)
+            .min_by(|&a, &b| error(entries, weights, a).total_cmp(&error(entries, weights, b)))
+            .unwrap_or(best);
+        step /= 10.0;
+    }
+    best
+}
+
+// The gradient of the error by each weight, up to a constant factor.
+fn gradient(entries: &[Entry], weights: &[[f64; 2]], k: f64) -> Vec<[f64; 2]> {
+    let mut gradient = vec![[0.0; 2]; weights.len()];
+    for entry in entries {
+        let s = sigmoid(k, entry.evaluate(weights));
+        let g = (s - entry.result) * s * (1.0 - s);
+        let (mg_factor, eg_factor) = entry.factors();
+        for &(i, c) in &entry.coefficients {
+            gradient[i as usize][0] += g * mg_factor * c as f64;
+            gradient[i as usize][1] += g * eg_factor * c as f64;
+        }
+    }
+    gradient
+}
+
+// Tunes the weights on the positions, starting from `weights`, and
+// reports the error now and then.
+fn tune(
+    entries: &[Entry],
+    weights: &[Score],
+    k: f64,
+    settings: &TunerSettings,
+    mut report: impl FnMut(usize, f64),
+) -> Vec<Score> {
+    let mut current = as_f64(weights);
+    let mut momentum = vec![[0.0; 2]; weights.len()];
+    let mut velocity = vec![[0.0; 2]; weights.len()];
+    for epoch in 1..=settings.epochs {
+        let gradient = gradient(entries, &current, k);
+        for i in 0..current.len() {
+            for phase in 0..2 {
+                let g = gradient[i][phase];
+                momentum[i][phase] = BETA1 * momentum[i][phase] + (1.0 - BETA1) * g;
+                velocity[i][phase] = BETA2 * velocity[i][phase] + (1.0 - BETA2) * g * g;
+                let m = momentum[i][phase] / (1.0 - BETA1.powi(epoch as i32));
+                let v = velocity[i][phase] / (1.0 - BETA2.powi(epoch as i32));
+                current[i][phase] -= settings.rate * m / (v.sqrt() + EPSILON);
+            }
+        }
+        if epoch % 50 == 0 || epoch == settings.epochs {
+            report(epoch, error(entries, &current, k));
+        }
+    }
+    current
+        .iter()
+        .map(|&[mg, eg]| Score::new(mg.round() as i32, eg.round() as i32))
+        .collect()
+}
+
+fn as_f64(weights: &[Score]) -> Vec<[f64; 2]> {
+    weights
+        .iter()
+        .map(|score| [score.mg as f64, score.eg as f64])
+        .collect()
+}
+
+// The weights the engine was built with, as one vector in the order of
+// `Term`.
+pub fn default_weights() -> Vec<Score> {
+    let weights: Vec<Score> = Term::ALL.iter().flat_map(|&term| values(term)).collect();
+    debug_assert_eq!(weights.len(), parameter_count());
+    weights
+}
+
+fn values(term: Term) -> Vec<Score> {
+    match term {
+        Term::Material => MATERIAL.to_vec(),
+        Term::Psqt => (0..PSQT_MG.len() * 64)
+            .map(|i| {
+                Score::new(
+                    PSQT_MG[i / 64][i % 64] as i32,
+                    PSQT_EG[i / 64][i % 64] as i32,
+                )
+            })
+            .collect(),
+        Term::Mobility => MOBILITY.to_vec(),
+        Term::KnightOutpost => vec![KNIGHT_OUTPOST],
+        Term::BishopOutpost => vec![BISHOP_OUTPOST],
+        Term::BishopPair => vec![BISHOP_PAIR],
+        Term::RookOpenFile => vec![ROOK_OPEN_FILE],
+        Term::RookSemiOpenFile => vec![ROOK_SEMI_OPEN_FILE],
+        Term::RookOnSeventh => vec![ROOK_ON_SEVENTH],
+        Term::TrappedRook => vec![TRAPPED_ROOK],
+        Term::TrappedBishop => vec![TRAPPED_BISHOP],
+        Term::PawnThreat => vec![PAWN_THREAT],
+        Term::MinorThreat => vec![MINOR_THREAT],
+        Term::Passed => PASSED.to_vec(),
+        Term::Candidate => CANDIDATE.to_vec(),
+        Term::Supported => SUPPORTED.to_vec(),
+        Term::Phalanx => PHALANX.to_vec(),
+        Term::Doubled => vec![DOUBLED],
+        Term::Isolated => vec![ISOLATED],
+        Term::Backward => vec![BACKWARD],
+        Term::PassedFreePath => PASSED_FREE_PATH.to_vec(),
+        Term::PassedOwnKingDistance => vec![PASSED_OWN_KING_DISTANCE],
+        Term::PassedTheirKingDistance => vec![PASSED_THEIR_KING_DISTANCE],
+        Term::Shield => SHIELD.to_vec(),
+        Term::Storm => STORM.to_vec(),
+        Term::BlockedStorm => BLOCKED_STORM.to_vec(),
+        Term::KingSemiOpenFile => vec![KING_SEMI_OPEN_FILE],
+        Term::KingOpenFile => vec![KING_OPEN_FILE],
+        Term::KingAttacker => KING_ATTACKER.to_vec(),
+    }
+}
+
+// The name of each term in `weights.rs` and the comment above it.
+fn describe(term: Term) -> (&'static str, &'static str) {
+    match term {
+        Term::Material => ("MATERIAL", "Piece values, in the order of `Pieces`."),
+        Term::Psqt => (
+            "PSQT",
+            "The positional part of the piece values, in the order of `Pieces`, laid\n\
+             out as a diagram with White at the bottom.",
+        ),
+        Term::Mobility => (
+            "MOBILITY",
+            "For every safe square a piece can go to, compared to how many it has on\n\
+             average, in the order of `Pieces`.",
+        ),
+        Term::KnightOutpost => (
+            "KNIGHT_OUTPOST",
+            "A minor piece in the enemy half, defended by a pawn, where no enemy pawn\n\
+             can ever chase it away.",
+        ),
+        Term::BishopOutpost => ("BISHOP_OUTPOST", ""),
+        Term::BishopPair => ("BISHOP_PAIR", ""),
+        Term::RookOpenFile => ("ROOK_OPEN_FILE", ""),
+        Term::RookSemiOpenFile => ("ROOK_SEMI_OPEN_FILE", ""),
+        Term::RookOnSeventh => (
+            "ROOK_ON_SEVENTH",
+            "only if there are pawns to eat there, or the king is cut off on the 8th",
+        ),
+        Term::TrappedRook => (
+            "TRAPPED_ROOK",
+            "a rook stuck in the corner by its own king, which can't castle any more",
+        ),
+        Term::TrappedBishop => (
+            "TRAPPED_BISHOP",
+            "a bishop which took the pawn on a7 or h7 and is cut off by b6 or g6",
+        ),
+        Term::PawnThreat => (
+            "PAWN_THREAT",
+            "for each piece attacked by a pawn, and each rook or queen attacked by a\n\
+             minor piece",
+        ),
+        Term::MinorThreat => ("MINOR_THREAT", ""),
+        Term::Passed => (
+            "PASSED",
+            "Pawns by their rank, as seen from their own side.",
+        ),
+        Term::Candidate => ("CANDIDATE", ""),
+        Term::Supported => ("SUPPORTED", "defended by another pawn"),
+        Term::Phalanx => ("PHALANX", "side by side with another pawn"),
+        Term::Doubled => ("DOUBLED", ""),
+        Term::Isolated => ("ISOLATED", ""),
+        Term::Backward => ("BACKWARD", ""),
+        Term::PassedFreePath => (
+            "PASSED_FREE_PATH",
+            "A passed pawn with nothing in the way to the promotion square, by rank.",
+        ),
+        Term::PassedOwnKingDistance => (
+            "PASSED_OWN_KING_DISTANCE",
+            "For each square of distance of the kings to the square in front of a\n\
+             passed pawn, times its rank less 2: ours is subtracted, theirs added.",
+        ),
+        Term::PassedTheirKingDistance => ("PASSED_THEIR_KING_DISTANCE", ""),
+        Term::Shield => (
+            "SHIELD",
+            "Own pawn on a file around the king, by how many ranks it is in front of\n\
+             it. 0 means there is none.",
+        ),
+        Term::Storm => (
+            "STORM",
+            "Enemy pawn coming up a file around the king, by how many ranks it is in\n\
+             front of it, and the same if one of our pawns blocks it.",
+        ),
+        Term::BlockedStorm => ("BLOCKED_STORM", ""),
+        Term::KingSemiOpenFile => (
+            "KING_SEMI_OPEN_FILE",
+            "a file around the king without our pawns, or without any",
+        ),
+        Term::KingOpenFile => ("KING_OPEN_FILE", ""),
+        Term::KingAttacker => (
+            "KING_ATTACKER",
+            "For each enemy piece attacking the squares around the king, in the order\n\
+             of `Pieces`, on top of the king danger in `king_safety.rs`. That grows\n\
+             with the square of the attacks, so it isn't tuned.",
+        ),
+    }
+}
+
+const PIECE_NAMES: [&str; 6] = ["Pawn", "Bishop", "Knight", "Rook", "Queen", "King"];
+
+// The weights as the source of `weights.rs`.
+pub fn weights_source(weights: &[Score]) -> String {
+    let mut items = vec![String::from(
+        "// The weights of the hand-crafted evaluation, for the middlegame and the\n\
+         // endgame, in centipawns. This file is written by the Texel tuner\n\
+         // (`rust_chess texel`), which is why they are all in one place. Only the\n\
+         // king danger, which isn't linear, has its own constants.\n\
+         \n\
+         use super::score::Score;\n",
+    )];
+    for term in Term::ALL {
+        let (name, comment) = describe(term);
+        let values = &weights[term.offset()..term.offset() + term.size()];
+        let mut item: String = comment
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(|line| format!("// {line}\n"))
+            .collect();
+        match term {
+            Term::Psqt => {
+                let mg: Vec<i32> = values.iter().map(|score| score.mg).collect();
+                let eg: Vec<i32> = values.iter().map(|score| score.eg).collect();
+                item += &psqt_source(&format!("{name}_MG"), &mg);
+                items.push(item);
+                item = psqt_source(&format!("{name}_EG"), &eg);
+            }
+            _ if term.size() == 1 => {
+                let score = values[0];
+                item += &format!(
+                    "pub const {name}: Score = Score::new({}, {});\n",
+                    score.mg, score.eg
+                );
+            }
+            _ => {
+                item += &format!("pub const {name}: [Score; {}] = [\n", values.len());
+                for score in values {
+                    item += &format!("    Score::new({}, {}),\n", score.mg, score.eg);
+                }
+                item += "];\n";
+            }
+        }
+        items.push(item);
+    }
+    items.join("\n")
+}
+
+fn psqt_source(name: &str, values: &[i32]) -> String {
+    let mut source = format!("#[rustfmt::skip]\npub const {name}: [[i16; 64]; 6] = [\n");
+    for (piece, table) in PIECE_NAMES.iter().zip(values.chunks(64)) {
+        source += &format!("    // {piece}\n    [\n");
+        for row in table.chunks(8) {
+            let row: Vec<String> = row
+                .iter()
+                .map(|&v| format!("{:3},", v.clamp(i16::MIN as i32, i16::MAX as i32)))
+                .collect();
+            source += &format!("        {}\n", row.join(" "));
+        }
+        source += "    ],\n";
+    }
+    source + "];\n"
+}
+
+// A position and its result from a line like those of `datagen`,
+// `<fen> | <score> | <result>`, or an EPD line with the result in
+// brackets or quotes, like `<fen> [0.5]` or `<fen> c9 "1-0";`.
+fn parse_line(line: &str) -> Option<(&str, f64)> {
+    if let Some((fen, rest)) = line.split_once('|') {
+        let result = rest.rsplit('|').next()?.trim().parse().ok()?;
+        return Some((fen.trim(), result));
+    }
+    let results = [
+        ("[1.0]", 1.0),
+        ("[0.5]", 0.5),
+        ("[0.0]", 0.0),
+        ("\"1-0\"", 1.0),
+        ("\"1/2-1/2\"", 0.5),
+        ("\"0-1\"", 0.0),
+    ];
+    let (marker, result) = results.iter().find(|(marker, _)| line.contains(marker))?;
+    let fen = line[..line.find(marker)?]
+        .trim()
+        .trim_end_matches("c9")
+        .trim();
+    Some((fen, *result))
+}
+
+fn load(text: &str, mg: &MoveGenerator, weights: &[Score]) -> Vec<Entry> {
+    let mut board = Board::new();
+    text.lines()
+        .filter_map(parse_line)
+        .filter_map(|(fen, result)| {
+            // EPD lines have no move counters
+            let fen = match fen.split_whitespace().count() {
+                4 => format!("{fen} 0 1"),
+                _ => fen.to_string(),
+            };
+            board.fen_setup(&fen).ok()?;
+            Entry::new(&board, mg, result, weights)
+        })
+        .collect()
+}
+
+// `rust_chess texel <positions> [out <weights.rs>] [epochs <n>] [rate <x>]`
+// tunes the weights on the positions and writes them as a new
+// `weights.rs`, to be copied to `src/evaluation/` and built.
+pub fn run(args: &[String]) {
+    let Some(path) = args.first() else {
+        println!("usage: texel <positions> [out <weights.rs>] [epochs <n>] [rate <x>]");
+        return;
+    };
+    let value = |name: &str| {
+        let i = args.iter().position(|arg| arg == name)?;
+        args.get(i + 1)
+    };
+    let defaults = TunerSettings::default();
+    let settings = TunerSettings {
+        epochs: value("epochs")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(defaults.epochs),
+        rate: value("rate")
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(defaults.rate),
+    };
+    let out = value("out").map_or("weights.rs", String::as_str);
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(error) => return println!("could not read {path}: {error}"),
+    };
+
+    let mg = MoveGenerator::new();
+    let weights = default_weights();
+    let entries = load(&text, &mg, &weights);
+    if entries.is_empty() {
+        return println!("no positions to tune on in {path}");
+    }
+    let start = as_f64(&weights);
+    let k = optimal_k(&entries, &start);
+    println!(
+        "{} positions, {} weights, K = {k:.3}, error {:.6}",
+        entries.len(),
+        weights.len(),
+        error(&entries, &start, k)
+    );
+    let tuned = tune(&entries, &weights, k, &settings, |epoch, error| {
+        println!("epoch {epoch}, error {error:.6}");
+    });
+    match fs::write(Path::new(out), weights_source(&tuned)) {
+        Ok(()) => println!("wrote {out}"),
+        Err(error) => println!("could not write {out}: {error}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::defs::*;
+    use crate::evaluation::Evaluator;
+    use std::sync::Arc;
+
+    fn evaluate_white(board: &Board, mg: Arc<MoveGenerator>) -> i32 {
+        let eval = Evaluator::new(mg).evaluate(board);
+        if board.we() == WHITE {
+            eval
+        } else {
+            -eval
+        }
+    }
+
+    #[test]
+    fn test_weights_source() {
+        let source = weights_source(&default_weights());
+        assert!(source == include_str!("evaluation/weights.rs"));
+    }
+
+    #[test]
+    fn test_traced_evaluation() {
+        let mg = Arc::new(MoveGenerator::new());
+        let weights = default_weights();
+        let start = as_f64(&weights);
+        for fen in [
+            "r3k2r/pPppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R b KQkq - 4 4",
+            "6k1/pp3ppp/8/3P4/2r5/8/PP3PPP/3R2K1 w - - 0 1",
+            "8/5pk1/6p1/8/3B4/8/5PPK/8 b - - 0 1",
+        ] {
+            let board = Board::fen(Some(fen.to_string()));
+            let entry = Entry::new(&board, &mg, 0.5, &weights).unwrap();
+            let traced = entry.evaluate(&start);
+            let eval = evaluate_white(&board, Arc::clone(&mg));
+            // the engine rounds down twice
+            assert!(
+                (traced - eval as f64).abs() <= 2.0,
+                "{fen}: {traced} {eval}"
+            );
+        }
+
+        // the black queen and knight attack the white king
+        let board = Board::fen(Some("6k1/8/8/8/8/5q1n/5PPP/6K1 w - - 0 1".to_string()));
+        let traced = evaluation::trace(&board, &mg).unwrap();
+        let attackers = &traced.coefficients.0[Term::KingAttacker.offset()..];
+        assert_eq!(attackers, [0, 0, -1, 0, -1, 0]);
+    }
+
+    #[test]
+    fn test_parse_line() {
+        let fen = "8/8/8/8/8/8/8/K1k5 w - - 0 1";
+        assert_eq!(parse_line(&format!("{fen} | -35 | 0.5")), Some((fen, 0.5)));
+        let epd = "8/8/8/8/8/8/8/K1k5 w - -";
+        assert_eq!(parse_line(&format!("{epd} [1.0]")), Some((epd, 1.0)));
+        assert_eq!(parse_line(&format!("{epd} c9 \"0-1\";")), Some((epd, 0.0)));
+        assert_eq!(parse_line(epd), None);
+    }
+
+    #[test]
+    fn test_tuning_lowers_the_error() {
+        let mg = MoveGenerator::new();
+        let weights = default_weights();
+        // White wins all of them, so an extra pawn should count for more
+        let text = [
+            "4k3/pp4pp/8/8/8/8/PPP3PP/4K3 w - - 0 1 | 0 | 1.0",
+            "4k3/p5pp/8/8/8/8/PP4PP/4K3 b - - 0 1 | 0 | 1.0",
+            "r3k3/pp4pp/8/8/8/8/PPP3PP/R3K3 w - - 0 1 | 0 | 1.0",
+            "4k3/pp3ppp/8/8/8/8/PP3PPP/4K3 w - - 0 1 | 0 | 0.5",
+        ]
+        .join("\n");
+        let entries = load(&text, &mg, &weights);
+        assert_eq!(entries.len(), 4);
+        let start = as_f64(&weights);
+        let k = optimal_k(&entries, &start);
+        assert!(k > 0.0);
+        let settings = TunerSettings {
+            epochs: 50,
+            rate: 2.0,
+        };
+        let tuned = tune(&entries, &weights, k, &settings, |_, _| ());
+        assert!(error(&entries, &as_f64(&tuned), k) < error(&entries, &start, k));
+    }
+}
@@ -0,0 +1,652 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+
+use crate::board::movelist::MoveList;
+use crate::board::moves::Move;
+use crate::board::Board;
+use crate::book::Book;
+use crate::defs::FEN_START_POSITION;
+use crate::engine::{Engine, Event};
+use crate::evaluation::kpk;
+use crate::evaluation::nnue::Network;
+use crate::move_generator::MoveGenerator;
+use crate::search::limits::SearchLimits;
+#[cfg(feature = "tune")]
+use crate::search::params::{spsa_input, Tunable, TUNABLES};
+use crate::search::time::{TimeControl, DEFAULT_MOVE_OVERHEAD};
+use crate::search::transposition::Bound;
+use crate::search::{SearchInfo, SearchResult, CHECKMATE, DEFAULT_HASH_MB, MATE_BOUND};
+use crate::syzygy::Tablebases;
+
+// `go` without any limit searches this deep
+const DEFAULT_DEPTH: i8 = 8;
+const MAX_HASH_MB: usize = 65536;
+const MAX_THREADS: usize = 256;
+const MAX_MULTI_PV: usize = 256;
+
+// Universal Chess Interface: reads commands from stdin and answers on
+// stdout, so the engine can be used from any chess GUI. Input is read in
+// its own thread and the search runs in another, both report to the main
+// loop through one channel. So commands are answered even while the
+// engine is thinking.
+pub struct Uci {
+    board: Board,
+    mg: Arc<MoveGenerator>,
+    engine: Engine,
+    move_overhead: u64,
+    multi_pv: usize,
+    // the evaluation network, if not the hand-crafted evaluation
+    network: Option<Arc<Network>>,
+    book: Option<Book>,
+    own_book: bool,
+    // the most played book move instead of a weighted random one
+    best_book_move: bool,
+    sender: Sender<Event>,
+    events: Receiver<Event>,
+    // commands which came in while waiting for the search to end
+    pending: VecDeque<String>,
+}
+
+impl Default for Uci {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Uci {
+    pub fn new() -> Self {
+        let mg = Arc::new(MoveGenerator::new());
+        // in the background: the first search to need it waits for it
+        thread::spawn(kpk::init);
+        let (sender, events) = mpsc::channel();
+        Self {
+            board: Board::fen(None),
+            engine: Engine::new(Arc::clone(&mg), DEFAULT_HASH_MB, sender.clone()),
+            mg,
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            multi_pv: 1,
+            network: None,
+            book: None,
+            own_book: false,
+            best_book_move: false,
+            sender,
+            events,
+            pending: VecDeque::new(),
+        }
+    }
+
+    pub fn run(&mut self) {
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let Ok(line) = line else { break };
+                if sender.send(Event::Command(line)).is_err() {
+                    return;
+                }
+            }
+            // stdin closed: the GUI is gone
+            let _ = sender.send(Event::Command(String::from("quit")));
+        });
+
+        loop {
+            let event = match self.pending.pop_front() {
+                Some(line) => Event::Command(line),
+                None => match self.events.recv() {
+                    Ok(event) => event,
+                    Err(_) => break,
+                },
+            };
+            let running = match event {
+                Event::Command(line) => self.command(&line),
+                Event::Info(info) => {
+                    println!("{}", format_info(&info));
+                    true
+                }
+                Event::BestMove(result) => {
+                    self.best_move(result);
+                    true
+                }
+            };
+            io::stdout().flush().unwrap();
+            if !running {
+                break;
+            }
+        }
+    }
+
+    fn best_move(&mut self, result: SearchResult) {
+        self.engine.join();
+        if result.ponder_move.is_null() {
+            println!("bestmove {}", result.best_move.to_uci());
+        } else {
+            println!(
+                "bestmove {} ponder {}",
+                result.best_move.to_uci(),
+                result.ponder_move.to_uci()
+            );
+        }
+    }
+
+    // Stops a running search and waits until its best move is out. Used
+    // before anything which needs the search or the position, though a
+    // GUI shouldn't send those while the engine is thinking.
+    fn wait_for_search(&mut self) {
+        if !self.engine.is_searching() {
+            return;
+        }
+        self.engine.stop();
+        while let Ok(event) = self.events.recv() {
+            match event {
+                Event::Command(line) => self.pending.push_back(line),
+                Event::Info(info) => println!("{}", format_info(&info)),
+                Event::BestMove(result) => {
+                    self.best_move(result);
+                    break;
+                }
+            }
+        }
+    }
+
+    // Handles one line of input. Returns false on `quit`.
+    fn command(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.first() {
+            Some(&"uci") => {
+                println!(
+                    "id name {} {}",
+                    env!("CARGO_PKG_NAME"),
+                    env!("CARGO_PKG_VERSION")
+                );
+                println!("id author the {} authors", env!("CARGO_PKG_NAME"));
+                println!(
+                    "option name Move Overhead type spin default {} min 0 max 5000",
+                    DEFAULT_MOVE_OVERHEAD
+                );
+                println!(
+                    "option name Hash type spin default {} min 1 max {}",
+                    DEFAULT_HASH_MB, MAX_HASH_MB
+                );
+                println!(
+                    "option name Threads type spin default 1 min 1 max {}",
+                    MAX_THREADS
+                );
+                println!(
+                    "option name MultiPV type spin default 1 min 1 max {}",
+                    MAX_MULTI_PV
+                );
+                println!("option name Ponder type check default false");
+                println!("option name SyzygyPath type string default <empty>");
+                println!("option name EvalFile type string default <empty>");
+                println!("option name OwnBook type check default false");
+                println!("option name BookFile type string default <empty>");
+                println!("option name BestBookMove type check default false");
+                #[cfg(feature = "tune")]
+                for tunable in TUNABLES {
+                    println!(
+                        "option name {} type spin default {} min {} max {}",
+                        tunable.name,
+                        tunable.get(&self.engine.params()),
+                        tunable.min,
+                        tunable.max
+                    );
+                }
+                println!("uciok");
+            }
+            Some(&"isready") => println!("readyok"),
+            // the tunable parameters as input for SPSA tools
+            #[cfg(feature = "tune")]
+            Some(&"spsa") => print!("{}", spsa_input(&self.engine.params())),
+            Some(&"ucinewgame") => {
+                self.wait_for_search();
+                self.engine.clear();
+                self.board = Board::fen(None);
+                self.board.set_network(self.network.clone());
+            }
+            Some(&"setoption") => {
+                self.wait_for_search();
+                self.set_option(&words[1..]);
+            }
+            Some(&"position") => {
+                self.wait_for_search();
+                self.position(&words[1..]);
+            }
+            Some(&"go") => {
+                self.wait_for_search();
+                self.go(&words[1..]);
+            }
+            Some(&"stop") => self.engine.stop(),
+            Some(&"ponderhit") => self.engine.ponderhit(),
+            Some(&"quit") => {
+                self.wait_for_search();
+                return false;
+            }
+            // unknown commands are ignored, as the protocol asks
+            _ => (),
+        }
+        io::stdout().flush().unwrap();
+        true
+    }
+
+    // position [startpos | fen <fen>] [moves <move> ...]
+    fn position(&mut self, args: &[&str]) {
+        let moves_at = args
+            .iter()
+            .position(|&a| a == "moves")
+            .unwrap_or(args.len());
+        let fen = match args.first() {
+            Some(&"startpos") => String::from(FEN_START_POSITION),
+            Some(&"fen") => args[1..moves_at].join(" "),
+            _ => return,
+        };
+
+        let mut board = Board::new();
+        if let Err(e) = board.fen_setup(&fen) {
+            println!("info string invalid fen: {}", e);
+            return;
+        }
+        for text in args.iter().skip(moves_at + 1) {
+            match parse_move(&mut board, &self.mg, text) {
+                Some(m) => board.make(m),
+                None => {
+                    println!("info string illegal move: {}", text);
+                    return;
+                }
+            }
+        }
+        board.set_network(self.network.clone());
+        self.board = board;
+    }
+
+    // setoption name <name> [value <value>], names may contain spaces
+    fn set_option(&mut self, args: &[&str]) {
+        let value_at = args
+            .iter()
+            .position(|&a| a == "value")
+            .unwrap_or(args.len());
+        let name = args.get(1..value_at).unwrap_or_default().join(" ");
+        let value = args.get(value_at + 1..).unwrap_or_default().join(" ");
+        match name.to_lowercase().as_str() {
+            "move overhead" => match value.parse() {
+                Ok(ms) => self.move_overhead = ms,
+                Err(_) => println!("info string invalid value: {}", value),
+            },
+            "hash" => match value.parse::<usize>() {
+                Ok(mb) => self.engine.set_hash(mb.clamp(1, MAX_HASH_MB)),
+                Err(_) => println!("info string invalid value: {}", value),
+            },
+            "threads" => match value.parse::<usize>() {
+                Ok(threads) => self.engine.set_threads(threads.clamp(1, MAX_THREADS)),
+                Err(_) => println!("info string invalid value: {}", value),
+            },
+            "multipv" => match value.parse::<usize>() {
+                Ok(lines) => self.multi_pv = lines.clamp(1, MAX_MULTI_PV),
+                Err(_) => println!("info string invalid value: {}", value),
+            },
+            // the GUI decides when to ponder, there is nothing to set
+            "ponder" => (),
+            "syzygypath" => {
+                let tb = (!value.is_empty() && value != "<empty>").then(|| Tablebases::new(&value));
+                if let Some(tb) = &tb {
+                    println!(
+                        "info string found tablebases up to {} pieces",
+                        tb.max_pieces()
+                    );
+                }
+                self.engine.set_tablebases(tb.map(Arc::new));
+            }
+            "evalfile" => {
+                self.network = None;
+                if !value.is_empty() && value != "<empty>" {
+                    match Network::load(Path::new(&value)) {
+                        Ok(network) => self.network = Some(Arc::new(network)),
+                        Err(e) => println!(
+                            "info string could not read network, using the hand-crafted evaluation: {}",
+                            e
+                        ),
+                    }
+                }
+                self.board.set_network(self.network.clone());
+            }
+            "ownbook" => self.own_book = value == "true",
+            "bookfile" => {
+                self.book = None;
+                if !value.is_empty() && value != "<empty>" {
+                    match Book::open(Path::new(&value)) {
+                        Ok(book) => {
+                            println!("info string book with {} entries", book.len());
+                            self.book = Some(book);
+                        }
+                        Err(e) => println!("info string could not read book: {}", e),
+                    }
+                }
+            }
+            "bestbookmove" => self.best_book_move = value == "true",
+            #[cfg(feature = "tune")]
+            _ if Tunable::find(&name).is_some() => match value.parse() {
+                Ok(value) => {
+                    let mut params = self.engine.params();
+                    Tunable::find(&name).unwrap().set(&mut params, value);
+                    self.engine.set_params(params);
+                }
+                Err(_) => println!("info string invalid value: {}", value),
+            },
+            _ => println!("info string unknown option: {}", name),
+        }
+    }
+
+    fn go(&mut self, args: &[&str]) {
+        if let Some(depth) = parse_arg::<u8>(args, "perft") {
+            self.perft(depth.max(1));
+            return;
+        }
+        let mut limits = parse_go(&mut self.board, &self.mg, args);
+        // a GUI which asks for an analysis wants one
+        let analysis = limits.infinite || limits.ponder || !limits.search_moves.is_empty();
+        if let Some(m) = self.book_move().filter(|_| !analysis) {
+            println!("bestmove {}", m.to_uci());
+            return;
+        }
+        limits.move_overhead = self.move_overhead;
+        limits.multi_pv = self.multi_pv;
+        self.engine.start(&self.board, limits);
+    }
+
+    // go perft <depth>: the leaf nodes below each legal move, to compare
+    // the move generation with other engines.
+    fn perft(&mut self, depth: u8) {
+        let mut moves = MoveList::new();
+        self.board.get_legal_moves(&self.mg, &mut moves);
+        let mut total = 0;
+        for m in moves.iter() {
+            self.board.make(*m);
+            let nodes = self.board.perft(&self.mg, depth - 1);
+            self.board.unmake();
+            println!("{}: {}", m.to_uci(), nodes);
+            total += nodes;
+        }
+        println!("\nNodes searched: {}", total);
+    }
+
+    fn book_move(&mut self) -> Option<Move> {
+        let book = self.book.as_ref().filter(|_| self.own_book)?;
+        book.probe(&mut self.board, &self.mg, self.best_book_move)
+    }
+}
+
+// go [searchmoves <move> ...] [ponder] [wtime <ms>] [btime <ms>] [winc <ms>]
+//    [binc <ms>] [movestogo <n>] [depth <n>] [nodes <n>] [mate <n>]
+//    [movetime <ms>] [infinite]
+pub fn parse_go(board: &mut Board, mg: &MoveGenerator, args: &[&str]) -> SearchLimits {
+    let mut limits = SearchLimits {
+        depth: parse_arg(args, "depth"),
+        nodes: parse_arg(args, "nodes"),
+        mate: parse_arg(args, "mate"),
+        time: TimeControl {
+            wtime: parse_arg(args, "wtime"),
+            btime: parse_arg(args, "btime"),
+            winc: parse_arg(args, "winc"),
+            binc: parse_arg(args, "binc"),
+            movestogo: parse_arg(args, "movestogo"),
+            movetime: parse_arg(args, "movetime"),
+        },
+        infinite: args.contains(&"infinite"),
+        ponder: args.contains(&"ponder"),
+        ..Default::default()
+    };
+    if let Some(i) = args.iter().position(|&a| a == "searchmoves") {
+        // the moves run until the next keyword
+        limits.search_moves = args[i + 1..]
+            .iter()
+            .map_while(|text| parse_move(board, mg, text))
+            .collect();
+    }
+
+    let unlimited = limits.depth.is_none()
+        && limits.nodes.is_none()
+        && limits.mate.is_none()
+        && !limits.time.is_set()
+        && !limits.infinite
+        && !limits.ponder;
+    if unlimited {
+        limits.depth = Some(DEFAULT_DEPTH);
+    }
+    limits
+}
+
+// The value following `name` in the argument list, e.g. `wtime 1000`.
+fn parse_arg<T: std::str::FromStr>(args: &[&str], name: &str) -> Option<T> {
+    let i = args.iter().position(|&a| a == name)?;
+    args.get(i + 1)?.parse().ok()
+}
+
+// Finds the legal move written in UCI notation, e.g. `e1g1` or `a7a8q`.
+pub fn parse_move(board: &mut Board, mg: &MoveGenerator, text: &str) -> Option<Move> {
+    let mut moves = MoveList::new();
+    board.get_legal_moves(mg, &mut moves);
+    moves.iter().copied().find(|m| m.to_uci() == text)
+}
+
+pub fn format_info(info: &SearchInfo) -> String {
+    let millis = info.time.as_millis() as u64;
+    let nps = info.nodes * 1000 / millis.max(1);
+    let mut text = format!(
+        "info depth {} seldepth {} multipv {} score {} nodes {} nps {} tbhits {} hashfull {} time {}",
+        info.depth,
+        info.seldepth,
+        info.multipv,
+        format_score(info.score, info.bound),
+        info.nodes,
+        nps,
+        info.tbhits,
+        info.hashfull,
+        millis
+    );
+    // a failed aspiration window may not have found a line yet
+    if !info.pv.is_empty() {
+        let pv: Vec<String> = info.pv.iter().map(|m| m.to_uci()).collect();
+        text.push_str(&format!(" pv {}", pv.join(" ")));
+    }
+    text
+}
+
+// Mate scores are given in moves, not plies, negative when being mated.
+fn format_score(score: i32, bound: Bound) -> String {
+    let mut text = if score > MATE_BOUND {
+        format!("mate {}", (CHECKMATE - score + 1) / 2)
+    } else if score < -MATE_BOUND {
+        format!("mate {}", -(CHECKMATE + score) / 2)
+    } else {
+        format!("cp {}", score)
+    };
+    match bound {
+        Bound::Lower => text.push_str(" lowerbound"),
+        Bound::Upper => text.push_str(" upperbound"),
+        Bound::Exact => (),
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position_with_moves() {
+        let mut uci = Uci::new();
+        uci.command("position startpos moves e2e4 e7e5 g1f3 b8c6 f1c4 g8f6 e1g1");
+        let expected = "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQ1RK1 b kq - 5 4";
+        assert_eq!(uci.board, Board::fen(Some(expected.to_string())));
+
+        uci.command("position fen 8/P7/8/8/8/8/8/k6K w - - 0 1 moves a7a8n");
+        assert_eq!(
+            uci.board,
+            Board::fen(Some("N7/8/8/8/8/8/8/k6K b - - 0 1".to_string()))
+        );
+    }
+
+    // Waits for the search started by `go` to report its best move.
+    fn best_move(uci: &mut Uci) -> SearchResult {
+        let timeout = std::time::Duration::from_secs(30);
+        loop {
+            match uci.events.recv_timeout(timeout).expect("no best move") {
+                Event::BestMove(result) => {
+                    uci.engine.join();
+                    return result;
+                }
+                Event::Info(_) | Event::Command(_) => (),
+            }
+        }
+    }
+
+    #[test]
+    fn test_search_runs_in_the_background() {
+        let mut uci = Uci::new();
+        uci.command("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        uci.command("go depth 3");
+        assert_eq!(best_move(&mut uci).best_move.to_uci(), "a1a8");
+
+        // an infinite search only ends on `stop`
+        uci.command("position startpos");
+        uci.command("go infinite");
+        thread::sleep(std::time::Duration::from_millis(50));
+        assert!(uci.engine.is_searching());
+        uci.command("stop");
+        assert!(!best_move(&mut uci).best_move.is_null());
+
+        // a ponder search waits for `ponderhit`, then plays on the clock
+        uci.command("go ponder movetime 50 depth 2");
+        thread::sleep(std::time::Duration::from_millis(100));
+        assert!(uci
+            .events
+            .try_iter()
+            .all(|e| !matches!(e, Event::BestMove(_))));
+        uci.command("ponderhit");
+        assert!(!best_move(&mut uci).best_move.is_null());
+    }
+
+    #[test]
+    fn test_parallel_search() {
+        let mut uci = Uci::new();
+        uci.command("setoption name Threads value 3");
+        // the helpers share the new table
+        uci.command("setoption name Hash value 1");
+        uci.command("position fen 6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1");
+        uci.command("go depth 5");
+        assert_eq!(best_move(&mut uci).best_move.to_uci(), "a1a8");
+
+        uci.command("position startpos");
+        uci.command("go movetime 100");
+        let result = best_move(&mut uci);
+        assert!(!result.best_move.is_null());
+        assert!(result.depth > 1);
+    }
+
+    #[test]
+    fn test_perft_keeps_position() {
+        let mut uci = Uci::new();
+        uci.command("position startpos moves e2e4");
+        let fen = uci.board.to_fen();
+        uci.command("go perft 3");
+        assert!(!uci.engine.is_searching());
+        assert_eq!(uci.board.to_fen(), fen);
+    }
+
+    #[test]
+    fn test_illegal_move_keeps_position() {
+        let mut uci = Uci::new();
+        uci.command("position startpos moves e2e5");
+        assert_eq!(uci.board, Board::fen(None));
+    }
+
+    #[test]
+    fn test_set_option_and_parse_arg() {
+        let mut uci = Uci::new();
+        uci.command("setoption name Move Overhead value 250");
+        assert_eq!(uci.move_overhead, 250);
+        uci.command("setoption name Threads value 4");
+        assert_eq!(uci.engine.threads(), 4);
+
+        let args = ["wtime", "1000", "movestogo", "x", "binc"];
+        assert_eq!(parse_arg::<u64>(&args, "wtime"), Some(1000));
+        assert_eq!(parse_arg::<u64>(&args, "movestogo"), None);
+        assert_eq!(parse_arg::<u64>(&args, "binc"), None);
+        assert_eq!(parse_arg::<u64>(&args, "btime"), None);
+    }
+
+    #[cfg(feature = "tune")]
+    #[test]
+    fn test_tunable_options() {
+        let mut uci = Uci::new();
+        uci.command("setoption name Threads value 2");
+        uci.command("setoption name AspWindow value 40");
+        uci.command("setoption name lmrdivisor value 250");
+        let params = uci.engine.params();
+        assert_eq!(params.asp_window, 40);
+        assert_eq!(params.lmr_divisor, 2.5);
+        // the helpers search with them too
+        uci.command("setoption name Threads value 3");
+        assert_eq!(uci.engine.params().asp_window, 40);
+    }
+
+    #[test]
+    fn test_parse_go() {
+        let mg = MoveGenerator::new();
+        let mut board = Board::fen(None);
+        let args: Vec<&str> = "searchmoves e2e4 d2d4 wtime 1000 nodes 500 mate 3 ponder"
+            .split(' ')
+            .collect();
+        let limits = parse_go(&mut board, &mg, &args);
+        let moves: Vec<String> = limits.search_moves.iter().map(|m| m.to_uci()).collect();
+        assert_eq!(moves, ["e2e4", "d2d4"]);
+        assert_eq!(limits.time.wtime, Some(1000));
+        assert_eq!(limits.nodes, Some(500));
+        assert_eq!(limits.mate, Some(3));
+        assert!(limits.ponder && !limits.infinite);
+        assert_eq!(limits.depth, None);
+
+        assert_eq!(parse_go(&mut board, &mg, &["infinite"]).depth, None);
+        assert_eq!(parse_go(&mut board, &mg, &[]).depth, Some(DEFAULT_DEPTH));
+    }
+
+    #[test]
+    fn test_format_score() {
+        assert_eq!(format_score(35, Bound::Exact), "cp 35");
+        assert_eq!(format_score(-12, Bound::Lower), "cp -12 lowerbound");
+        assert_eq!(format_score(CHECKMATE - 1, Bound::Exact), "mate 1");
+        assert_eq!(
+            format_score(CHECKMATE - 3, Bound::Upper),
+            "mate 2 upperbound"
+        );
+        assert_eq!(format_score(-CHECKMATE + 2, Bound::Exact), "mate -1");
+    }
+
+    #[test]
+    fn test_format_info() {
+        let mut info = SearchInfo {
+            multipv: 1,
+            depth: 5,
+            seldepth: 9,
+            score: 40,
+            bound: Bound::Upper,
+            nodes: 2000,
+            tbhits: 0,
+            hashfull: 3,
+            time: std::time::Duration::from_millis(10),
+            pv: Vec::new(),
+        };
+        assert_eq!(
+            format_info(&info),
+            "info depth 5 seldepth 9 multipv 1 score cp 40 upperbound nodes 2000 nps 200000 tbhits 0 hashfull 3 time 10"
+        );
+        let mut board = Board::fen(None);
+        let mg = MoveGenerator::new();
+        info.pv = vec![parse_move(&mut board, &mg, "e2e4").unwrap()];
+        info.bound = Bound::Exact;
+        assert!(format_info(&info)
+            .ends_with("score cp 40 nodes 2000 nps 200000 tbhits 0 hashfull 3 time 10 pv e2e4"));
+    }
+}